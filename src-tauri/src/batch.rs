@@ -0,0 +1,185 @@
+//! Overall progress of a batch of conversions.
+//!
+//! The frontend runs the queue, but registers each batch here first with the
+//! duration of every job. Progress of the individual tasks is then folded into
+//! one duration-weighted percentage, sent as `batch-progress`, so long files
+//! move the master bar more than short ones.
+
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use tauri::Emitter;
+
+/// Weight given to jobs of unknown or zero duration, in seconds.
+const MIN_JOB_WEIGHT: f64 = 1.0;
+
+lazy_static! {
+    static ref BATCHES: Mutex<HashMap<String, Batch>> = Mutex::new(HashMap::new());
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct BatchJob {
+    pub task_id: String,
+    /// Output duration in seconds, from the probe
+    pub duration: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BatchProgress {
+    pub batch_id: String,
+    pub percent: f64,
+    /// Position in the batch of the job that just reported
+    pub current_index: usize,
+    /// Jobs that have ended, whatever the outcome
+    pub finished: usize,
+    pub total: usize,
+}
+
+struct Batch {
+    jobs: Vec<BatchJob>,
+    percents: Vec<f64>,
+    finished: Vec<bool>,
+}
+
+impl Batch {
+    fn new(jobs: Vec<BatchJob>) -> Self {
+        let count = jobs.len();
+        Self {
+            jobs,
+            percents: vec![0.0; count],
+            finished: vec![false; count],
+        }
+    }
+
+    fn position(&self, task_id: &str) -> Option<usize> {
+        self.jobs.iter().position(|j| j.task_id == task_id)
+    }
+
+    fn percent(&self) -> f64 {
+        let weight = |job: &BatchJob| job.duration.max(MIN_JOB_WEIGHT);
+        let total: f64 = self.jobs.iter().map(weight).sum();
+        if total <= 0.0 {
+            return 0.0;
+        }
+        let done: f64 = self
+            .jobs
+            .iter()
+            .zip(&self.percents)
+            .map(|(job, percent)| weight(job) * percent / 100.0)
+            .sum();
+        (done / total * 100.0).min(100.0)
+    }
+
+    fn progress(&self, batch_id: &str, index: usize) -> BatchProgress {
+        BatchProgress {
+            batch_id: batch_id.to_string(),
+            percent: self.percent(),
+            current_index: index,
+            finished: self.finished.iter().filter(|f| **f).count(),
+            total: self.jobs.len(),
+        }
+    }
+
+    fn is_done(&self) -> bool {
+        self.finished.iter().all(|f| *f)
+    }
+}
+
+/// Register a batch; replaces an earlier one with the same id.
+pub fn start(batch_id: &str, jobs: Vec<BatchJob>) {
+    tracing::info!(batch_id = %batch_id, jobs = jobs.len(), "Batch started");
+    with_batches(|batches| {
+        batches.insert(batch_id.to_string(), Batch::new(jobs));
+    });
+}
+
+/// Forget a batch whose remaining jobs won't run (queue stopped).
+pub fn end(batch_id: &str) {
+    with_batches(|batches| {
+        batches.remove(batch_id);
+    });
+}
+
+/// Record a task's progress and emit the batch's overall progress.
+pub fn report_progress(window: &tauri::WebviewWindow, task_id: &str, percent: f64) {
+    let progress = with_batches(|batches| update(batches, task_id, Some(percent)));
+    if let Some(progress) = progress {
+        let _ = window.emit("batch-progress", &progress);
+    }
+}
+
+/// Mark a task as ended (completed, failed or cancelled) so it counts as fully
+/// processed. The batch is dropped once all its jobs have ended.
+pub fn finish_task(window: &tauri::WebviewWindow, task_id: &str) {
+    let progress = with_batches(|batches| update(batches, task_id, None));
+    if let Some(progress) = progress {
+        let _ = window.emit("batch-progress", &progress);
+    }
+}
+
+/// `None` as `percent` finishes the task.
+fn update(
+    batches: &mut HashMap<String, Batch>,
+    task_id: &str,
+    percent: Option<f64>,
+) -> Option<BatchProgress> {
+    let (batch_id, batch, index) = batches
+        .iter_mut()
+        .find_map(|(id, batch)| batch.position(task_id).map(|i| (id.clone(), batch, i)))?;
+
+    match percent {
+        Some(percent) => batch.percents[index] = percent.clamp(0.0, 100.0),
+        None => {
+            batch.percents[index] = 100.0;
+            batch.finished[index] = true;
+        }
+    }
+    let progress = batch.progress(&batch_id, index);
+
+    if batch.is_done() {
+        tracing::info!(batch_id = %batch_id, "Batch finished");
+        batches.remove(&batch_id);
+    }
+    Some(progress)
+}
+
+fn with_batches<T>(f: impl FnOnce(&mut HashMap<String, Batch>) -> T) -> T {
+    f(&mut BATCHES.lock().unwrap_or_else(|e| e.into_inner()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn job(task_id: &str, duration: f64) -> BatchJob {
+        BatchJob {
+            task_id: task_id.to_string(),
+            duration,
+        }
+    }
+
+    #[test]
+    fn test_duration_weighted_progress() {
+        let mut batches = HashMap::new();
+        batches.insert(
+            "b".to_string(),
+            Batch::new(vec![job("long", 300.0), job("short", 100.0)]),
+        );
+
+        // Half of the long file is 150 of 400 seconds
+        let progress = update(&mut batches, "long", Some(50.0)).unwrap();
+        assert_eq!(progress.percent, 37.5);
+        assert_eq!((progress.current_index, progress.finished), (0, 0));
+
+        let progress = update(&mut batches, "short", None).unwrap();
+        assert_eq!(progress.percent, 62.5);
+        assert_eq!((progress.current_index, progress.finished), (1, 1));
+        assert!(update(&mut batches, "unknown", Some(10.0)).is_none());
+
+        // Done once every job has ended; the batch is then forgotten
+        let progress = update(&mut batches, "long", None).unwrap();
+        assert_eq!((progress.percent, progress.finished), (100.0, 2));
+        assert!(batches.is_empty());
+    }
+}
@@ -1,5 +1,6 @@
 use crate::error::{AppError, AppResult, ErrorCode};
-use std::path::PathBuf;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
 use tauri::AppHandle;
 use tauri::Manager;
 
@@ -128,3 +129,108 @@ pub fn check_binaries(app: &AppHandle) -> AppResult<bool> {
         ))
     }
 }
+
+/// Version and build configuration of the FFmpeg binaries in use.
+#[derive(Debug, Clone, Serialize)]
+pub struct FfmpegInfo {
+    pub ffmpeg_version: String,
+    pub ffprobe_version: Option<String>,
+    /// External libraries compiled in, e.g. `libx264`, `libvpx`
+    pub enabled_libraries: Vec<String>,
+    /// Every `./configure` flag the build was made with
+    pub configuration: Vec<String>,
+}
+
+/// Run `ffmpeg -version` / `-buildconf` (and `ffprobe -version`) and parse them.
+pub async fn get_ffmpeg_info(app: &AppHandle) -> AppResult<FfmpegInfo> {
+    let ffmpeg = get_ffmpeg_path(app)?;
+    let version = run_for_output(&ffmpeg, "-version").await?;
+    let buildconf = run_for_output(&ffmpeg, "-buildconf").await?;
+
+    let ffprobe_version = match get_ffprobe_path(app) {
+        Ok(ffprobe) => run_for_output(&ffprobe, "-version")
+            .await
+            .ok()
+            .and_then(|out| parse_version(&out)),
+        Err(_) => None,
+    };
+
+    let configuration = parse_configuration(&buildconf);
+    Ok(FfmpegInfo {
+        ffmpeg_version: parse_version(&version).unwrap_or_else(|| "unknown".to_string()),
+        ffprobe_version,
+        enabled_libraries: enabled_libraries(&configuration),
+        configuration,
+    })
+}
+
+async fn run_for_output(binary: &Path, flag: &str) -> AppResult<String> {
+    let output = crate::utils::create_async_hidden_command(binary.to_str().unwrap_or("ffmpeg"))
+        .args(["-hide_banner", flag])
+        .output()
+        .await
+        .map_err(|e| {
+            AppError::new(ErrorCode::BinaryNotFound, "Failed to run FFmpeg")
+                .with_details(e.to_string())
+        })?;
+
+    if !output.status.success() {
+        return Err(AppError::new(
+            ErrorCode::BinaryNotFound,
+            format!("FFmpeg {} failed with code: {}", flag, output.status),
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// `6.1.1` from `ffmpeg version 6.1.1 Copyright (c) ...`.
+fn parse_version(output: &str) -> Option<String> {
+    output
+        .lines()
+        .find_map(|line| line.split_once(" version "))
+        .and_then(|(_, rest)| rest.split_whitespace().next())
+        .map(str::to_string)
+}
+
+/// The `--flag` entries of `-buildconf` output, one per line.
+fn parse_configuration(output: &str) -> Vec<String> {
+    output
+        .split_whitespace()
+        .filter(|token| token.starts_with("--"))
+        .map(str::to_string)
+        .collect()
+}
+
+fn enabled_libraries(configuration: &[String]) -> Vec<String> {
+    configuration
+        .iter()
+        .filter_map(|flag| flag.strip_prefix("--enable-"))
+        .filter(|name| name.starts_with("lib"))
+        .map(str::to_string)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_ffmpeg_info() {
+        let version = "ffmpeg version 6.1.1-static https://johnvansickle.com/ffmpeg/  Copyright (c) 2000-2023 the FFmpeg developers\nbuilt with gcc 8 (Debian 8.3.0-6)";
+        assert_eq!(parse_version(version).as_deref(), Some("6.1.1-static"));
+        assert_eq!(parse_version("garbage"), None);
+
+        let buildconf = "  configuration:\n    --enable-gpl\n    --enable-libx264\n    --enable-libvpx\n    --disable-debug\n";
+        let configuration = parse_configuration(buildconf);
+        assert_eq!(
+            configuration,
+            [
+                "--enable-gpl",
+                "--enable-libx264",
+                "--enable-libvpx",
+                "--disable-debug"
+            ]
+        );
+        assert_eq!(enabled_libraries(&configuration), ["libx264", "libvpx"]);
+    }
+}
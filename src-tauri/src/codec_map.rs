@@ -6,7 +6,15 @@
 //! - Codec + GPU vendor → GPU encoder name
 //! - Codec type → software encoder name
 //! - GPU encoder name → software fallback
-//! - GPU encoder detection
+//! - GPU encoder detection (NVENC, QSV, AMF, VideoToolbox, VAAPI)
+//! - Encoder frame-structure capabilities (B-frames, reference frames)
+//! - Constant-quality option and accepted range per encoder
+//! - Intermediate (ProRes/DNxHR) profile per quality preset
+
+use crate::types::Quality;
+
+/// Upper bound accepted for `-bf` / `-refs`. Encoders clamp or reject anything beyond this.
+pub const MAX_FRAME_STRUCTURE_COUNT: u32 = 16;
 
 /// Get the GPU encoder name for a given codec type and GPU vendor.
 ///
@@ -33,6 +41,12 @@ pub fn gpu_encoder_for_codec(codec: &str, vendor: &str) -> Option<&'static str>
         ("h264", "apple") => Some("h264_videotoolbox"),
         ("hevc", "apple") => Some("hevc_videotoolbox"),
 
+        // VAAPI (Linux, Intel/AMD via Mesa or intel-media-driver)
+        ("h264", "vaapi") => Some("h264_vaapi"),
+        ("hevc", "vaapi") => Some("hevc_vaapi"),
+        ("vp9", "vaapi") => Some("vp9_vaapi"),
+        ("av1", "vaapi") => Some("av1_vaapi"),
+
         _ => None,
     }
 }
@@ -53,6 +67,9 @@ pub fn software_encoder_for_codec(codec: &str) -> Option<&'static str> {
         "vp8" => Some("libvpx"),
         "av1" => Some("libaom-av1"),
         "theora" => Some("libtheora"),
+        "webp" => Some("libwebp"),
+        "prores" => Some("prores_ks"),
+        "jxl" => Some("libjxl"),
         _ => None,
     }
 }
@@ -63,6 +80,12 @@ pub fn is_gpu_encoder(name: &str) -> bool {
         || name.contains("qsv")
         || name.contains("amf")
         || name.contains("videotoolbox")
+        || is_vaapi_encoder(name)
+}
+
+/// Check if an encoder is a VAAPI encoder, which takes frames in GPU memory.
+pub fn is_vaapi_encoder(name: &str) -> bool {
+    name.ends_with("_vaapi")
 }
 
 /// Get the software fallback for a GPU encoder.
@@ -86,10 +109,303 @@ pub fn software_fallback_for_encoder(encoder: &str) -> Option<&'static str> {
     }
 }
 
+/// Check if a software encoder honours an explicit B-frame count (`-bf`).
+///
+/// Hardware encoders are excluded on purpose: each vendor enforces its own
+/// (often generation-specific) limit and rejects values outside it.
+pub fn supports_b_frames(encoder: &str) -> bool {
+    matches!(
+        encoder,
+        "libx264" | "libx265" | "mpeg4" | "libxvid" | "mpeg1video" | "mpeg2video"
+    )
+}
+
+/// Check if a software encoder honours an explicit reference frame count (`-refs`).
+pub fn supports_ref_frames(encoder: &str) -> bool {
+    matches!(encoder, "libx264" | "libx265")
+}
+
+/// x264/x265 speed presets, fastest first.
+pub const X26X_PRESETS: [&str; 9] = [
+    "ultrafast",
+    "superfast",
+    "veryfast",
+    "faster",
+    "fast",
+    "medium",
+    "slow",
+    "slower",
+    "veryslow",
+];
+/// NVENC presets, p1 (fastest) to p7 (best quality).
+pub const NVENC_PRESETS: [&str; 7] = ["p1", "p2", "p3", "p4", "p5", "p6", "p7"];
+
+/// Speed presets an encoder accepts for `-preset`, or `None` if it has no
+/// comparable speed/quality trade-off to pick.
+pub fn encoder_presets(encoder: &str) -> Option<&'static [&'static str]> {
+    match encoder {
+        "libx264" | "libx265" => Some(&X26X_PRESETS),
+        e if e.contains("nvenc") => Some(&NVENC_PRESETS),
+        _ => None,
+    }
+}
+
+/// Slice counts accepted by FFV1 version 3.
+pub const FFV1_SLICE_COUNTS: [u32; 7] = [4, 6, 9, 12, 16, 24, 30];
+
+/// Pixel format an encoder must be fed, if it only accepts one.
+///
+/// v210 is by definition 10-bit 4:2:2 packed.
+pub fn required_pixel_format(encoder: &str) -> Option<&'static str> {
+    match encoder {
+        "v210" => Some("yuv422p10le"),
+        _ => None,
+    }
+}
+
+/// Pixel formats an encoder accepts, for the encoders commonly picked by
+/// hand. `None` means unknown: FFmpeg converts or reports it.
+pub fn supported_pixel_formats(encoder: &str) -> Option<&'static [&'static str]> {
+    let formats: &'static [&'static str] = match encoder {
+        "libx264" => &[
+            "yuv420p",
+            "yuvj420p",
+            "yuv422p",
+            "yuv444p",
+            "nv12",
+            "yuv420p10le",
+            "yuv422p10le",
+            "yuv444p10le",
+        ],
+        "libx265" => &[
+            "yuv420p",
+            "yuv422p",
+            "yuv444p",
+            "yuv420p10le",
+            "yuv422p10le",
+            "yuv444p10le",
+            "yuv420p12le",
+            "gray",
+        ],
+        "libvpx-vp9" | "libaom-av1" => &[
+            "yuv420p",
+            "yuv422p",
+            "yuv444p",
+            "yuv420p10le",
+            "yuv422p10le",
+            "yuv444p10le",
+        ],
+        "libsvtav1" => &["yuv420p", "yuv420p10le"],
+        "libvpx" | "mpeg4" | "libxvid" | "libtheora" => &["yuv420p"],
+        "h264_nvenc" => &["yuv420p", "nv12", "yuv444p", "p010le"],
+        "hevc_nvenc" => &["yuv420p", "nv12", "yuv444p", "p010le", "yuv444p16le"],
+        "av1_nvenc" => &["yuv420p", "nv12", "p010le"],
+        e if e.contains("amf") => &["nv12", "p010le"],
+        e if e.contains("qsv") => &["nv12", "p010le"],
+        e if e.starts_with("prores") => &["yuv422p10le", "yuv444p10le"],
+        "dnxhd" => &["yuv422p", "yuv422p10le", "yuv444p10le"],
+        "v210" => &["yuv422p10le"],
+        _ => return None,
+    };
+    Some(formats)
+}
+
+/// Whether `pix_fmt` is 8-bit 4:2:0, the only layout every TV and phone
+/// hardware decoder handles.
+pub fn is_widely_playable_pixel_format(pix_fmt: &str) -> bool {
+    matches!(pix_fmt, "yuv420p" | "yuvj420p" | "nv12")
+}
+
+/// Encoders writing a still-image format, best first. libjxl is missing from
+/// many FFmpeg builds; AVIF falls back to SVT-AV1 without libaom.
+pub fn still_image_encoders(extension: &str) -> &'static [&'static str] {
+    match extension {
+        "avif" => &["libaom-av1", "libsvtav1"],
+        "jxl" => &["libjxl"],
+        _ => &[],
+    }
+}
+
+/// Check if an encoder is marked experimental and needs `-strict experimental`.
+///
+/// FFmpeg's native Opus and Vorbis encoders are the usual fallbacks when the
+/// external libraries are missing; `dca` is the only DTS encoder.
+pub fn is_experimental_encoder(encoder: &str) -> bool {
+    matches!(encoder, "opus" | "vorbis" | "dca")
+}
+
+/// Surround formats home-theatre receivers decode themselves, so they are
+/// worth keeping untouched rather than re-encoding.
+pub const PASSTHROUGH_AUDIO_CODECS: [&str; 3] = ["ac3", "eac3", "dts"];
+
+/// Whether `codec` (a probed codec or an encoder name) is the container
+/// codec `container_codec`. The surround codecs are matched exactly: E-AC-3
+/// contains "ac3" but isn't playable where only AC-3 is, and DTS is encoded
+/// by `dca`.
+pub fn audio_codec_matches(container_codec: &str, codec: &str) -> bool {
+    if container_codec == codec {
+        return true;
+    }
+
+    match container_codec {
+        "ac3" | "eac3" => false,
+        "dts" => codec == "dca",
+        "aac" => codec.starts_with("aac"),
+        _ => !PASSTHROUGH_AUDIO_CODECS.contains(&codec) && codec.contains(container_codec),
+    }
+}
+
+/// Sample rate an audio encoder must be fed, if it only accepts one.
+pub fn required_sample_rate(encoder: &str) -> Option<u32> {
+    match encoder {
+        "opus" => Some(48000),
+        _ => None,
+    }
+}
+
+/// Check if an encoder is lossless and should keep the source pixel format
+/// instead of the container's (usually 8-bit 4:2:0) default.
+pub fn preserves_source_pixel_format(encoder: &str) -> bool {
+    matches!(encoder, "ffv1" | "rawvideo")
+}
+
+/// Profile an intermediate (editing) encoder runs with for a quality preset.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct IntermediateProfile {
+    /// `-profile:v` value
+    pub profile: &'static str,
+    /// The only layout the profile encodes
+    pub pixel_format: &'static str,
+    /// Nominal data rate, in bits per pixel of each frame
+    pub bits_per_pixel: f64,
+}
+
+/// Check if an encoder makes editing intermediates (ProRes, DNxHR), whose
+/// data rate is set by the profile rather than a bitrate or quality target.
+pub fn is_intermediate_encoder(encoder: &str) -> bool {
+    encoder.starts_with("prores") || encoder == "dnxhd"
+}
+
+/// ProRes Proxy/422/HQ/4444 or DNxHR LB/SQ/HQ/HQX for `quality`. DNxHD's
+/// fixed-bitrate profiles only take broadcast frame sizes, so DNxHR is used.
+///
+/// Returns `None` for encoders that aren't intermediates.
+pub fn intermediate_profile(encoder: &str, quality: Quality) -> Option<IntermediateProfile> {
+    let (profile, pixel_format, bits_per_pixel) = match (encoder, quality) {
+        (e, Quality::Low) if e.starts_with("prores") => ("0", "yuv422p10le", 0.72),
+        (e, Quality::High) if e.starts_with("prores") => ("3", "yuv422p10le", 3.54),
+        (e, Quality::Ultra) if e.starts_with("prores") => ("4", "yuv444p10le", 5.31),
+        (e, _) if e.starts_with("prores") => ("2", "yuv422p10le", 2.37),
+        ("dnxhd", Quality::Low) => ("dnxhr_lb", "yuv422p", 0.72),
+        ("dnxhd", Quality::High) => ("dnxhr_hq", "yuv422p", 3.54),
+        ("dnxhd", Quality::Ultra) => ("dnxhr_hqx", "yuv422p10le", 3.54),
+        ("dnxhd", _) => ("dnxhr_sq", "yuv422p", 2.33),
+        _ => return None,
+    };
+    Some(IntermediateProfile {
+        profile,
+        pixel_format,
+        bits_per_pixel,
+    })
+}
+
+/// Constant-quality option an encoder takes and the values it accepts (lower = better).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct QualityScale {
+    pub option: &'static str,
+    pub min: u32,
+    pub max: u32,
+}
+
+impl QualityScale {
+    pub fn clamp(&self, value: u32) -> u32 {
+        value.clamp(self.min, self.max)
+    }
+}
+
+/// Constant-quality scale for an encoder. Hardware encoders reject (or silently
+/// ignore) values outside their range, each with its own option name.
+///
+/// Returns `None` for encoders without a comparable scale (VideoToolbox's
+/// `-q:v` runs the other way, bitrate-only encoders have none).
+pub fn quality_scale(encoder: &str) -> Option<QualityScale> {
+    let (option, min, max) = match encoder {
+        e if e.contains("nvenc") => ("-cq", 0, 51),
+        e if e.contains("qsv") => ("-global_quality", 1, 51),
+        "av1_amf" => ("-qp_i", 0, 255),
+        e if e.contains("amf") => ("-qp_i", 0, 51),
+        "av1_vaapi" | "vp9_vaapi" => ("-qp", 0, 255),
+        e if is_vaapi_encoder(e) => ("-qp", 0, 51),
+        "libx264" | "libx265" => ("-crf", 0, 51),
+        "libvpx" => ("-crf", 4, 63),
+        "libvpx-vp9" | "libaom-av1" | "libsvtav1" => ("-crf", 0, 63),
+        _ => return None,
+    };
+    Some(QualityScale { option, min, max })
+}
+
+/// Check if an encoder supports two-pass bitrate targeting.
+pub fn supports_two_pass(encoder: &str) -> bool {
+    matches!(encoder, "libx264" | "libx265" | "libvpx-vp9")
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_audio_codec_matches() {
+        assert!(audio_codec_matches("ac3", "ac3"));
+        assert!(!audio_codec_matches("ac3", "eac3"));
+        assert!(audio_codec_matches("dts", "dca"));
+        assert!(audio_codec_matches("opus", "libopus"));
+        assert!(audio_codec_matches("aac", "aac_at"));
+        assert!(!audio_codec_matches("mp3", "mp2"));
+    }
+
+    #[test]
+    fn test_supported_pixel_formats() {
+        assert!(supported_pixel_formats("libx265")
+            .unwrap()
+            .contains(&"yuv420p10le"));
+        assert!(!supported_pixel_formats("libx264")
+            .unwrap()
+            .contains(&"p010le"));
+        assert!(supported_pixel_formats("h264_qsv")
+            .unwrap()
+            .contains(&"nv12"));
+        assert_eq!(supported_pixel_formats("libwebp"), None);
+    }
+
+    #[test]
+    fn test_intermediate_profile() {
+        let hq = intermediate_profile("prores_ks", Quality::High).unwrap();
+        assert_eq!(hq.profile, "3");
+        assert_eq!(
+            intermediate_profile("prores_ks", Quality::Ultra)
+                .unwrap()
+                .pixel_format,
+            "yuv444p10le"
+        );
+        assert_eq!(
+            intermediate_profile("dnxhd", Quality::Custom)
+                .unwrap()
+                .profile,
+            "dnxhr_sq"
+        );
+        assert_eq!(intermediate_profile("libx264", Quality::High), None);
+        assert!(is_intermediate_encoder("prores"));
+        assert!(!is_intermediate_encoder("ffv1"));
+    }
+
+    #[test]
+    fn test_encoder_presets() {
+        assert!(encoder_presets("libx265").unwrap().contains(&"veryslow"));
+        assert!(encoder_presets("hevc_nvenc").unwrap().contains(&"p7"));
+        assert!(!encoder_presets("h264_nvenc").unwrap().contains(&"slow"));
+        assert_eq!(encoder_presets("libvpx-vp9"), None);
+    }
+
     #[test]
     fn test_gpu_encoder_for_codec() {
         assert_eq!(gpu_encoder_for_codec("h264", "nvidia"), Some("h264_nvenc"));
@@ -99,6 +415,7 @@ mod tests {
             gpu_encoder_for_codec("h264", "apple"),
             Some("h264_videotoolbox")
         );
+        assert_eq!(gpu_encoder_for_codec("hevc", "vaapi"), Some("hevc_vaapi"));
         assert_eq!(gpu_encoder_for_codec("theora", "nvidia"), None);
         assert_eq!(gpu_encoder_for_codec("h264", "unknown"), None);
     }
@@ -111,6 +428,7 @@ mod tests {
         assert_eq!(software_encoder_for_codec("vp8"), Some("libvpx"));
         assert_eq!(software_encoder_for_codec("av1"), Some("libaom-av1"));
         assert_eq!(software_encoder_for_codec("theora"), Some("libtheora"));
+        assert_eq!(software_encoder_for_codec("webp"), Some("libwebp"));
         assert_eq!(software_encoder_for_codec("mpeg2video"), None);
         assert_eq!(software_encoder_for_codec("dvvideo"), None);
     }
@@ -121,6 +439,7 @@ mod tests {
         assert!(is_gpu_encoder("hevc_qsv"));
         assert!(is_gpu_encoder("av1_amf"));
         assert!(is_gpu_encoder("h264_videotoolbox"));
+        assert!(is_gpu_encoder("h264_vaapi"));
         assert!(!is_gpu_encoder("libx264"));
         assert!(!is_gpu_encoder("mpeg2video"));
         assert!(!is_gpu_encoder("copy"));
@@ -135,4 +454,66 @@ mod tests {
         assert_eq!(software_fallback_for_encoder("libx264"), None);
         assert_eq!(software_fallback_for_encoder("copy"), None);
     }
+
+    #[test]
+    fn test_frame_structure_support() {
+        assert!(supports_b_frames("libx264"));
+        assert!(supports_b_frames("mpeg2video"));
+        assert!(!supports_b_frames("h264_nvenc"));
+        assert!(!supports_b_frames("libvpx-vp9"));
+        assert!(supports_ref_frames("libx265"));
+        assert!(!supports_ref_frames("mpeg4"));
+        assert!(!supports_ref_frames("hevc_qsv"));
+    }
+
+    #[test]
+    fn test_native_audio_encoder_constraints() {
+        assert!(is_experimental_encoder("opus"));
+        assert!(is_experimental_encoder("vorbis"));
+        assert!(!is_experimental_encoder("libopus"));
+        assert_eq!(required_sample_rate("opus"), Some(48000));
+        assert_eq!(required_sample_rate("libopus"), None);
+    }
+
+    #[test]
+    fn test_quality_scale_clamping() {
+        let nvenc = quality_scale("h264_nvenc").unwrap();
+        assert_eq!(nvenc.option, "-cq");
+        assert_eq!(nvenc.clamp(60), 51);
+
+        let qsv = quality_scale("hevc_qsv").unwrap();
+        assert_eq!(qsv.option, "-global_quality");
+        assert_eq!(qsv.clamp(0), 1);
+
+        let amf = quality_scale("h264_amf").unwrap();
+        assert_eq!(amf.option, "-qp_i");
+        assert_eq!(amf.clamp(70), 51);
+        assert_eq!(quality_scale("av1_amf").unwrap().clamp(70), 70);
+
+        assert_eq!(quality_scale("h264_vaapi").unwrap().clamp(99), 51);
+        assert_eq!(quality_scale("av1_vaapi").unwrap().clamp(99), 99);
+
+        assert_eq!(quality_scale("libx264").unwrap().clamp(23), 23);
+        assert_eq!(quality_scale("libvpx").unwrap().clamp(0), 4);
+        assert_eq!(quality_scale("libvpx-vp9").unwrap().clamp(70), 63);
+
+        assert_eq!(quality_scale("h264_videotoolbox"), None);
+        assert_eq!(quality_scale("mpeg2video"), None);
+    }
+
+    #[test]
+    fn test_still_image_encoders() {
+        assert_eq!(still_image_encoders("avif"), ["libaom-av1", "libsvtav1"]);
+        assert_eq!(still_image_encoders("jxl"), ["libjxl"]);
+        assert!(still_image_encoders("webp").is_empty());
+    }
+
+    #[test]
+    fn test_supports_two_pass() {
+        assert!(supports_two_pass("libx264"));
+        assert!(supports_two_pass("libx265"));
+        assert!(supports_two_pass("libvpx-vp9"));
+        assert!(!supports_two_pass("h264_nvenc"));
+        assert!(!supports_two_pass("mpeg4"));
+    }
 }
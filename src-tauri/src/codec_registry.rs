@@ -4,12 +4,18 @@ use std::sync::OnceLock;
 
 static AVAILABLE_ENCODERS: OnceLock<HashSet<String>> = OnceLock::new();
 static AVAILABLE_DECODERS: OnceLock<HashSet<String>> = OnceLock::new();
+/// Codec ids (as reported by ffprobe) that at least one decoder handles
+static DECODABLE_CODECS: OnceLock<HashSet<String>> = OnceLock::new();
 
 /// Initialize the codec registry by querying FFmpeg for available encoders and decoders.
 /// Should be called once at app startup after FFmpeg binary is located.
 pub fn init(ffmpeg_path: &str) {
-    let _ = AVAILABLE_ENCODERS.get_or_init(|| parse_ffmpeg_list(ffmpeg_path, "-encoders"));
-    let _ = AVAILABLE_DECODERS.get_or_init(|| parse_ffmpeg_list(ffmpeg_path, "-decoders"));
+    let _ = AVAILABLE_ENCODERS
+        .get_or_init(|| parse_codec_list(&run_ffmpeg_list(ffmpeg_path, "-encoders")));
+
+    let decoders = run_ffmpeg_list(ffmpeg_path, "-decoders");
+    let _ = AVAILABLE_DECODERS.get_or_init(|| parse_codec_list(&decoders));
+    let _ = DECODABLE_CODECS.get_or_init(|| parse_decodable_codecs(&decoders));
 
     let enc_count = AVAILABLE_ENCODERS.get().map_or(0, |s| s.len());
     let dec_count = AVAILABLE_DECODERS.get().map_or(0, |s| s.len());
@@ -29,6 +35,17 @@ pub fn is_encoder_available(name: &str) -> bool {
     AVAILABLE_ENCODERS.get().is_some_and(|s| s.contains(name))
 }
 
+/// Check if an encoder is known to be missing from this FFmpeg build.
+///
+/// Unlike `!is_encoder_available`, returns `false` when the registry is not
+/// initialized or the encoder list came back empty — only a list that was
+/// actually read can rule an encoder out.
+pub fn lacks_encoder(name: &str) -> bool {
+    AVAILABLE_ENCODERS
+        .get()
+        .is_some_and(|s| !s.is_empty() && !s.contains(name))
+}
+
 /// Check if a specific decoder is available in this FFmpeg build.
 ///
 /// Returns `false` if the registry has not been initialized — conservative default
@@ -38,6 +55,17 @@ pub fn is_decoder_available(name: &str) -> bool {
     AVAILABLE_DECODERS.get().is_some_and(|s| s.contains(name))
 }
 
+/// Check if this FFmpeg build can decode streams of the given codec id
+/// (e.g. `av1`, `hevc`), by any of its decoders.
+///
+/// Returns `true` if the registry has not been initialized — the input is
+/// unknown territory, so let FFmpeg itself decide.
+pub fn can_decode(codec: &str) -> bool {
+    DECODABLE_CODECS
+        .get()
+        .is_none_or(|s| s.is_empty() || s.contains(codec))
+}
+
 /// Check if the registry has been initialized
 pub fn is_initialized() -> bool {
     AVAILABLE_ENCODERS.get().is_some()
@@ -53,6 +81,20 @@ pub fn get_audio_fallback(codec: &str) -> Option<&'static str> {
                 None
             }
         }
+        "libopus" if !is_encoder_available("libopus") => {
+            if is_encoder_available("opus") {
+                Some("opus")
+            } else {
+                None
+            }
+        }
+        "opus" if !is_encoder_available("opus") => {
+            if is_encoder_available("libopus") {
+                Some("libopus")
+            } else {
+                None
+            }
+        }
         "libvorbis" if !is_encoder_available("libvorbis") => {
             if is_encoder_available("vorbis") {
                 Some("vorbis")
@@ -64,27 +106,24 @@ pub fn get_audio_fallback(codec: &str) -> Option<&'static str> {
     }
 }
 
-fn parse_ffmpeg_list(ffmpeg_path: &str, flag: &str) -> HashSet<String> {
+fn run_ffmpeg_list(ffmpeg_path: &str, flag: &str) -> String {
     let output = create_hidden_command(ffmpeg_path)
         .args(["-hide_banner", flag])
         .output();
 
     match output {
-        Ok(out) if out.status.success() => {
-            let stdout = String::from_utf8_lossy(&out.stdout);
-            parse_codec_list(&stdout)
-        }
+        Ok(out) if out.status.success() => String::from_utf8_lossy(&out.stdout).into_owned(),
         Ok(out) => {
             tracing::warn!(
                 flag,
                 stderr = %String::from_utf8_lossy(&out.stderr).trim(),
                 "FFmpeg list command failed"
             );
-            HashSet::new()
+            String::new()
         }
         Err(e) => {
             tracing::warn!(flag, error = %e, "Failed to run FFmpeg list command");
-            HashSet::new()
+            String::new()
         }
     }
 }
@@ -120,6 +159,24 @@ fn parse_codec_list(output: &str) -> HashSet<String> {
     codecs
 }
 
+/// Codec ids covered by `ffmpeg -decoders` output. A decoder named after its
+/// codec counts directly; others say which codec they handle, e.g.
+/// `libdav1d ... (codec av1)`.
+fn parse_decodable_codecs(output: &str) -> HashSet<String> {
+    let mut codecs = parse_codec_list(output);
+
+    for line in output.lines() {
+        if let Some(start) = line.rfind("(codec ") {
+            let rest = &line[start + "(codec ".len()..];
+            if let Some(end) = rest.find(')') {
+                codecs.insert(rest[..end].trim().to_string());
+            }
+        }
+    }
+
+    codecs
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -147,4 +204,23 @@ mod tests {
         assert!(result.contains("libopus"));
         assert_eq!(result.len(), 6);
     }
+
+    #[test]
+    fn test_parse_decodable_codecs() {
+        let sample = r#"Decoders:
+ V..... = Video
+ ------
+ V....D h264                 H.264 / AVC / MPEG-4 AVC / MPEG-4 part 10
+ V....D libdav1d             dav1d AV1 decoder by VideoLAN (codec av1)
+ V..... h264_cuvid           Nvidia CUVID H264 decoder (codec h264)
+ A....D aac                  AAC (Advanced Audio Coding)
+"#;
+
+        let result = parse_decodable_codecs(sample);
+        assert!(result.contains("h264"));
+        assert!(result.contains("av1"));
+        assert!(result.contains("libdav1d"));
+        assert!(result.contains("aac"));
+        assert!(!result.contains("hevc"));
+    }
 }
@@ -1,18 +1,27 @@
+use crate::batch::{self, BatchJob};
 use crate::binary;
 use crate::codec_registry;
 use crate::converter;
-use crate::formats::{audio, video};
+use crate::diagnostics::{self, CheckStatus, DiagnosticsReport};
+use crate::error::command_error;
+use crate::formats::{audio, video, Category};
 use crate::gpu::{self, GpuInfo};
 use crate::media::{self, MediaInfo};
-use crate::types::ConversionSettings;
+use crate::profiles::{self, Profile};
+use crate::silence::{self, SilenceRange};
+use crate::task_journal::{self, OrphanedTask};
+use crate::temp_files;
+use crate::types::{ConversionSettings, Quality};
 use crate::utils;
 use crate::validator::{self, ValidationContext, ValidationResult};
 use crate::AppState;
 use serde_json::{json, Value};
+use std::collections::HashSet;
 use tauri::{Emitter, Manager, State};
 use tokio::sync::OnceCell;
 
-static GPU_CACHE: OnceCell<GpuInfo> = OnceCell::const_new();
+/// Every detected GPU in priority order; the first entry is the "best available"
+static GPU_CACHE: OnceCell<Vec<GpuInfo>> = OnceCell::const_new();
 static AUDIO_FORMATS_CACHE: OnceCell<Vec<audio::AudioFormat>> = OnceCell::const_new();
 static VIDEO_FORMATS_CACHE: OnceCell<Vec<video::VideoFormat>> = OnceCell::const_new();
 
@@ -58,27 +67,80 @@ pub async fn check_ffmpeg(app: tauri::AppHandle) -> Result<bool, String> {
     let ok = binary::check_binaries(&app).map_err(|e| -> String { e.into() })?;
 
     // Initialize codec registry on first successful FFmpeg check
-    if ok && !codec_registry::is_initialized() {
-        if let Ok(ffmpeg_path) = binary::get_ffmpeg_path(&app) {
-            if let Some(ffmpeg) = ffmpeg_path.to_str() {
-                codec_registry::init(ffmpeg);
-            }
-        }
+    if ok {
+        init_codec_registry(&app);
     }
 
     Ok(ok)
 }
 
+fn init_codec_registry(app: &tauri::AppHandle) {
+    if codec_registry::is_initialized() {
+        return;
+    }
+    if let Ok(ffmpeg_path) = binary::get_ffmpeg_path(app) {
+        if let Some(ffmpeg) = ffmpeg_path.to_str() {
+            codec_registry::init(ffmpeg);
+        }
+    }
+}
+
+/// Check the whole toolchain for the "Help > Diagnostics" panel: FFmpeg and
+/// ffprobe, GPU detection, the temp directory and the encoders the formats use.
+#[tauri::command]
+pub async fn run_diagnostics(app: tauri::AppHandle) -> DiagnosticsReport {
+    let binaries = diagnostics::check_binaries(&app).await;
+    if binaries.status == CheckStatus::Ok {
+        init_codec_registry(&app);
+    }
+
+    DiagnosticsReport::new(
+        binaries,
+        detected_gpus(&app).await,
+        &get_audio_formats().await,
+        &get_video_formats().await,
+    )
+}
+
+/// Version and build configuration of the bundled FFmpeg, so the frontend can
+/// gray out formats whose libraries aren't compiled in.
+#[tauri::command]
+pub async fn get_ffmpeg_info(app: tauri::AppHandle) -> Result<binary::FfmpegInfo, String> {
+    binary::get_ffmpeg_info(&app).await.map_err(String::from)
+}
+
 #[tauri::command]
 pub async fn detect_gpu(app: tauri::AppHandle) -> GpuInfo {
-    let ffmpeg_path = binary::get_ffmpeg_path(&app)
+    detected_gpus(&app)
+        .await
+        .first()
+        .cloned()
+        .unwrap_or_default()
+}
+
+/// All usable GPUs, best first. `ConversionSettings::gpu_index` indexes into this list.
+#[tauri::command]
+pub async fn detect_all_gpus(app: tauri::AppHandle) -> Vec<GpuInfo> {
+    detected_gpus(&app).await.to_vec()
+}
+
+async fn detected_gpus(app: &tauri::AppHandle) -> &'static [GpuInfo] {
+    let ffmpeg_path = binary::get_ffmpeg_path(app)
         .ok()
         .and_then(|p| p.to_str().map(|s| s.to_string()));
 
     GPU_CACHE
-        .get_or_init(|| async { gpu::detect_gpu(ffmpeg_path).await })
+        .get_or_init(|| async { gpu::detect_all_gpus(ffmpeg_path).await })
         .await
-        .clone()
+}
+
+/// Number of CPU threads available to the app, for a sensible default
+/// encoder thread count in the UI.
+#[tauri::command]
+pub fn get_cpu_count() -> usize {
+    std::thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(1)
 }
 
 #[tauri::command]
@@ -111,6 +173,41 @@ pub async fn detect_media_type(
         .map_err(|e| e.to_string())
 }
 
+/// Analyze a dropped batch in one call. Each entry is that file's result, in input order.
+#[tauri::command]
+pub async fn detect_media_type_batch(
+    app_handle: tauri::AppHandle,
+    paths: Vec<String>,
+) -> Vec<Result<MediaInfo, String>> {
+    media::detect_media_type_batch(&app_handle, paths)
+        .await
+        .into_iter()
+        .map(|r| r.map_err(|e| e.to_string()))
+        .collect()
+}
+
+/// Like `detect_media_type_batch`, but each result is paired with its path so
+/// callers can match them up without relying on order.
+#[tauri::command]
+pub async fn probe_batch(
+    app_handle: tauri::AppHandle,
+    paths: Vec<String>,
+) -> Vec<(String, Result<MediaInfo, String>)> {
+    let results = media::detect_media_type_batch(&app_handle, paths.clone()).await;
+    paths
+        .into_iter()
+        .zip(results)
+        .map(|(path, r)| (path, r.map_err(|e| e.to_string())))
+        .collect()
+}
+
+/// Drop cached probe results, e.g. after files were edited in place in a way
+/// that kept their size and timestamp. Returns how many were cleared.
+#[tauri::command]
+pub fn clear_probe_cache() -> usize {
+    media::clear_probe_cache()
+}
+
 #[tauri::command]
 pub async fn get_audio_formats() -> Vec<audio::AudioFormat> {
     AUDIO_FORMATS_CACHE
@@ -127,6 +224,85 @@ pub async fn get_video_formats() -> Vec<video::VideoFormat> {
         .clone()
 }
 
+/// One output extension in a format picker.
+#[derive(serde::Serialize)]
+struct ExtensionEntry {
+    extension: String,
+    name: String,
+    category: Category,
+}
+
+#[derive(Default, serde::Serialize)]
+struct ExtensionGroups {
+    audio: Vec<ExtensionEntry>,
+    video: Vec<ExtensionEntry>,
+    /// Still-image outputs (AVIF, JPEG XL), split off the video formats
+    image: Vec<ExtensionEntry>,
+}
+
+/// Every output extension grouped by media type, each group ordered by
+/// category (popular first), so pickers don't have to merge the audio and
+/// video lists themselves.
+#[tauri::command]
+pub async fn get_all_extensions() -> Value {
+    let audio_formats = AUDIO_FORMATS_CACHE
+        .get_or_init(|| async { audio::get_all_formats() })
+        .await;
+    let video_formats = VIDEO_FORMATS_CACHE
+        .get_or_init(|| async { video::get_all_formats() })
+        .await;
+
+    let mut groups = ExtensionGroups::default();
+    for fmt in audio_formats {
+        groups.audio.push(ExtensionEntry {
+            extension: fmt.extension.clone(),
+            name: fmt.name.clone(),
+            category: fmt.category,
+        });
+    }
+    for fmt in video_formats {
+        let group = if fmt.still_image {
+            &mut groups.image
+        } else {
+            &mut groups.video
+        };
+        group.push(ExtensionEntry {
+            extension: fmt.extension.clone(),
+            name: fmt.name.clone(),
+            category: fmt.category,
+        });
+    }
+    // The caches come from hash maps, so only the category order is fixed
+    for group in [&mut groups.audio, &mut groups.video, &mut groups.image] {
+        group.sort_by(|a, b| {
+            a.category
+                .cmp(&b.category)
+                .then_with(|| a.extension.cmp(&b.extension))
+        });
+    }
+    json!(groups)
+}
+
+/// Recommended starting settings for converting to `format`, with the
+/// ranges the format and its encoder accept, so the UI can pre-fill and bound
+/// its controls.
+#[tauri::command]
+pub fn get_format_defaults(
+    format: String,
+    media_type: String,
+) -> Result<converter::defaults::FormatDefaults, String> {
+    let quality = Quality::default();
+    if media_type == "audio" {
+        audio::get_format(&format)
+            .map(|fmt| converter::defaults::for_audio(&fmt, quality))
+            .ok_or_else(|| format!("Unknown audio format: {}", format))
+    } else {
+        video::get_format(&format)
+            .map(|fmt| converter::defaults::for_video(&fmt, quality))
+            .ok_or_else(|| format!("Unknown video format: {}", format))
+    }
+}
+
 #[tauri::command]
 pub async fn get_recommended_formats(
     video_codec: String,
@@ -155,6 +331,8 @@ struct CategoryResult {
     setup: Vec<String>,
     experimental: Vec<String>,
     problematic: Vec<String>,
+    /// Encoder missing from this FFmpeg build; can never work
+    unavailable: Vec<String>,
 }
 
 fn categorize_video_formats(
@@ -166,8 +344,12 @@ fn categorize_video_formats(
 ) -> Value {
     let mut result = CategoryResult::default();
     for fmt in formats {
-        let compat = fmt.get_compatibility_level(video_codec, audio_codec, width, height);
         let ext = fmt.extension.clone();
+        if !fmt.is_available() {
+            result.unavailable.push(ext);
+            continue;
+        }
+        let compat = fmt.get_compatibility_level(video_codec, audio_codec, width, height);
         match compat {
             video::FormatCompatibility::Fast => result.fast.push(ext),
             video::FormatCompatibility::Safe => result.safe.push(ext),
@@ -185,6 +367,10 @@ fn categorize_audio_formats(formats: &[audio::AudioFormat], audio_codec: &str) -
     let mut result = CategoryResult::default();
     for fmt in formats {
         let ext = fmt.extension.clone();
+        if !fmt.is_available() {
+            result.unavailable.push(ext);
+            continue;
+        }
         match fmt.stability {
             Stability::Stable => {
                 if fmt.can_copy_codec(audio_codec) {
@@ -207,6 +393,32 @@ pub fn validate_conversion(context: Value) -> ValidationResult {
     validator::validate(&ctx)
 }
 
+/// Predicted output size range for converting `media` with `settings`.
+#[tauri::command]
+pub fn estimate_output_size(
+    media: MediaInfo,
+    settings: Value,
+) -> Result<converter::estimate::SizeEstimate, String> {
+    let mut settings: ConversionSettings =
+        serde_json::from_value(settings).map_err(|e| e.to_string())?;
+    settings.resolve_legacy_bitrate(settings.extract_audio_only || media.primary_video().is_none());
+    Ok(converter::estimate::estimate(&media, &settings))
+}
+
+/// Run a conversion and count its task as ended in its batch (if any),
+/// whatever the outcome.
+async fn in_batch(
+    window: &tauri::WebviewWindow,
+    task_id: Option<String>,
+    conversion: impl std::future::Future<Output = anyhow::Result<String>>,
+) -> Result<String, String> {
+    let result = conversion.await.map_err(command_error);
+    if let Some(task_id) = task_id {
+        batch::finish_task(window, &task_id);
+    }
+    result
+}
+
 #[tauri::command]
 pub async fn convert_audio(
     state: State<'_, AppState>,
@@ -219,16 +431,20 @@ pub async fn convert_audio(
     let settings: ConversionSettings =
         serde_json::from_value(settings).map_err(|e| e.to_string())?;
 
-    converter::audio::convert(
-        window,
-        &input,
-        &output,
-        &format,
-        settings,
-        state.active_processes.clone(),
+    let task_id = settings.task_id.clone();
+    in_batch(
+        &window.clone(),
+        task_id,
+        converter::audio::convert(
+            window,
+            &input,
+            &output,
+            &format,
+            settings,
+            state.active_processes.clone(),
+        ),
     )
     .await
-    .map_err(|e| e.to_string())
 }
 
 #[tauri::command]
@@ -244,17 +460,32 @@ pub async fn convert_video(
     let settings: ConversionSettings =
         serde_json::from_value(settings).map_err(|e| e.to_string())?;
 
-    converter::video::convert(
-        window,
-        &input,
-        &output,
-        &format,
-        gpu_info,
-        settings,
-        state.active_processes.clone(),
+    // An explicit device choice overrides the GPU the frontend sent
+    let gpu_info = match settings.gpu_index {
+        Some(index) => {
+            let gpus = detected_gpus(window.app_handle()).await;
+            gpus.get(index)
+                .cloned()
+                .ok_or_else(|| format!("GPU #{} not found ({} detected)", index, gpus.len()))?
+        }
+        None => gpu_info,
+    };
+
+    let task_id = settings.task_id.clone();
+    in_batch(
+        &window.clone(),
+        task_id,
+        converter::video::convert(
+            window,
+            &input,
+            &output,
+            &format,
+            gpu_info,
+            settings,
+            state.active_processes.clone(),
+        ),
     )
     .await
-    .map_err(|e| e.to_string())
 }
 
 #[tauri::command]
@@ -269,16 +500,268 @@ pub async fn extract_audio(
     let settings: ConversionSettings =
         serde_json::from_value(settings).map_err(|e| e.to_string())?;
 
-    converter::audio::extract_from_video(
+    let task_id = settings.task_id.clone();
+    in_batch(
+        &window.clone(),
+        task_id,
+        converter::audio::extract_from_video(
+            window,
+            &input,
+            &output,
+            &format,
+            settings,
+            state.active_processes.clone(),
+        ),
+    )
+    .await
+}
+
+/// Put the audio of `audio` under the picture of `video`, copying the video
+/// stream. With `loop_shorter` the shorter input repeats instead of the output
+/// ending with it.
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
+pub async fn mux_audio(
+    state: State<'_, AppState>,
+    window: tauri::WebviewWindow,
+    video: String,
+    audio: String,
+    output: String,
+    format: String,
+    settings: Value,
+    loop_shorter: bool,
+) -> Result<String, String> {
+    let settings: ConversionSettings =
+        serde_json::from_value(settings).map_err(|e| e.to_string())?;
+
+    let task_id = settings.task_id.clone();
+    in_batch(
+        &window.clone(),
+        task_id,
+        converter::mux::mux_audio(
+            window,
+            &video,
+            &audio,
+            &output,
+            &format,
+            settings,
+            loop_shorter,
+            state.active_processes.clone(),
+        ),
+    )
+    .await
+}
+
+/// Convert `input` to `format` and stream the result into `sink` instead of
+/// writing a file: `-` for the app's stdout, otherwise a FIFO or named pipe
+/// the integrating process reads from. Audio formats go through the audio
+/// converter, everything else through the video one.
+#[tauri::command]
+pub async fn convert_to_stream(
+    state: State<'_, AppState>,
+    window: tauri::WebviewWindow,
+    input: String,
+    format: String,
+    sink: String,
+    gpu_info: GpuInfo,
+    settings: Value,
+) -> Result<String, String> {
+    let mut settings: ConversionSettings =
+        serde_json::from_value(settings).map_err(|e| e.to_string())?;
+    settings.stream_sink = Some(sink);
+
+    let processes = state.active_processes.clone();
+    let result = if audio::get_format(&format).is_some() {
+        converter::audio::convert(
+            window,
+            &input,
+            utils::PIPE_OUTPUT,
+            &format,
+            settings,
+            processes,
+        )
+        .await
+    } else {
+        converter::video::convert(
+            window,
+            &input,
+            utils::PIPE_OUTPUT,
+            &format,
+            gpu_info,
+            settings,
+            processes,
+        )
+        .await
+    };
+    result.map_err(command_error)
+}
+
+/// Silent stretches in audio track `audio_track` (first by default) of `path`,
+/// for showing what silence trimming would cut.
+#[tauri::command]
+pub async fn detect_silence(
+    app: tauri::AppHandle,
+    path: String,
+    audio_track: Option<usize>,
+) -> Result<Vec<SilenceRange>, String> {
+    let info = media::detect_media_type(&app, &path)
+        .await
+        .map_err(|e| e.to_string())?;
+    let track = audio_track.unwrap_or(0);
+    if track >= info.audio_streams.len() {
+        return Err(format!(
+            "Audio track {} not found ({} available)",
+            track,
+            info.audio_streams.len()
+        ));
+    }
+
+    let ffmpeg_path = binary::get_ffmpeg_path(&app).map_err(String::from)?;
+    silence::detect(&ffmpeg_path.to_string_lossy(), &path, track, info.duration)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+fn config_dir(app: &tauri::AppHandle) -> Result<std::path::PathBuf, String> {
+    app.path()
+        .app_config_dir()
+        .map_err(|e| format!("Config directory unavailable: {}", e))
+}
+
+/// Conversions a crash or forced quit interrupted in an earlier run.
+#[tauri::command]
+pub fn list_interrupted_tasks() -> Vec<OrphanedTask> {
+    task_journal::orphans()
+}
+
+/// Forget an interrupted conversion, deleting its partial output unless
+/// `keep_output` is set.
+#[tauri::command]
+pub fn resolve_interrupted_task(task_id: String, keep_output: bool) -> Result<(), String> {
+    task_journal::resolve_orphan(&task_id, !keep_output).map_err(|e| e.to_string())
+}
+
+/// Built-in profiles followed by the user's saved ones.
+#[tauri::command]
+pub fn list_profiles(app: tauri::AppHandle) -> Result<Vec<Profile>, String> {
+    profiles::list(&config_dir(&app)?).map_err(|e| e.to_string())
+}
+
+/// Settings and output format of the profile called `name`, for the frontend
+/// to fill in before converting.
+#[tauri::command]
+pub fn load_profile(app: tauri::AppHandle, name: String) -> Result<Profile, String> {
+    profiles::load(&config_dir(&app)?, &name).map_err(|e| e.to_string())
+}
+
+/// Save `settings` and `format` as a user profile, replacing one of the same name.
+#[tauri::command]
+pub fn save_profile(
+    app: tauri::AppHandle,
+    name: String,
+    format: String,
+    settings: ConversionSettings,
+) -> Result<Profile, String> {
+    profiles::save(&config_dir(&app)?, &name, &format, settings).map_err(|e| e.to_string())
+}
+
+/// Remove a user profile; returns whether it existed.
+#[tauri::command]
+pub fn delete_profile(app: tauri::AppHandle, name: String) -> Result<bool, String> {
+    profiles::delete(&config_dir(&app)?, &name).map_err(|e| e.to_string())
+}
+
+/// Dump the frames of `input` into `output_dir` as a numbered PNG sequence,
+/// optionally subsampled to `fps`.
+#[tauri::command]
+pub async fn extract_frames(
+    state: State<'_, AppState>,
+    window: tauri::WebviewWindow,
+    input: String,
+    output_dir: String,
+    fps: Option<f64>,
+    overwrite: bool,
+    settings: Value,
+) -> Result<String, String> {
+    let settings: ConversionSettings =
+        serde_json::from_value(settings).map_err(|e| e.to_string())?;
+
+    converter::frames::extract(
         window,
         &input,
-        &output,
-        &format,
+        &output_dir,
+        fps,
+        overwrite,
         settings,
         state.active_processes.clone(),
     )
     .await
-    .map_err(|e| e.to_string())
+    .map_err(command_error)
+}
+
+/// Convert `input` to `target_format` beside it and replace the original once
+/// the result has been verified. Returns the path of the converted file.
+#[tauri::command]
+pub async fn convert_in_place(
+    state: State<'_, AppState>,
+    window: tauri::WebviewWindow,
+    input: String,
+    target_format: String,
+    settings: Value,
+) -> Result<String, String> {
+    let settings: ConversionSettings =
+        serde_json::from_value(settings).map_err(|e| e.to_string())?;
+
+    let gpus = detected_gpus(window.app_handle()).await;
+    let gpu_info = match settings.gpu_index {
+        Some(index) => gpus
+            .get(index)
+            .cloned()
+            .ok_or_else(|| format!("GPU #{} not found ({} detected)", index, gpus.len()))?,
+        None => gpus.first().cloned().unwrap_or_default(),
+    };
+
+    let task_id = settings.task_id.clone();
+    in_batch(
+        &window.clone(),
+        task_id,
+        converter::in_place::convert(
+            window,
+            &input,
+            &target_format,
+            gpu_info,
+            settings,
+            state.active_processes.clone(),
+        ),
+    )
+    .await
+}
+
+/// Register a queue run so its tasks' progress is combined into
+/// `batch-progress` events, weighted by each job's duration.
+#[tauri::command]
+pub fn start_batch(batch_id: String, jobs: Vec<BatchJob>) {
+    batch::start(&batch_id, jobs);
+}
+
+/// Stop tracking a batch whose remaining jobs won't run.
+#[tauri::command]
+pub fn end_batch(batch_id: String) {
+    batch::end(&batch_id);
+}
+
+/// Set how many hardware-encoded conversions may run at the same time.
+/// Extra GPU jobs wait for a free slot and emit `gpu-throttled`.
+#[tauri::command]
+pub fn set_gpu_session_limit(limit: u32) {
+    converter::gpu_throttle::set_limit(limit as usize);
+}
+
+/// Choose whether conversions without metadata edits keep the source's
+/// metadata (`true`) or strip it (`false`, the default).
+#[tauri::command]
+pub fn set_default_preserve_metadata(preserve: bool) {
+    converter::builder::set_default_preserve_metadata(preserve);
 }
 
 #[tauri::command]
@@ -289,15 +772,44 @@ pub async fn cancel_conversion(state: State<'_, AppState>, task_id: String) -> R
     Ok(())
 }
 
+/// Stop every running conversion at once and report which tasks were
+/// cancelled, as `all-cancelled` and as the return value.
+#[tauri::command]
+pub async fn cancel_all_conversions(
+    state: State<'_, AppState>,
+    window: tauri::WebviewWindow,
+) -> Result<Vec<String>, String> {
+    let task_ids = crate::kill_all_processes(&state.active_processes).await;
+    tracing::info!(count = task_ids.len(), "Cancelled all conversions");
+    let _ = window.emit("all-cancelled", &task_ids);
+    Ok(task_ids)
+}
+
+/// Remove temp artifacts (pass logs, partial files) not owned by a running
+/// conversion. Returns how many were removed.
+#[tauri::command]
+pub async fn cleanup_temp_files(state: State<'_, AppState>) -> Result<usize, String> {
+    let active: HashSet<String> = state
+        .active_processes
+        .lock()
+        .await
+        .keys()
+        .cloned()
+        .collect();
+    tokio::task::spawn_blocking(move || temp_files::cleanup_stale(&active))
+        .await
+        .map_err(|e| e.to_string())
+}
+
 pub async fn init_caches(window: &tauri::WebviewWindow) {
     let ffmpeg_path = binary::get_ffmpeg_path(window.app_handle())
         .ok()
         .and_then(|p| p.to_str().map(|s| s.to_string()));
 
-    let gpu = GPU_CACHE
-        .get_or_init(|| async { gpu::detect_gpu(ffmpeg_path).await })
+    let gpus = GPU_CACHE
+        .get_or_init(|| async { gpu::detect_all_gpus(ffmpeg_path).await })
         .await;
-    let _ = window.emit("gpu-detected", gpu);
+    let _ = window.emit("gpu-detected", gpus.first().cloned().unwrap_or_default());
 
     tokio::spawn(async {
         AUDIO_FORMATS_CACHE
@@ -1,9 +1,17 @@
 use super::builder::FfmpegBuilder;
-use super::spawn_ffmpeg;
+use super::defaults;
+use super::disk;
+use super::{
+    check_extra_args, check_fades, emit_phase, ensure_decodable, filters, match_output_extension,
+    measure_loudness, resolve_output_path, select_audio_track, spawn_ffmpeg, ConversionPhase,
+    RunOptions,
+};
+use crate::codec_map;
 use crate::codec_registry;
 use crate::formats::audio::{self, AudioFormat};
-use crate::media;
-use crate::types::ConversionSettings;
+use crate::media::{self, MediaInfo};
+use crate::silence;
+use crate::types::{ConversionSettings, OPUS_FRAME_DURATIONS};
 use crate::utils;
 use anyhow::{Context, Result};
 use std::collections::HashMap;
@@ -17,30 +25,55 @@ pub async fn convert(
     input: &str,
     output: &str,
     format: &str,
-    settings: ConversionSettings,
+    mut settings: ConversionSettings,
     processes: Arc<Mutex<HashMap<String, Child>>>,
 ) -> Result<String> {
     // Pre-flight validation: ensure input file still exists
     utils::validate_input_path(input)?;
+    utils::validate_output_path(output)?;
+    check_extra_args(&settings)?;
+    settings.resolve_legacy_bitrate(true);
 
     let task_id = settings.task_id();
     let fmt = audio::get_format(format).context(format!("Unknown audio format: {}", format))?;
-    let media = media::detect_media_type(window.app_handle(), input).await?;
+    let output = &match_output_extension(
+        &window,
+        &task_id,
+        output,
+        &fmt.extension,
+        fmt.container.as_deref(),
+    );
+    let output = &resolve_output_path(&window, &task_id, output, settings.on_collision)?;
+    emit_phase(&window, &task_id, ConversionPhase::Probing);
+    let mut media = media::detect_media_type(window.app_handle(), input).await?;
+    select_audio_track(&mut media, &settings)?;
+    check_fades(&settings, settings.output_duration(media.duration))?;
+    disk::ensure_free_space(output, disk::estimate_output_size(&media, &settings, false))?;
 
     // Resolve actual codec (check availability)
-    let codec = resolve_audio_codec(&fmt)?;
+    let codec = resolve_audio_codec(&fmt, &settings)?;
+    let channels = output_channels(&fmt, &settings)?;
+
+    if codec != "copy" {
+        ensure_decodable(media.audio_codec())?;
+        if !measure_loudness(&window, &task_id, input, &mut settings, &processes).await {
+            return Ok(task_id);
+        }
+    }
 
     let mut builder = FfmpegBuilder::new(input, output)
         .hide_banner()
-        .overwrite()
+        .on_collision(settings.on_collision)
+        .extra_args(&settings.extra_args)
         .input_file()
         .progress_pipe()
         .disable_video()
-        .metadata(&settings.metadata)
+        .metadata(&settings.metadata, settings.preserve_metadata)
+        .audio_track(settings.audio_track_index)
         .audio_codec(&codec);
 
     if codec != "copy" {
-        builder = apply_audio_settings(builder, &fmt, &settings);
+        builder = apply_audio_settings(builder, &fmt, &codec, channels, &media, &settings);
         builder = apply_container_and_params(builder, &fmt);
     } else {
         builder = apply_container_and_params(builder, &fmt);
@@ -51,9 +84,10 @@ pub async fn convert(
     spawn_ffmpeg(
         window,
         task_id,
-        media.duration,
+        settings.output_duration(media.duration),
         args,
         output_path,
+        RunOptions::from_settings(&settings),
         processes,
     )
     .await
@@ -64,37 +98,61 @@ pub async fn extract_from_video(
     input: &str,
     output: &str,
     format: &str,
-    settings: ConversionSettings,
+    mut settings: ConversionSettings,
     processes: Arc<Mutex<HashMap<String, Child>>>,
 ) -> Result<String> {
     // Pre-flight validation: ensure input file still exists
     utils::validate_input_path(input)?;
+    utils::validate_output_path(output)?;
+    check_extra_args(&settings)?;
+    settings.resolve_legacy_bitrate(true);
 
     let task_id = settings.task_id();
     let fmt = audio::get_format(format).context(format!("Unknown audio format: {}", format))?;
-    let media = media::detect_media_type(window.app_handle(), input).await?;
+    let output = &match_output_extension(
+        &window,
+        &task_id,
+        output,
+        &fmt.extension,
+        fmt.container.as_deref(),
+    );
+    let output = &resolve_output_path(&window, &task_id, output, settings.on_collision)?;
+    emit_phase(&window, &task_id, ConversionPhase::Probing);
+    let mut media = media::detect_media_type(window.app_handle(), input).await?;
+    select_audio_track(&mut media, &settings)?;
+    check_fades(&settings, settings.output_duration(media.duration))?;
+    disk::ensure_free_space(output, disk::estimate_output_size(&media, &settings, false))?;
 
     if media.audio_streams.is_empty() {
         anyhow::bail!("No audio streams found in input file");
     }
+    let channels = output_channels(&fmt, &settings)?;
+
+    if !measure_loudness(&window, &task_id, input, &mut settings, &processes).await {
+        return Ok(task_id);
+    }
 
     let mut builder = FfmpegBuilder::new(input, output)
         .hide_banner()
-        .overwrite()
+        .on_collision(settings.on_collision)
+        .extra_args(&settings.extra_args)
         .input_file()
         .progress_pipe()
         .disable_video()
-        .metadata(&settings.metadata);
+        .metadata(&settings.metadata, settings.preserve_metadata)
+        .audio_track(settings.audio_track_index);
 
     let source_codec = &media.audio_streams[0].codec;
 
-    if settings.copy_audio && fmt.can_copy_codec(source_codec) {
+    // Filtering requires re-encoding, so stream copy is off the table
+    if settings.copy_audio && !settings.has_audio_filters() && fmt.can_copy_codec(source_codec) {
         builder = builder.audio_codec("copy");
     } else {
-        let codec = resolve_audio_codec(&fmt)?;
+        let codec = resolve_audio_codec(&fmt, &settings)?;
         builder = builder.audio_codec(&codec);
         if codec != "copy" {
-            builder = apply_audio_settings(builder, &fmt, &settings);
+            ensure_decodable(media.audio_codec())?;
+            builder = apply_audio_settings(builder, &fmt, &codec, channels, &media, &settings);
         }
     }
 
@@ -105,31 +163,32 @@ pub async fn extract_from_video(
     spawn_ffmpeg(
         window,
         task_id,
-        media.duration,
+        settings.output_duration(media.duration),
         args,
         output_path,
+        RunOptions::from_settings(&settings),
         processes,
     )
     .await
 }
 
 /// Check if the target codec is available; try fallback if not.
-fn resolve_audio_codec(fmt: &AudioFormat) -> Result<String> {
-    let codec = &fmt.codec;
+fn resolve_audio_codec(fmt: &AudioFormat, settings: &ConversionSettings) -> Result<String> {
+    let codec = settings.preferred_audio_encoder(&fmt.codec);
 
     // "copy" is always available
     if codec == "copy" {
-        return Ok(codec.clone());
+        return Ok(codec.to_string());
     }
 
     // If registry not initialized, trust the format definition
     if !codec_registry::is_initialized() {
-        return Ok(codec.clone());
+        return Ok(codec.to_string());
     }
 
     // Check primary codec
     if codec_registry::is_encoder_available(codec) {
-        return Ok(codec.clone());
+        return Ok(codec.to_string());
     }
 
     // Try fallback
@@ -151,18 +210,73 @@ fn resolve_audio_codec(fmt: &AudioFormat) -> Result<String> {
     )
 }
 
+/// Channel count for `-ac`. An explicit count passes through untouched (so
+/// stereo can be upmixed to 5.1 or 7.1) and fails when the format can't carry
+/// it; anything else falls back to the closest count the format supports.
+fn output_channels(fmt: &AudioFormat, settings: &ConversionSettings) -> Result<u32> {
+    match settings.explicit_channels() {
+        Some(count) if !fmt.supports_channels(count) => anyhow::bail!(
+            "Format '{}' cannot hold {} audio channels. Supported: {}",
+            fmt.extension,
+            count,
+            fmt.channels_list()
+        ),
+        Some(count) => Ok(count),
+        None => Ok(fmt.best_channels(settings.channels())),
+    }
+}
+
 fn apply_audio_settings(
     builder: FfmpegBuilder,
     fmt: &AudioFormat,
+    codec: &str,
+    channels: u32,
+    media: &MediaInfo,
     settings: &ConversionSettings,
 ) -> FfmpegBuilder {
-    let sample_rate = fmt.best_sample_rate(settings.sample_rate());
-    let channels = fmt.best_channels(settings.channels());
+    let sample_rate = codec_map::required_sample_rate(codec)
+        .unwrap_or_else(|| fmt.best_sample_rate(settings.sample_rate()));
 
-    let mut builder = builder.sample_rate(sample_rate).channels(channels);
+    let mut chain = filters::audio_chain(settings, media);
+    // First, so a fade-in starts at the first sound
+    if settings.trim_silence {
+        chain.insert(0, silence::trim_filter());
+    }
+    // Last, so loudnorm still measures the source layout
+    let source_channels = media.primary_audio().map(|a| a.channels).unwrap_or(0);
+    if let Some(downmix) = filters::downmix_filter(source_channels, channels) {
+        tracing::warn!(
+            source_channels,
+            channels,
+            "Downmixing folds channels together, loud passages may clip"
+        );
+        chain.push(downmix);
+    }
+    if let Some(layout) = settings.channel_layout {
+        if channels != layout.channels() {
+            tracing::warn!(
+                requested = layout.channels(),
+                channels,
+                "Channel layout not supported by the format, using closest"
+            );
+        }
+    }
+
+    let mut builder = builder
+        .sample_rate(sample_rate)
+        .channels(channels)
+        .audio_filters(&chain);
+
+    if codec_map::is_experimental_encoder(codec) {
+        builder = builder.arg("-strict", "experimental");
+    }
+
+    if let Some(threads) = settings.threads() {
+        builder = builder.threads(codec, threads);
+    }
 
     if fmt.lossy {
-        builder = apply_lossy_settings(builder, fmt, settings);
+        builder = apply_lossy_settings(builder, fmt, codec, settings);
     } else {
         builder = apply_lossless_settings(builder, fmt, settings);
     }
@@ -173,35 +287,56 @@ fn apply_audio_settings(
 fn apply_lossy_settings(
     builder: FfmpegBuilder,
     fmt: &AudioFormat,
+    codec: &str,
     settings: &ConversionSettings,
 ) -> FfmpegBuilder {
-    let quality = settings.quality.as_str();
+    let bitrate = settings
+        .audio_only_bitrate()
+        .or_else(|| defaults::audio_bitrate(fmt, codec, settings.quality));
 
-    match fmt.codec.as_str() {
+    let builder = match codec {
         "libvorbis" => {
-            let q = match quality {
+            let q = match settings.quality.as_str() {
                 "low" => "3",
                 "medium" => "5",
                 "high" => "7",
                 "ultra" => "9",
                 _ => "5",
             };
-            builder.arg("-q:a", q)
+            return builder.arg("-q:a", q);
+        }
+        "libopus" => apply_libopus_tuning(builder.arg("-vbr", "on"), settings),
+        // The native encoder has no VBR switch or tuning options
+        "opus" => {
+            if settings.opus_application.is_some() || settings.opus_frame_duration.is_some() {
+                tracing::warn!("Opus application and frame duration ignored by the native encoder");
+            }
+            builder
         }
-        "libopus" => {
-            let bitrate = settings
-                .bitrate
-                .or_else(|| fmt.get_bitrate_for_quality(quality))
-                .unwrap_or(128);
-            builder.arg("-vbr", "on").audio_bitrate(bitrate)
+        _ => builder,
+    };
+    match bitrate {
+        Some(kbps) => builder.audio_bitrate(kbps),
+        None => builder,
+    }
+}
+
+fn apply_libopus_tuning(builder: FfmpegBuilder, settings: &ConversionSettings) -> FfmpegBuilder {
+    let application = settings.opus_application.unwrap_or_default();
+    let builder = builder.arg("-application", application.as_str());
+
+    match settings.opus_frame_duration {
+        Some(ms) if OPUS_FRAME_DURATIONS.contains(&ms) => {
+            builder.arg("-frame_duration", &ms.to_string())
         }
-        _ => {
-            let bitrate = settings
-                .bitrate
-                .or_else(|| fmt.get_bitrate_for_quality(quality))
-                .unwrap_or(192);
-            builder.audio_bitrate(bitrate)
+        Some(ms) => {
+            tracing::warn!(
+                frame_duration = ms,
+                "Unsupported Opus frame duration ignored"
+            );
+            builder
         }
+        None => builder,
     }
 }
 
@@ -210,27 +345,24 @@ fn apply_lossless_settings(
     fmt: &AudioFormat,
     settings: &ConversionSettings,
 ) -> FfmpegBuilder {
-    let quality = settings.quality.as_str();
-
-    match fmt.codec.as_str() {
-        "flac" | "wavpack" => {
-            let level = match quality {
-                "low" => "0",
-                "medium" => "5",
-                "high" => "8",
-                "ultra" => "12",
-                _ => "5",
-            };
-            builder.arg("-compression_level", level)
+    match defaults::compression_level(fmt, settings.quality) {
+        Some(level) => {
+            let level = settings
+                .compression_level
+                .map_or(level, |level| level.min(defaults::COMPRESSION_LEVELS.1));
+            builder.arg("-compression_level", &level.to_string())
         }
-        _ => builder,
+        None => builder,
     }
 }
 
 fn apply_container_and_params(builder: FfmpegBuilder, fmt: &AudioFormat) -> FfmpegBuilder {
-    let mut builder = builder;
-    if let Some(container) = &fmt.container {
-        builder = builder.format(container);
+    // Named even where the extension implies it: a streamed output has none
+    let muxer = fmt.container.as_deref().unwrap_or(&fmt.extension);
+    let mut builder = builder.format(muxer).args_vec(&fmt.special_params);
+
+    if fmt.container.as_deref() == Some("mp4") {
+        builder = builder.faststart();
     }
-    builder.args_vec(&fmt.special_params)
+    builder
 }
@@ -1,12 +1,21 @@
-use crate::types::{FileMetadata, Quality};
-use std::path::PathBuf;
+use super::filters;
+use crate::codec_map;
+use crate::types::{
+    CollisionPolicy, Deinterlacer, FileMetadata, FilterStrength, Flip, Quality, RateControl,
+    ScaleMode,
+};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
 
 pub struct FfmpegBuilder {
     input: PathBuf,
     output: PathBuf,
     args: Vec<String>,
     filters: Vec<String>,
+    audio_filters: Vec<String>,
     filter_complex: Option<String>,
+    x265_params: Vec<String>,
+    extra_args: Vec<String>,
 }
 
 impl FfmpegBuilder {
@@ -16,7 +25,10 @@ impl FfmpegBuilder {
             output: PathBuf::from(output),
             args: Vec::with_capacity(32),
             filters: Vec::with_capacity(4),
+            audio_filters: Vec::new(),
             filter_complex: None,
+            x265_params: Vec::new(),
+            extra_args: Vec::new(),
         }
     }
 
@@ -26,11 +38,29 @@ impl FfmpegBuilder {
         self
     }
 
+    /// Add another input after the main one, preceded by its own input options.
+    /// Its streams are `1:...` in `-map` specs.
+    pub fn extra_input(mut self, path: &str, input_options: &[String]) -> Self {
+        self.args.extend_from_slice(input_options);
+        self.args.push("-i".to_string());
+        self.args.push(path.to_string());
+        self
+    }
+
     pub fn overwrite(mut self) -> Self {
         self.args.push("-y".to_string());
         self
     }
 
+    /// `-y` when existing output may be replaced, otherwise `-n` so FFmpeg
+    /// refuses to clobber a file that appeared after the path was checked.
+    pub fn on_collision(self, policy: CollisionPolicy) -> Self {
+        match policy {
+            CollisionPolicy::Overwrite => self.overwrite(),
+            CollisionPolicy::Rename | CollisionPolicy::Fail => self.flag("-n"),
+        }
+    }
+
     pub fn hide_banner(mut self) -> Self {
         self.args.push("-hide_banner".to_string());
         self
@@ -42,6 +72,17 @@ impl FfmpegBuilder {
         self
     }
 
+    /// Insert input-side options (e.g. hardware decoding) in front of `-i`.
+    pub fn input_args(mut self, args: &[String]) -> Self {
+        let pos = self
+            .args
+            .iter()
+            .position(|a| a == "-i")
+            .unwrap_or(self.args.len());
+        self.args.splice(pos..pos, args.iter().cloned());
+        self
+    }
+
     pub fn arg(mut self, key: &str, value: &str) -> Self {
         self.args.push(key.to_string());
         self.args.push(value.to_string());
@@ -53,6 +94,28 @@ impl FfmpegBuilder {
         self
     }
 
+    /// Remove a previously added `key value` pair (e.g. a preset's `-crf`).
+    pub fn remove_arg(mut self, key: &str) -> Self {
+        if let Some(pos) = self.args.iter().position(|a| a == key) {
+            let end = (pos + 2).min(self.args.len());
+            self.args.drain(pos..end);
+        }
+        self
+    }
+
+    /// Add a `key=value` entry to `-x265-params`. All entries are merged into a
+    /// single option at build time, since FFmpeg keeps only the last occurrence.
+    pub fn x265_param(mut self, key: &str, value: &str) -> Self {
+        self.x265_params.push(format!("{}={}", key, value));
+        self
+    }
+
+    /// User-supplied options, emitted after everything else at build time.
+    pub fn extra_args(mut self, args: &[String]) -> Self {
+        self.extra_args.extend_from_slice(args);
+        self
+    }
+
     pub fn args_vec(mut self, args: &[String]) -> Self {
         self.args.extend_from_slice(args);
         self
@@ -74,6 +137,28 @@ impl FfmpegBuilder {
         self.arg("-c:a", codec)
     }
 
+    pub fn map(self, spec: &str) -> Self {
+        self.arg("-map", spec)
+    }
+
+    /// Keep only audio track `index` of the input; FFmpeg picks one when `None`.
+    pub fn audio_track(self, index: Option<usize>) -> Self {
+        match index {
+            Some(index) => self.map(&format!("0:a:{}", index)),
+            None => self,
+        }
+    }
+
+    /// Whether streams are selected explicitly rather than by FFmpeg's defaults.
+    pub fn has_maps(&self) -> bool {
+        self.args.iter().any(|a| a == "-map")
+    }
+
+    /// Codec for the `index`-th subtitle stream of the output.
+    pub fn subtitle_codec(self, index: usize, codec: &str) -> Self {
+        self.arg(&format!("-c:s:{}", index), codec)
+    }
+
     pub fn format(self, container: &str) -> Self {
         self.arg("-f", container)
     }
@@ -94,6 +179,41 @@ impl FfmpegBuilder {
         self.arg("-r", &fps.to_string())
     }
 
+    pub fn b_frames(self, count: u32) -> Self {
+        self.arg("-bf", &count.to_string())
+    }
+
+    pub fn ref_frames(self, count: u32) -> Self {
+        self.arg("-refs", &count.to_string())
+    }
+
+    pub fn gop(self, frames: u32) -> Self {
+        self.arg("-g", &frames.to_string())
+    }
+
+    pub fn keyint_min(self, frames: u32) -> Self {
+        self.arg("-keyint_min", &frames.to_string())
+    }
+
+    /// Keyframe at every multiple of `seconds`, whatever the GOP settings.
+    pub fn force_keyframes_every(self, seconds: f64) -> Self {
+        self.arg(
+            "-force_key_frames",
+            &format!("expr:gte(t,n_forced*{})", seconds),
+        )
+    }
+
+    /// Cap encoder threads. libx265 sizes its own thread pool and ignores
+    /// `-threads`, so it gets `pools` as well.
+    pub fn threads(self, encoder: &str, count: u32) -> Self {
+        let builder = self.arg("-threads", &count.to_string());
+        if encoder == "libx265" {
+            builder.x265_param("pools", &count.to_string())
+        } else {
+            builder
+        }
+    }
+
     pub fn resolution(
         mut self,
         width: Option<u32>,
@@ -119,6 +239,83 @@ impl FfmpegBuilder {
         self
     }
 
+    /// Scale to exactly `width`x`height`, keeping or dropping the aspect ratio
+    /// per `mode`. `source` is the size of the picture reaching the scaler.
+    pub fn scale_to_frame(
+        mut self,
+        width: u32,
+        height: u32,
+        mode: ScaleMode,
+        source: Option<(u32, u32)>,
+    ) -> Self {
+        self.filters
+            .push(filters::scale_to_frame(width, height, mode, source));
+        self
+    }
+
+    /// Turn interlaced fields into progressive frames. Goes first in the chain:
+    /// cropping or scaling before it mixes lines from different fields.
+    pub fn deinterlace(mut self, deinterlacer: Deinterlacer) -> Self {
+        self.filters.push(deinterlacer.filter().to_string());
+        self
+    }
+
+    /// Tone-map HDR frames to 8-bit SDR and tag the output as BT.709, so
+    /// players don't treat the result as HDR.
+    pub fn tonemap_sdr(mut self) -> Self {
+        self.filters.push(filters::TONEMAP_SDR.to_string());
+        self.arg("-color_primaries", "bt709")
+            .arg("-color_trc", "bt709")
+            .arg("-colorspace", "bt709")
+    }
+
+    /// Keep a `width`x`height` region whose top-left corner is at (`x`, `y`).
+    /// Must come before any scale filter, since the offsets are in source pixels.
+    pub fn crop(mut self, width: u32, height: u32, x: u32, y: u32) -> Self {
+        self.filters
+            .push(format!("crop={}:{}:{}:{}", width, height, x, y));
+        self
+    }
+
+    /// Rotate clockwise by 90, 180 or 270 degrees; other angles are ignored.
+    /// Quarter turns swap width and height, so a following scale sees the
+    /// rotated dimensions.
+    pub fn rotate(mut self, degrees: u32) -> Self {
+        if let Some(filter) = filters::rotation_filter(degrees) {
+            self.filters.push(filter.to_string());
+        }
+        self
+    }
+
+    /// Keep a rotation-tagged source's picture as stored instead of letting
+    /// FFmpeg turn it upright on decode. The display matrix is carried over,
+    /// so players still show it upright.
+    pub fn no_autorotate(self) -> Self {
+        self.input_args(&["-noautorotate".to_string()])
+    }
+
+    pub fn flip(mut self, flip: Flip) -> Self {
+        self.filters.push(flip.filter().to_string());
+        self
+    }
+
+    /// Reduce noise. Belongs before any scale filter: downscaling first
+    /// bakes the noise into fewer, larger pixels.
+    pub fn denoise(mut self, strength: FilterStrength) -> Self {
+        if let Some(filter) = strength.denoise_filter() {
+            self.filters.push(filter.to_string());
+        }
+        self
+    }
+
+    /// Sharpen edges. Belongs after scaling, which softens the picture.
+    pub fn sharpen(mut self, strength: FilterStrength) -> Self {
+        if let Some(filter) = strength.sharpen_filter() {
+            self.filters.push(filter.to_string());
+        }
+        self
+    }
+
     /// Scale to fit within a bounding box while maintaining aspect ratio.
     /// Output dimensions are rounded to even numbers (required by most codecs).
     /// Used for auto-downscaling when source exceeds format's max_resolution.
@@ -130,22 +327,110 @@ impl FfmpegBuilder {
         self
     }
 
+    /// Render an external subtitle file into the picture.
+    pub fn burn_subtitles(mut self, path: &str) -> Self {
+        self.filters
+            .push(format!("subtitles='{}'", escape_filter_path(path)));
+        self
+    }
+
+    /// Whether decoded frames stay in GPU memory (`-hwaccel_output_format`),
+    /// which CPU-side filters such as `subtitles` can't read.
+    pub fn has_hw_frames_output(&self) -> bool {
+        self.args.iter().any(|a| a == "-hwaccel_output_format")
+    }
+
+    /// Keep decoded frames in system memory. The hardware decoder (and its
+    /// device, which `hwupload` needs) stays; frames are copied back from the GPU.
+    pub fn download_frames(self) -> Self {
+        self.remove_arg("-hwaccel_output_format")
+    }
+
+    pub fn has_video_filters(&self) -> bool {
+        !self.filters.is_empty()
+    }
+
+    /// Upload frames to the hardware device (VAAPI encoders read GPU surfaces only).
+    pub fn hw_upload(mut self) -> Self {
+        self.filters.push("hwupload".to_string());
+        self
+    }
+
+    /// Append a filter to the `-vf` chain.
+    pub fn video_filter(mut self, filter: &str) -> Self {
+        self.filters.push(filter.to_string());
+        self
+    }
+
     pub fn pixel_format(mut self, fmt: &str) -> Self {
         self.filters.push(format!("format={}", fmt));
         self
     }
 
+    /// How many times an animation plays (0 = forever). The GIF muxer counts
+    /// repeats after the first play, with -1 for none; WebP counts plays.
+    pub fn loop_count(self, container: &str, plays: u32) -> Self {
+        let value = match (container, plays) {
+            (_, 0) => 0,
+            ("gif", 1) => -1,
+            ("gif", n) => n as i64 - 1,
+            (_, n) => n as i64,
+        };
+        self.arg("-loop", &value.to_string())
+    }
+
+    /// Move the MP4/MOV index (`moov`) to the front so the file plays and seeks
+    /// before it is fully downloaded. Kept as-is if the format already sets movflags.
+    pub fn faststart(self) -> Self {
+        if self.args.iter().any(|a| a == "-movflags") {
+            self
+        } else {
+            self.arg("-movflags", "+faststart")
+        }
+    }
+
+    /// Write fragmented MP4: a fragment per keyframe behind an empty `moov`,
+    /// so the file streams while it is still being written. Takes the place
+    /// of `faststart`, which has no index to move.
+    pub fn fragmented(self) -> Self {
+        self.arg("-movflags", "+frag_keyframe+empty_moov+default_base_moof")
+    }
+
+    /// Set the MP4 `major_brand`. Files for old players also need the index up front.
+    pub fn mp4_brand(self, brand: &str) -> Self {
+        self.faststart().arg("-brand", brand)
+    }
+
+    /// Append audio filters; all of them end up comma-joined in a single `-af`.
+    pub fn audio_filters(mut self, filters: &[String]) -> Self {
+        self.audio_filters.extend_from_slice(filters);
+        self
+    }
+
     pub fn filter_complex(mut self, fc: &str) -> Self {
         self.filter_complex = Some(fc.to_string());
         self
     }
 
-    pub fn metadata(mut self, meta: &Option<FileMetadata>) -> Self {
-        let args = meta
-            .as_ref()
-            .map(|m| m.to_ffmpeg_args())
-            .unwrap_or_else(|| vec!["-map_metadata".to_string(), "-1".to_string()]);
-        self.args.extend(args);
+    /// Two-pass rate control. `log_prefix` is the pass log path without extension.
+    /// libx265 takes its pass settings through `-x265-params`; other encoders use `-pass`.
+    pub fn two_pass(self, pass: u8, log_prefix: &Path, codec: &str) -> Self {
+        if codec.contains("libx265") {
+            let stats = escape_x265_value(&format!("{}.log", log_prefix.to_string_lossy()));
+            self.x265_param("pass", &pass.to_string())
+                .x265_param("stats", &stats)
+        } else {
+            self.arg("-pass", &pass.to_string())
+                .arg("-passlogfile", &log_prefix.to_string_lossy())
+        }
+    }
+
+    /// Write the user's metadata edits. `preserve` copies every source tag and
+    /// chapter first, with the edits applied on top; when unset, source metadata
+    /// is stripped or kept according to [`set_default_preserve_metadata`].
+    pub fn metadata(mut self, meta: &Option<FileMetadata>, preserve: Option<bool>) -> Self {
+        let by_default = PRESERVE_METADATA_BY_DEFAULT.load(Ordering::Relaxed);
+        self.args.extend(metadata_args(meta, preserve, by_default));
         self
     }
 
@@ -181,6 +466,8 @@ impl FfmpegBuilder {
             .arg("-look_ahead", "1")
     }
 
+    /// Usage and quality profile only. Without a bitrate or `amf_cqp` the
+    /// encoder needs an explicit target, since constant QP crashes older drivers.
     pub fn amf_preset(self, quality: Quality) -> Self {
         let (usage, qual_profile) = match quality {
             Quality::Low => ("transcoding", "speed"),
@@ -193,6 +480,71 @@ impl FfmpegBuilder {
             .arg("-profile:v", "main")
     }
 
+    /// Replace the quality-derived `-preset` with an explicit one. Presets
+    /// `encoder` doesn't know are ignored so a GPU→software retry still runs.
+    pub fn encoder_preset(self, encoder: &str, preset: &str) -> Self {
+        let allowed = codec_map::encoder_presets(encoder).unwrap_or_default();
+        if !allowed.contains(&preset) {
+            tracing::warn!(
+                encoder,
+                preset,
+                "Preset not supported by encoder, keeping default"
+            );
+            return self;
+        }
+        self.remove_arg("-preset").arg("-preset", preset)
+    }
+
+    /// AMF constant QP derived from the quality preset. AV1 QPs run to 255,
+    /// so the H.264-scale value is stretched to match.
+    pub fn amf_cqp(self, encoder: &str, quality: Quality) -> Self {
+        let qp: u32 = quality.video_crf().parse().unwrap_or(23);
+        let qp = if encoder == "av1_amf" { qp * 5 } else { qp };
+        self.constant_quality(encoder, qp)
+    }
+
+    /// Replace the preset's constant-quality value with a user-chosen one, clamped
+    /// to the range `encoder` accepts. Encoders without a quality scale keep their preset.
+    pub fn constant_quality(self, encoder: &str, value: u32) -> Self {
+        let Some(scale) = codec_map::quality_scale(encoder) else {
+            tracing::warn!(
+                encoder,
+                "Encoder has no constant-quality scale, keeping preset"
+            );
+            return self;
+        };
+
+        let clamped = scale.clamp(value);
+        if clamped != value {
+            tracing::warn!(
+                encoder,
+                requested = value,
+                used = clamped,
+                min = scale.min,
+                max = scale.max,
+                "Quality value out of encoder range, clamped"
+            );
+        }
+        let value = clamped.to_string();
+
+        let builder = self.remove_arg(scale.option);
+        if encoder.contains("amf") {
+            // AMF takes per-frame-type QPs under constant-QP rate control
+            builder
+                .remove_arg("-qp_p")
+                .arg("-rc", "cqp")
+                .arg("-qp_i", &value)
+                .arg("-qp_p", &value)
+        } else {
+            builder.arg(scale.option, &value)
+        }
+    }
+
+    /// VAAPI: constant QP unless a bitrate is set (rate control is picked automatically)
+    pub fn vaapi_preset(self, quality: Quality) -> Self {
+        self.arg("-qp", quality.video_crf())
+    }
+
     pub fn videotoolbox_preset(self) -> Self {
         self.arg("-profile:v", "high").arg("-allow_sw", "1")
     }
@@ -239,6 +591,22 @@ impl FfmpegBuilder {
             .arg("-tiles", "2x2")
     }
 
+    /// Still-image encoders: AV1 in still-picture mode for AVIF, libjxl's
+    /// Butteraugli distance for JPEG XL.
+    pub fn still_image_preset(self, encoder: &str, quality: Quality) -> Self {
+        match encoder {
+            "libaom-av1" => self
+                .arg("-crf", quality.avif_crf())
+                .arg("-b:v", "0")
+                .arg("-still-picture", "1")
+                .arg("-cpu-used", "4")
+                .arg("-row-mt", "1"),
+            "libsvtav1" => self.arg("-crf", quality.avif_crf()),
+            "libjxl" => self.arg("-distance", quality.jxl_distance()),
+            _ => self,
+        }
+    }
+
     pub fn mpeg_preset(self, quality: Quality) -> Self {
         let bitrate = match quality {
             Quality::Low => "2000k",
@@ -316,15 +684,42 @@ impl FfmpegBuilder {
         self.arg("-q:v", q)
     }
 
+    /// ProRes Proxy (low) up to 4444 (ultra), tagged as Apple's so editors
+    /// don't flag the file.
     pub fn prores_preset(self, quality: Quality) -> Self {
-        let profile = match quality {
-            Quality::Low => "0",
-            Quality::Medium => "2",
-            Quality::High => "3",
-            Quality::Ultra => "4",
-            Quality::Custom => "2",
+        self.intermediate_profile("prores_ks", quality)
+            .arg("-vendor", "apl0")
+    }
+
+    /// DNxHR LB (low) up to HQX (ultra), which unlike DNxHD take any frame size.
+    pub fn dnxhd_preset(self, quality: Quality) -> Self {
+        self.intermediate_profile("dnxhd", quality)
+    }
+
+    fn intermediate_profile(self, encoder: &str, quality: Quality) -> Self {
+        match codec_map::intermediate_profile(encoder, quality) {
+            Some(profile) => self.arg("-profile:v", profile.profile),
+            None => self,
+        }
+    }
+
+    /// FFV1 version 3 — the archival/preservation profile: intra-only,
+    /// range coder and per-slice CRCs so damage can be detected and contained.
+    pub fn ffv1_preset(self, quality: Quality) -> Self {
+        let context = match quality {
+            Quality::High | Quality::Ultra => "1",
+            _ => "0",
         };
-        self.arg("-profile:v", profile).arg("-vendor", "apl0")
+        self.arg("-level", "3")
+            .arg("-g", "1")
+            .arg("-coder", "1")
+            .arg("-context", context)
+            .arg("-slicecrc", "1")
+    }
+
+    /// Slice count for FFV1 v3; slices are encoded in parallel (one thread each).
+    pub fn ffv1_slices(self, slices: u32) -> Self {
+        self.arg("-slices", &slices.to_string())
     }
 
     pub fn generic_bitrate_preset(self, quality: Quality) -> Self {
@@ -340,12 +735,35 @@ impl FfmpegBuilder {
 
     // ========== Master codec router ==========
 
-    pub fn apply_video_codec_preset(self, codec: &str, quality: Quality) -> Self {
+    /// Encoder defaults for `quality`, switched to bitrate targeting when
+    /// `rate_control` asks for it and a `bitrate` (kbps) is given.
+    pub fn apply_video_codec_preset(
+        self,
+        codec: &str,
+        quality: Quality,
+        rate_control: RateControl,
+        bitrate: Option<u32>,
+    ) -> Self {
+        let builder = self.video_codec_preset(codec, quality);
+        match bitrate {
+            // Intermediates have no bitrate control, the profile sets it
+            Some(kbps)
+                if rate_control != RateControl::Crf
+                    && !codec_map::is_intermediate_encoder(codec) =>
+            {
+                builder.rate_control(codec, rate_control, kbps)
+            }
+            _ => builder,
+        }
+    }
+
+    fn video_codec_preset(self, codec: &str, quality: Quality) -> Self {
         match codec {
             c if c.contains("nvenc") => self.nvenc_preset(quality),
             c if c.contains("qsv") => self.qsv_preset(quality),
             c if c.contains("amf") => self.amf_preset(quality),
             c if c.contains("videotoolbox") => self.videotoolbox_preset(),
+            c if c.contains("vaapi") => self.vaapi_preset(quality),
             c if c.contains("libx264") => self.x264_preset(quality),
             c if c.contains("libx265") => self.x265_preset(quality),
             c if c.contains("libvpx-vp9") => self.vpx_preset(quality, true),
@@ -360,12 +778,49 @@ impl FfmpegBuilder {
             "wmv1" | "wmv2" => self.wmv_preset(quality),
             "mjpeg" => self.mjpeg_preset(quality),
             c if c.contains("prores") => self.prores_preset(quality),
-            "copy" | "rawvideo" | "gif" | "dvvideo" => self,
+            "dnxhd" => self.dnxhd_preset(quality),
+            "ffv1" => self.ffv1_preset(quality),
+            "copy" | "rawvideo" | "gif" | "dvvideo" | "v210" => self,
             _ => self.generic_bitrate_preset(quality),
         }
     }
 
+    /// Replace the preset's constant-quality option with a `kbps` target. CBR
+    /// pins min/max rate to the target with a one-second VBV buffer and, where
+    /// the encoder has one, switches on its strict CBR mode.
+    fn rate_control(self, codec: &str, mode: RateControl, kbps: u32) -> Self {
+        let builder = match codec_map::quality_scale(codec) {
+            Some(scale) => self.remove_arg(scale.option),
+            None => self,
+        };
+        // VP8/VP9 presets set `-b:v 0` for pure constant quality
+        let rate = format!("{}k", kbps);
+        let builder = builder.remove_arg("-b:v").arg("-b:v", &rate);
+        if mode != RateControl::Cbr {
+            return builder;
+        }
+
+        let builder = builder
+            .arg("-minrate", &rate)
+            .arg("-maxrate", &rate)
+            .arg("-bufsize", &rate);
+        match codec {
+            "libx264" => builder.arg("-x264-params", "nal-hrd=cbr"),
+            "libx265" => builder.x265_param("strict-cbr", "1"),
+            c if c.contains("nvenc") || c.contains("amf") => {
+                builder.remove_arg("-rc").arg("-rc", "cbr")
+            }
+            c if codec_map::is_vaapi_encoder(c) => builder.arg("-rc_mode", "CBR"),
+            _ => builder,
+        }
+    }
+
     pub fn build(mut self) -> (Vec<String>, String) {
+        if !self.x265_params.is_empty() {
+            self.args.push("-x265-params".to_string());
+            self.args.push(self.x265_params.join(":"));
+        }
+
         if let Some(fc) = &self.filter_complex {
             self.args.push("-filter_complex".to_string());
             self.args.push(fc.clone());
@@ -374,9 +829,435 @@ impl FfmpegBuilder {
             self.args.push(self.filters.join(","));
         }
 
+        if !self.audio_filters.is_empty() {
+            self.args.push("-af".to_string());
+            self.args.push(self.audio_filters.join(","));
+        }
+
+        self.args.append(&mut self.extra_args);
+
         let output = self.output.to_string_lossy().to_string();
         self.args.push(output.clone());
 
         (self.args, output)
     }
 }
+
+/// Policy for conversions without metadata edits. Off by default: source
+/// metadata (tags, encoder info, GPS/camera data) is stripped.
+static PRESERVE_METADATA_BY_DEFAULT: AtomicBool = AtomicBool::new(false);
+
+/// Keep source metadata on conversions that don't edit it, instead of
+/// stripping it. Affects every conversion started afterwards.
+pub fn set_default_preserve_metadata(preserve: bool) {
+    PRESERVE_METADATA_BY_DEFAULT.store(preserve, Ordering::Relaxed);
+}
+
+fn metadata_args(
+    meta: &Option<FileMetadata>,
+    preserve: Option<bool>,
+    preserve_by_default: bool,
+) -> Vec<String> {
+    if preserve == Some(true) {
+        let mut args: Vec<String> = ["-map_metadata", "0", "-map_chapters", "0"]
+            .iter()
+            .map(|a| a.to_string())
+            .collect();
+        // Later `-metadata` options override the copied tags one by one
+        if let Some(m) = meta {
+            args.extend(m.tag_args());
+        }
+        return args;
+    }
+
+    match meta {
+        Some(m) => m.to_ffmpeg_args(),
+        // FFmpeg copies global metadata from the first input unless told otherwise
+        None if preserve.unwrap_or(preserve_by_default) => Vec::new(),
+        None => vec!["-map_metadata".to_string(), "-1".to_string()],
+    }
+}
+
+/// Escape a file path for a filter option inside single quotes. Two levels
+/// apply: the filtergraph strips the quotes, then the option parser treats `:`
+/// as a separator — so Windows drive colons need `\:`. Backslashes become forward
+/// slashes and a literal `'` closes the quote, adds an escaped quote, and reopens.
+fn escape_filter_path(path: &str) -> String {
+    path.replace('\\', "/")
+        .replace(':', "\\:")
+        .replace('\'', "'\\\\\\''")
+}
+
+/// Escape a value for `-x265-params`, where `:` separates entries.
+/// Backslashes become forward slashes (accepted by x265 on Windows too) so
+/// drive-letter colons are the only thing that needs escaping.
+fn escape_x265_value(value: &str) -> String {
+    value.replace('\\', "/").replace(':', "\\:")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A tagged 1080p HEVC phone clip with an AAC stereo track.
+    fn source_media(rotation: Option<u32>) -> crate::media::MediaInfo {
+        use crate::media::{AudioStream, MediaInfo, MediaType, VideoStream};
+
+        MediaInfo {
+            media_type: MediaType::Video,
+            duration: 10.0,
+            file_size: 1_000_000,
+            format_name: "mov,mp4,m4a,3gp,3g2,mj2".to_string(),
+            bitrate: None,
+            video_streams: vec![VideoStream {
+                codec: "hevc".to_string(),
+                width: 1920,
+                height: 1080,
+                fps: 30.0,
+                bitrate: None,
+                field_order: None,
+                color_transfer: None,
+                color_primaries: None,
+                color_space: None,
+                rotation,
+            }],
+            audio_streams: vec![AudioStream {
+                codec: "aac".to_string(),
+                sample_rate: 48000,
+                channels: 2,
+                bitrate: Some(128_000),
+                language: None,
+                title: None,
+            }],
+            subtitle_streams: Vec::new(),
+            attachments: Vec::new(),
+            tags: None,
+            chapter_count: 0,
+            mismatch_warning: None,
+        }
+    }
+
+    #[test]
+    fn test_metadata_default_policy() {
+        assert_eq!(
+            metadata_args(&None, None, false),
+            vec!["-map_metadata", "-1"]
+        );
+        assert!(metadata_args(&None, None, true).is_empty());
+
+        let edited = Some(FileMetadata {
+            title: Some("Demo".to_string()),
+            ..Default::default()
+        });
+        // Explicit edits replace the source metadata, whatever the default policy
+        for preserve in [false, true] {
+            assert_eq!(
+                metadata_args(&edited, None, preserve),
+                vec!["-map_metadata", "-1", "-metadata", "title=Demo"]
+            );
+        }
+    }
+
+    #[test]
+    fn test_remux_metadata_policy() {
+        use crate::converter::video::remux_preserve_metadata;
+        use crate::types::ConversionSettings;
+
+        let remux = ConversionSettings {
+            remux_only: true,
+            ..Default::default()
+        };
+        // A plain remux follows the app-wide default
+        assert!(metadata_args(&None, remux_preserve_metadata(&remux), true).is_empty());
+        assert_eq!(
+            metadata_args(&None, remux_preserve_metadata(&remux), false),
+            vec!["-map_metadata", "-1"]
+        );
+
+        let all_streams = ConversionSettings {
+            keep_all_streams: true,
+            ..remux.clone()
+        };
+        assert_eq!(
+            metadata_args(&None, remux_preserve_metadata(&all_streams), false),
+            vec!["-map_metadata", "0", "-map_chapters", "0"]
+        );
+
+        let strip = ConversionSettings {
+            preserve_metadata: Some(false),
+            ..all_streams
+        };
+        assert_eq!(
+            metadata_args(&None, remux_preserve_metadata(&strip), true),
+            vec!["-map_metadata", "-1"]
+        );
+    }
+
+    #[test]
+    fn test_metadata_preserve_with_edits() {
+        let edited = Some(FileMetadata {
+            title: Some("Demo".to_string()),
+            ..Default::default()
+        });
+        assert_eq!(
+            metadata_args(&edited, Some(true), false),
+            vec![
+                "-map_metadata",
+                "0",
+                "-map_chapters",
+                "0",
+                "-metadata",
+                "title=Demo"
+            ]
+        );
+        // An explicit "don't preserve" beats a preserving default
+        assert_eq!(
+            metadata_args(&None, Some(false), true),
+            vec!["-map_metadata", "-1"]
+        );
+    }
+
+    #[test]
+    fn test_constant_quality_override() {
+        let (args, _) = FfmpegBuilder::new("in.mp4", "out.mp4")
+            .nvenc_preset(Quality::Medium)
+            .constant_quality("h264_nvenc", 60)
+            .build();
+        let cq = args.iter().position(|a| a == "-cq").unwrap();
+        assert_eq!(args[cq + 1], "51");
+        assert_eq!(args.iter().filter(|a| *a == "-cq").count(), 1);
+
+        let (args, _) = FfmpegBuilder::new("in.mp4", "out.mp4")
+            .amf_preset(Quality::High)
+            .constant_quality("hevc_amf", 20)
+            .build();
+        let joined = args.join(" ");
+        assert!(joined.contains("-rc cqp -qp_i 20 -qp_p 20"));
+    }
+
+    #[test]
+    fn test_encoder_preset_override() {
+        let (args, _) = FfmpegBuilder::new("in.mp4", "out.mp4")
+            .x264_preset(Quality::Ultra)
+            .encoder_preset("libx264", "fast")
+            .build();
+        assert!(args.join(" ").contains("-crf 15 -preset fast"));
+        assert_eq!(args.iter().filter(|a| *a == "-preset").count(), 1);
+
+        // NVENC names mean nothing to x264
+        let (args, _) = FfmpegBuilder::new("in.mp4", "out.mp4")
+            .x264_preset(Quality::Ultra)
+            .encoder_preset("libx264", "p7")
+            .build();
+        assert!(args.join(" ").contains("-preset veryslow"));
+    }
+
+    #[test]
+    fn test_loop_count() {
+        let loop_arg = |container: &str, plays: u32| {
+            let (args, _) = FfmpegBuilder::new("in.mp4", "out")
+                .loop_count(container, plays)
+                .build();
+            args[args.iter().position(|a| a == "-loop").unwrap() + 1].clone()
+        };
+        assert_eq!(loop_arg("gif", 0), "0");
+        assert_eq!(loop_arg("gif", 1), "-1");
+        assert_eq!(loop_arg("gif", 3), "2");
+        assert_eq!(loop_arg("webp", 3), "3");
+    }
+
+    #[test]
+    fn test_still_image_preset() {
+        let still_args = |encoder: &str| {
+            let (args, _) = FfmpegBuilder::new("in.mp4", "out")
+                .still_image_preset(encoder, Quality::High)
+                .build();
+            args.join(" ")
+        };
+        assert_eq!(
+            still_args("libaom-av1"),
+            "-crf 24 -b:v 0 -still-picture 1 -cpu-used 4 -row-mt 1 out"
+        );
+        assert_eq!(still_args("libsvtav1"), "-crf 24 out");
+        assert_eq!(still_args("libjxl"), "-distance 1.0 out");
+    }
+
+    #[test]
+    fn test_amf_cqp_from_quality() {
+        let (args, _) = FfmpegBuilder::new("in.mp4", "out.mp4")
+            .amf_preset(Quality::Medium)
+            .amf_cqp("h264_amf", Quality::Medium)
+            .build();
+        assert!(args.join(" ").contains("-rc cqp -qp_i 23 -qp_p 23"));
+
+        let (args, _) = FfmpegBuilder::new("in.mp4", "out.mp4")
+            .amf_cqp("av1_amf", Quality::Medium)
+            .build();
+        assert!(args.join(" ").contains("-qp_i 115 -qp_p 115"));
+    }
+
+    #[test]
+    fn test_crop_before_scale() {
+        let (args, _) = FfmpegBuilder::new("in.mp4", "out.mp4")
+            .crop(1920, 800, 0, 140)
+            .resolution(Some(1280), None, false)
+            .build();
+        let vf = args.iter().position(|a| a == "-vf").unwrap();
+        assert_eq!(args[vf + 1], "crop=1920:800:0:140,scale=1280:-2");
+    }
+
+    #[test]
+    fn test_orientation_filters() {
+        let (args, _) = FfmpegBuilder::new("in.mp4", "out.mp4")
+            .rotate(180)
+            .flip(Flip::Horizontal)
+            .rotate(45)
+            .build();
+        let vf = args.iter().position(|a| a == "-vf").unwrap();
+        assert_eq!(args[vf + 1], "transpose=2,transpose=2,hflip");
+    }
+
+    #[test]
+    fn test_source_orientation() {
+        use crate::converter::video::apply_source_orientation;
+        use crate::types::ConversionSettings;
+
+        let media = source_media(Some(90));
+        let args = |settings: &ConversionSettings| {
+            let mut builder = apply_source_orientation(
+                FfmpegBuilder::new("in.mp4", "out.mp4").input_file(),
+                &media,
+                settings,
+            );
+            if let Some(degrees) = settings.rotation() {
+                builder = builder.rotate(degrees);
+            }
+            builder.build().0.join(" ")
+        };
+
+        // FFmpeg's auto-rotation turns the tagged source upright on its own
+        let tagged = ConversionSettings {
+            source_rotation: Some(90),
+            ..Default::default()
+        };
+        assert_eq!(args(&tagged), "-i in.mp4 out.mp4");
+
+        let keep = ConversionSettings {
+            keep_orientation: true,
+            ..Default::default()
+        };
+        assert_eq!(args(&keep), "-noautorotate -i in.mp4 out.mp4");
+
+        // Only the user's own turn is filtered, on top of the upright picture
+        let rotated = ConversionSettings {
+            rotate: Some(90),
+            ..tagged
+        };
+        assert_eq!(args(&rotated), "-i in.mp4 -vf transpose=1 out.mp4");
+    }
+
+    #[test]
+    fn test_requested_audio_copy_with_filters() {
+        use crate::converter::video::apply_audio_settings;
+        use crate::formats::video;
+        use crate::types::ConversionSettings;
+
+        let fmt = video::get_format("mp4").unwrap();
+        let media = source_media(None);
+        let args = |settings: &ConversionSettings| {
+            let builder = FfmpegBuilder::new("in.mp4", "out.mp4");
+            apply_audio_settings(builder, &fmt, &media, settings)
+                .build()
+                .0
+                .join(" ")
+        };
+
+        let copy = ConversionSettings {
+            audio_codec: Some("copy".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(args(&copy), "-c:a copy out.mp4");
+
+        // Filtering and stream copy can't be combined, so the audio is re-encoded
+        let louder = ConversionSettings {
+            volume_db: Some(3.0),
+            ..copy
+        };
+        let louder = args(&louder);
+        assert!(!louder.contains("-c:a copy"));
+        assert!(louder.contains("-af volume=3dB"));
+    }
+
+    #[test]
+    fn test_denoise_and_sharpen_around_scale() {
+        let (args, _) = FfmpegBuilder::new("in.mp4", "out.mp4")
+            .denoise(FilterStrength::Light)
+            .resolution(Some(1280), None, false)
+            .sharpen(FilterStrength::Medium)
+            .sharpen(FilterStrength::Off)
+            .build();
+        let vf = args.iter().position(|a| a == "-vf").unwrap();
+        assert_eq!(
+            args[vf + 1],
+            "hqdn3d=2:1.5:3:2.25,scale=1280:-2,unsharp=5:5:1.0:5:5:0"
+        );
+    }
+
+    #[test]
+    fn test_cbr_rate_control() {
+        let (args, _) = FfmpegBuilder::new("in.mp4", "out.flv")
+            .apply_video_codec_preset("libx264", Quality::Medium, RateControl::Cbr, Some(6000))
+            .build();
+        assert!(!args.contains(&"-crf".to_string()));
+        for key in ["-b:v", "-minrate", "-maxrate", "-bufsize"] {
+            let pos = args.iter().position(|a| a == key).unwrap();
+            assert_eq!(args[pos + 1], "6000k");
+        }
+        assert!(args
+            .windows(2)
+            .any(|w| w == ["-x264-params", "nal-hrd=cbr"]));
+
+        let (args, _) = FfmpegBuilder::new("in.mp4", "out.flv")
+            .apply_video_codec_preset("h264_nvenc", Quality::Medium, RateControl::Cbr, Some(6000))
+            .build();
+        assert!(args.windows(2).any(|w| w == ["-rc", "cbr"]));
+        assert!(!args.contains(&"-cq".to_string()));
+
+        // No bitrate: the preset's constant quality stays
+        let (args, _) = FfmpegBuilder::new("in.mp4", "out.mp4")
+            .apply_video_codec_preset("libx264", Quality::Medium, RateControl::Cbr, None)
+            .build();
+        assert!(args.contains(&"-crf".to_string()));
+    }
+
+    #[test]
+    fn test_extra_args_before_output() {
+        let (args, output) = FfmpegBuilder::new("in.mp4", "out.mp4")
+            .extra_args(&["-tune".to_string(), "film".to_string()])
+            .video_filter("hflip")
+            .build();
+        assert_eq!(output, "out.mp4");
+        assert_eq!(args[args.len() - 3..], ["-tune", "film", "out.mp4"]);
+    }
+
+    #[test]
+    fn test_x265_threads_sets_pools() {
+        let (args, _) = FfmpegBuilder::new("in.mp4", "out.mp4")
+            .threads("libx265", 8)
+            .build();
+        assert!(args.windows(2).any(|w| w == ["-threads", "8"]));
+        assert!(args.windows(2).any(|w| w == ["-x265-params", "pools=8"]));
+    }
+
+    #[test]
+    fn test_escape_filter_path() {
+        assert_eq!(
+            escape_filter_path(r"C:\Users\me\Movies\subs.srt"),
+            r"C\:/Users/me/Movies/subs.srt"
+        );
+        assert_eq!(
+            escape_filter_path("/home/me/it's.ass"),
+            r"/home/me/it'\\\''s.ass"
+        );
+    }
+}
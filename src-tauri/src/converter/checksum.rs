@@ -0,0 +1,53 @@
+//! SHA-256 sidecar files for distributing converted outputs.
+
+use anyhow::{Context, Result};
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+const READ_CHUNK: usize = 1 << 20;
+
+/// Hash `output` and write `<output>.sha256` next to it, in the format
+/// `sha256sum -c` accepts. Returns the sidecar path.
+pub async fn write_sidecar(output: &str) -> Result<PathBuf> {
+    let output = PathBuf::from(output);
+    let digest = {
+        let output = output.clone();
+        tokio::task::spawn_blocking(move || sha256_file(&output)).await??
+    };
+
+    let file_name = output
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let mut sidecar = output.into_os_string();
+    sidecar.push(".sha256");
+    let sidecar = PathBuf::from(sidecar);
+
+    tokio::fs::write(&sidecar, format!("{}  {}\n", digest, file_name))
+        .await
+        .context("Failed to write checksum file")?;
+
+    Ok(sidecar)
+}
+
+fn sha256_file(path: &Path) -> Result<String> {
+    let mut file = File::open(path).context("Failed to open output for hashing")?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0u8; READ_CHUNK];
+
+    loop {
+        let read = file.read(&mut buf)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buf[..read]);
+    }
+
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect())
+}
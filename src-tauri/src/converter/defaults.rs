@@ -0,0 +1,137 @@
+//! Recommended starting settings per output format, so the UI can pre-fill
+//! its controls with what the converter would pick anyway and bound its
+//! sliders to what the format and encoder accept.
+
+use crate::codec_map;
+use crate::formats::audio::AudioFormat;
+use crate::formats::video::VideoFormat;
+use crate::types::{ConversionSettings, Quality};
+use serde::Serialize;
+
+/// Opus bitrate (kbps) when the format gives no range.
+const FALLBACK_OPUS_KBPS: u32 = 128;
+/// Lossy audio bitrate (kbps) for other encoders when the format gives no range.
+const FALLBACK_AUDIO_KBPS: u32 = 192;
+/// FLAC/WavPack `-compression_level` bounds (higher is smaller and slower).
+pub const COMPRESSION_LEVELS: (u32, u32) = (0, 12);
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FormatDefaults {
+    pub settings: ConversionSettings,
+    pub ranges: SettingRanges,
+}
+
+/// Values a setting may take for the format; `None`/empty when it doesn't apply.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct SettingRanges {
+    /// Lossy audio bitrate bounds in kbps
+    pub audio_bitrate: Option<(u32, u32)>,
+    pub sample_rates: Vec<u32>,
+    pub channels: Vec<u32>,
+    pub compression_level: Option<(u32, u32)>,
+    /// Constant-quality bounds of the default software encoder (lower is better)
+    pub crf: Option<(u32, u32)>,
+    pub max_resolution: Option<(u32, u32)>,
+}
+
+/// Bitrate in kbps for a lossy audio encode at `quality`, or `None` when the
+/// encoder takes a quality scale instead (Vorbis `-q:a`) or the format is lossless.
+pub fn audio_bitrate(fmt: &AudioFormat, codec: &str, quality: Quality) -> Option<u32> {
+    if !fmt.lossy || codec == "libvorbis" {
+        return None;
+    }
+    let fallback = match codec {
+        "libopus" | "opus" => FALLBACK_OPUS_KBPS,
+        _ => FALLBACK_AUDIO_KBPS,
+    };
+    Some(
+        fmt.get_bitrate_for_quality(quality.as_str())
+            .unwrap_or(fallback),
+    )
+}
+
+/// FLAC/WavPack compression level for `quality`, or `None` for codecs without one.
+pub fn compression_level(fmt: &AudioFormat, quality: Quality) -> Option<u32> {
+    if !matches!(fmt.codec.as_str(), "flac" | "wavpack") {
+        return None;
+    }
+    Some(match quality {
+        Quality::Low => 0,
+        Quality::High => 8,
+        Quality::Ultra => COMPRESSION_LEVELS.1,
+        Quality::Medium | Quality::Custom => 5,
+    })
+}
+
+pub fn for_audio(fmt: &AudioFormat, quality: Quality) -> FormatDefaults {
+    let settings = ConversionSettings {
+        quality,
+        audio_bitrate: audio_bitrate(fmt, &fmt.codec, quality),
+        sample_rate: Some(fmt.recommended_sample_rate),
+        channels: Some(fmt.best_channels(2)),
+        compression_level: compression_level(fmt, quality),
+        ..Default::default()
+    };
+    let ranges = SettingRanges {
+        audio_bitrate: fmt.bitrate_range.filter(|_| fmt.lossy),
+        sample_rates: fmt.sample_rates.clone(),
+        channels: fmt.channels_support.clone(),
+        compression_level: settings.compression_level.map(|_| COMPRESSION_LEVELS),
+        ..Default::default()
+    };
+    FormatDefaults { settings, ranges }
+}
+
+/// Defaults for the format's software encoder; the GPU encoder, when one is
+/// picked at conversion time, maps `crf` onto its own scale.
+pub fn for_video(fmt: &VideoFormat, quality: Quality) -> FormatDefaults {
+    let scale = fmt
+        .get_software_codec()
+        .and_then(|encoder| codec_map::quality_scale(&encoder))
+        .filter(|_| !fmt.still_image);
+    let crf = scale.and_then(|scale| quality.video_crf().parse().ok().map(|crf| scale.clamp(crf)));
+
+    let settings = ConversionSettings {
+        quality,
+        crf,
+        ..Default::default()
+    };
+    let ranges = SettingRanges {
+        crf: scale.map(|scale| (scale.min, scale.max)),
+        max_resolution: fmt.max_resolution,
+        ..Default::default()
+    };
+    FormatDefaults { settings, ranges }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formats::{audio, video};
+
+    #[test]
+    fn test_audio_defaults() {
+        let opus = for_audio(&audio::get_format("opus").unwrap(), Quality::Medium);
+        assert_eq!(opus.settings.audio_bitrate, Some(128));
+        assert_eq!(opus.settings.sample_rate, Some(48000));
+        assert_eq!(opus.settings.compression_level, None);
+        assert_eq!(opus.ranges.audio_bitrate, Some((6, 510)));
+
+        let flac = for_audio(&audio::get_format("flac").unwrap(), Quality::Medium);
+        assert_eq!(flac.settings.audio_bitrate, None);
+        assert_eq!(flac.settings.compression_level, Some(5));
+        assert_eq!(flac.ranges.compression_level, Some(COMPRESSION_LEVELS));
+        assert_eq!(flac.ranges.audio_bitrate, None);
+    }
+
+    #[test]
+    fn test_video_defaults() {
+        let mp4 = for_video(&video::get_format("mp4").unwrap(), Quality::High);
+        assert_eq!(mp4.settings.crf, Some(19));
+        assert_eq!(mp4.ranges.crf, Some((0, 51)));
+
+        let avif = for_video(&video::get_format("avif").unwrap(), Quality::High);
+        assert_eq!(avif.settings.crf, None);
+        assert_eq!(avif.ranges.crf, None);
+    }
+}
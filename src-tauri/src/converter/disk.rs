@@ -0,0 +1,171 @@
+//! Free-space check on the output drive before a conversion starts, so it
+//! fails up front instead of dying halfway with a truncated file.
+
+use crate::error::{AppError, ErrorCode};
+use crate::media::MediaInfo;
+use crate::types::ConversionSettings;
+use anyhow::Result;
+use std::path::Path;
+
+/// Headroom for container overhead, metadata and bitrate overshoot.
+const SIZE_MARGIN: f64 = 1.1;
+/// Audio bitrate assumed alongside a video bitrate when the source doesn't report one.
+const FALLBACK_AUDIO_KBPS: u64 = 192;
+/// Bytes per pixel of 16-bit RGB, more than any compressed still image takes.
+const STILL_BYTES_PER_PIXEL: u64 = 6;
+
+/// Rough output size in bytes: target bitrate × output duration when a bitrate
+/// is set, otherwise the source size scaled to the output duration. For video
+/// targets `settings.video_bitrate` covers the video stream only, so the
+/// audio track's bitrate is added.
+pub fn estimate_output_size(media: &MediaInfo, settings: &ConversionSettings, video: bool) -> u64 {
+    let duration = settings.output_duration(media.duration);
+    let target = if video {
+        settings.video_bitrate
+    } else {
+        settings.audio_only_bitrate()
+    };
+
+    let estimate = match target {
+        Some(kbps) if duration > 0.0 => {
+            let audio_kbps = match media.primary_audio() {
+                Some(audio) if video => settings
+                    .audio_bitrate
+                    .map(u64::from)
+                    .or(audio.bitrate.map(|b| b / 1000))
+                    .unwrap_or(FALLBACK_AUDIO_KBPS),
+                _ => 0,
+            };
+            (kbps as u64 + audio_kbps) as f64 * 1000.0 / 8.0 * duration
+        }
+        _ if media.duration > 0.0 => media.file_size as f64 * duration / media.duration,
+        _ => media.file_size as f64,
+    };
+
+    (estimate * SIZE_MARGIN) as u64
+}
+
+/// Upper bound in bytes for a single-frame output (AVIF, JPEG XL): one
+/// uncompressed frame at the requested size, or the source's when unset.
+pub fn estimate_still_size(media: &MediaInfo, settings: &ConversionSettings) -> u64 {
+    let source = media.primary_video().map(|v| (v.width, v.height));
+    let (width, height) = settings
+        .width
+        .zip(settings.height)
+        .or(source)
+        .unwrap_or_default();
+    width as u64 * height as u64 * STILL_BYTES_PER_PIXEL
+}
+
+/// Fail with `DiskFull` when the drive holding `output` has less than
+/// `required` bytes free. Skipped if free space can't be queried.
+pub fn ensure_free_space(output: &str, required: u64) -> Result<()> {
+    let Some(dir) = Path::new(output).ancestors().skip(1).find(|p| p.is_dir()) else {
+        return Ok(());
+    };
+
+    let available = match fs2::available_space(dir) {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            tracing::debug!(dir = %dir.display(), error = %e, "Could not query free space");
+            return Ok(());
+        }
+    };
+
+    if required > available {
+        return Err(AppError::new(
+            ErrorCode::DiskFull,
+            format!(
+                "Not enough disk space: the output needs about {}, only {} free",
+                format_size(required),
+                format_size(available)
+            ),
+        )
+        .with_details(dir.display().to_string())
+        .into());
+    }
+
+    Ok(())
+}
+
+fn format_size(bytes: u64) -> String {
+    const MB: f64 = 1024.0 * 1024.0;
+    const GB: f64 = MB * 1024.0;
+
+    let bytes = bytes as f64;
+    if bytes >= GB {
+        format!("{:.1} GB", bytes / GB)
+    } else {
+        format!("{:.0} MB", (bytes / MB).ceil())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::media::{MediaType, VideoStream};
+
+    fn source(file_size: u64, duration: f64) -> MediaInfo {
+        MediaInfo {
+            media_type: MediaType::Video,
+            duration,
+            file_size,
+            format_name: "matroska,webm".to_string(),
+            bitrate: None,
+            video_streams: Vec::new(),
+            audio_streams: Vec::new(),
+            subtitle_streams: Vec::new(),
+            attachments: Vec::new(),
+            tags: None,
+            chapter_count: 0,
+            mismatch_warning: None,
+        }
+    }
+
+    #[test]
+    fn test_estimate_output_size() {
+        let media = source(100_000_000, 100.0);
+
+        // No bitrate: source size, halved by double speed
+        let fast = ConversionSettings {
+            speed: Some(2.0),
+            ..Default::default()
+        };
+        assert_eq!(estimate_output_size(&media, &fast, true), 55_000_000);
+
+        // 8000 kbps for 100 s = 100 MB before the margin
+        let bitrate = ConversionSettings {
+            video_bitrate: Some(8000),
+            ..Default::default()
+        };
+        assert_eq!(estimate_output_size(&media, &bitrate, true), 110_000_000);
+    }
+
+    #[test]
+    fn test_estimate_still_size() {
+        let mut media = source(20_000_000_000, 7200.0);
+        media.video_streams.push(VideoStream {
+            codec: "h264".to_string(),
+            width: 3840,
+            height: 2160,
+            fps: 24.0,
+            bitrate: None,
+            field_order: None,
+            color_transfer: None,
+            color_primaries: None,
+            color_space: None,
+            rotation: None,
+        });
+
+        // One frame, whatever the length of the film
+        let defaults = ConversionSettings::default();
+        assert_eq!(estimate_still_size(&media, &defaults), 3840 * 2160 * 6);
+
+        let resized = ConversionSettings {
+            width: Some(1920),
+            height: Some(1080),
+            ..Default::default()
+        };
+        assert_eq!(estimate_still_size(&media, &resized), 1920 * 1080 * 6);
+    }
+}
@@ -0,0 +1,273 @@
+//! Output size prediction shown before converting.
+//!
+//! A bitrate target pins the size down well; constant-quality encodes depend
+//! on how complex the content is, so those estimates come from the same
+//! bits-per-pixel heuristic AMF auto-bitrate uses and are flagged as rough.
+//! Intermediates (ProRes, DNxHR) run at their profile's nominal data rate.
+
+use super::video::{calculate_auto_bitrate, frame_size};
+use crate::codec_map;
+use crate::media::MediaInfo;
+use crate::types::{ConversionSettings, Quality, RateControl};
+use serde::Serialize;
+
+/// Audio bitrate assumed when neither the settings nor the source give one.
+const FALLBACK_AUDIO_KBPS: u32 = 192;
+/// Spread around the expected size for bitrate targets (overshoot, container).
+const BITRATE_SPREAD: f64 = 0.1;
+/// Spread for constant-quality encodes; real results vary by content.
+const QUALITY_SPREAD: f64 = 0.5;
+/// CRF steps that double or halve the bitrate (x264/x265 rule of thumb).
+const CRF_DOUBLING_STEP: f64 = 6.0;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SizeEstimate {
+    /// Most likely size in bytes
+    pub expected_bytes: u64,
+    pub min_bytes: u64,
+    pub max_bytes: u64,
+    /// Based on quality heuristics rather than a bitrate target
+    pub low_confidence: bool,
+}
+
+impl SizeEstimate {
+    fn new(kbps: f64, duration: f64, spread: f64, low_confidence: bool) -> Self {
+        let bytes = kbps * 1000.0 / 8.0 * duration;
+        Self {
+            expected_bytes: bytes as u64,
+            min_bytes: (bytes * (1.0 - spread)) as u64,
+            max_bytes: (bytes * (1.0 + spread)) as u64,
+            low_confidence,
+        }
+    }
+}
+
+/// Predict the output size of converting `media` with `settings`.
+pub fn estimate(media: &MediaInfo, settings: &ConversionSettings) -> SizeEstimate {
+    let duration = settings.output_duration(media.duration).max(0.0);
+
+    // Streams are copied, so the size follows the source
+    if settings.remux_only && media.duration > 0.0 {
+        let kbps = media.file_size as f64 * 8.0 / 1000.0 / media.duration;
+        return SizeEstimate::new(kbps, duration, BITRATE_SPREAD, false);
+    }
+
+    let audio_only = settings.extract_audio_only || media.primary_video().is_none();
+    if audio_only {
+        return match settings.audio_only_bitrate() {
+            Some(kbps) => SizeEstimate::new(kbps as f64, duration, BITRATE_SPREAD, false),
+            None => SizeEstimate::new(
+                audio_kbps_for_quality(settings.quality) as f64,
+                duration,
+                QUALITY_SPREAD,
+                true,
+            ),
+        };
+    }
+
+    // The audio stream comes on top of the video
+    let audio_kbps = match media.primary_audio() {
+        Some(audio) => settings
+            .audio_bitrate
+            .or(audio.bitrate.map(|b| (b / 1000) as u32))
+            .unwrap_or(FALLBACK_AUDIO_KBPS),
+        None => 0,
+    } as f64;
+
+    let codec = settings.video_codec.as_deref().unwrap_or("libx264");
+    let encoder = codec_map::software_encoder_for_codec(codec).unwrap_or(codec);
+    if let Some(profile) = codec_map::intermediate_profile(encoder, settings.quality) {
+        let kbps = video_kbps_at(media, settings, profile.bits_per_pixel);
+        return SizeEstimate::new(kbps + audio_kbps, duration, BITRATE_SPREAD, false);
+    }
+
+    let bitrate_mode = settings.crf.is_none() || settings.rate_control != RateControl::Crf;
+    if let Some(kbps) = settings.video_bitrate.filter(|_| bitrate_mode) {
+        return SizeEstimate::new(kbps as f64 + audio_kbps, duration, BITRATE_SPREAD, false);
+    }
+
+    let video_kbps = quality_video_kbps(media, settings);
+    SizeEstimate::new(video_kbps + audio_kbps, duration, QUALITY_SPREAD, true)
+}
+
+/// Likely video bitrate of a constant-quality encode.
+fn quality_video_kbps(media: &MediaInfo, settings: &ConversionSettings) -> f64 {
+    let (width, height, fps) = output_frame(media, settings);
+    let codec = settings.video_codec.as_deref().unwrap_or("libx264");
+
+    let kbps = calculate_auto_bitrate(width, height, fps, settings.quality, codec) as f64;
+
+    // An explicit CRF moves away from the one the quality preset implies
+    match settings.crf {
+        Some(crf) => {
+            let preset_crf: f64 = settings.quality.video_crf().parse().unwrap_or(23.0);
+            kbps * 2f64.powf((preset_crf - crf as f64) / CRF_DOUBLING_STEP)
+        }
+        None => kbps,
+    }
+}
+
+/// Video bitrate of an encode spending `bits_per_pixel` on every frame.
+fn video_kbps_at(media: &MediaInfo, settings: &ConversionSettings, bits_per_pixel: f64) -> f64 {
+    let (width, height, fps) = output_frame(media, settings);
+    width as f64 * height as f64 * fps as f64 * bits_per_pixel / 1000.0
+}
+
+/// Output frame size and rate after the resize and frame rate settings.
+fn output_frame(media: &MediaInfo, settings: &ConversionSettings) -> (u32, u32, u32) {
+    let (source_width, source_height) = frame_size(media, settings).unwrap_or((1920, 1080));
+    let (width, height) = match (settings.width, settings.height) {
+        (Some(w), Some(h)) => (w, h),
+        (Some(w), None) => (w, scale_side(source_height, w, source_width)),
+        (None, Some(h)) => (scale_side(source_width, h, source_height), h),
+        (None, None) => settings
+            .relative_size((source_width, source_height))
+            .unwrap_or((source_width, source_height)),
+    };
+    let fps = settings.fps.unwrap_or_else(|| {
+        media
+            .primary_video()
+            .map(|v| v.fps.round() as u32)
+            .unwrap_or(30)
+    });
+    (width, height, fps)
+}
+
+/// Other side of a frame resized to `side` while keeping its aspect ratio.
+fn scale_side(other: u32, side: u32, original: u32) -> u32 {
+    if original == 0 {
+        return other;
+    }
+    (other as f64 * side as f64 / original as f64).round() as u32
+}
+
+fn audio_kbps_for_quality(quality: Quality) -> u32 {
+    match quality {
+        Quality::Low => 128,
+        Quality::Medium | Quality::Custom => FALLBACK_AUDIO_KBPS,
+        Quality::High => 256,
+        Quality::Ultra => 320,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::media::{AudioStream, MediaType, VideoStream};
+
+    fn video_source() -> MediaInfo {
+        MediaInfo {
+            media_type: MediaType::Video,
+            duration: 100.0,
+            file_size: 500_000_000,
+            format_name: "matroska,webm".to_string(),
+            bitrate: None,
+            video_streams: vec![VideoStream {
+                codec: "h264".to_string(),
+                width: 1920,
+                height: 1080,
+                fps: 30.0,
+                bitrate: None,
+                field_order: None,
+                color_transfer: None,
+                color_primaries: None,
+                color_space: None,
+                rotation: None,
+            }],
+            audio_streams: vec![AudioStream {
+                codec: "aac".to_string(),
+                sample_rate: 48000,
+                channels: 2,
+                bitrate: Some(128_000),
+                language: None,
+                title: None,
+            }],
+            subtitle_streams: Vec::new(),
+            attachments: Vec::new(),
+            tags: None,
+            chapter_count: 0,
+            mismatch_warning: None,
+        }
+    }
+
+    #[test]
+    fn test_estimate() {
+        let media = video_source();
+
+        // (3872 + 128) kbps for 100 s = 50 MB
+        let bitrate = ConversionSettings {
+            video_bitrate: Some(3872),
+            ..Default::default()
+        };
+        let est = estimate(&media, &bitrate);
+        assert_eq!(est.expected_bytes, 50_000_000);
+        assert_eq!((est.min_bytes, est.max_bytes), (45_000_000, 55_000_000));
+        assert!(!est.low_confidence);
+
+        // Six CRF steps below the preset doubles the video part
+        let medium = estimate(&media, &ConversionSettings::default());
+        let crf = estimate(
+            &media,
+            &ConversionSettings {
+                crf: Some(17),
+                ..Default::default()
+            },
+        );
+        assert!(medium.low_confidence && crf.low_confidence);
+        assert!(medium.min_bytes < medium.expected_bytes);
+        let audio_bytes = 128.0 * 1000.0 / 8.0 * 100.0;
+        let ratio = (crf.expected_bytes as f64 - audio_bytes)
+            / (medium.expected_bytes as f64 - audio_bytes);
+        assert!((ratio - 2.0).abs() < 0.01);
+
+        // ProRes HQ runs at its profile's rate (~220 Mbps at 1080p30), whatever the bitrate
+        let prores = estimate(
+            &media,
+            &ConversionSettings {
+                video_codec: Some("prores".to_string()),
+                quality: Quality::High,
+                video_bitrate: Some(500),
+                ..Default::default()
+            },
+        );
+        assert!(!prores.low_confidence);
+        let video_mbps = (prores.expected_bytes as f64 - audio_bytes) * 8.0 / 100.0 / 1e6;
+        assert!((video_mbps - 220.2).abs() < 0.1);
+
+        // Audio at its own rate: (3872 + 256) kbps for 100 s
+        let split = ConversionSettings {
+            video_bitrate: Some(3872),
+            audio_bitrate: Some(256),
+            ..Default::default()
+        };
+        assert_eq!(estimate(&media, &split).expected_bytes, 51_600_000);
+
+        // Audio extraction ignores the video stream
+        let audio = ConversionSettings {
+            extract_audio_only: true,
+            audio_bitrate: Some(320),
+            ..Default::default()
+        };
+        assert_eq!(estimate(&media, &audio).expected_bytes, 4_000_000);
+
+        // An old client's `bitrate` still sets an audio-only output's rate
+        let legacy: ConversionSettings =
+            serde_json::from_str(r#"{"extract_audio_only": true, "bitrate": 320}"#).unwrap();
+        assert_eq!(legacy.video_bitrate, None);
+        assert_eq!(estimate(&media, &legacy).expected_bytes, 4_000_000);
+
+        // A video bitrate never becomes the audio rate of an extraction
+        let extract = ConversionSettings {
+            extract_audio_only: true,
+            video_bitrate: Some(5000),
+            ..Default::default()
+        };
+        assert_eq!(extract.audio_only_bitrate(), None);
+
+        let mut video_legacy: ConversionSettings =
+            serde_json::from_str(r#"{"bitrate": 3872}"#).unwrap();
+        video_legacy.resolve_legacy_bitrate(false);
+        assert_eq!(video_legacy.video_bitrate, Some(3872));
+        assert_eq!(video_legacy.audio_only_bitrate(), None);
+    }
+}
@@ -0,0 +1,443 @@
+//! Filter chain assembly. Keeps the order of filters in one place so that
+//! options combine deterministically regardless of which ones are enabled.
+
+use crate::loudness;
+use crate::media::MediaInfo;
+use crate::types::{ConversionSettings, ScaleMode};
+
+/// Fallback when the source sample rate is unknown.
+const DEFAULT_SAMPLE_RATE: u32 = 48000;
+/// GIF/WebP frame rate and width when the settings leave them open; keeps files small.
+const ANIMATION_DEFAULT_FPS: u32 = 15;
+const ANIMATION_DEFAULT_WIDTH: u32 = 480;
+/// GIF delays are whole centiseconds and browsers slow anything under 2 down
+/// to 10, so 50 fps is the fastest a GIF reliably plays.
+const GIF_MAX_FPS: u32 = 50;
+/// WebP frame delays are whole milliseconds, but past 60 fps files only grow.
+const WEBP_MAX_FPS: u32 = 60;
+/// HDR → SDR: linearise, convert BT.2020 to BT.709 primaries, compress the
+/// highlights with Hable's curve, then re-encode with the BT.709 transfer as
+/// 8-bit 4:2:0. `npl=100` maps SDR reference white to 100 nits.
+pub const TONEMAP_SDR: &str = "zscale=t=linear:npl=100,format=gbrpf32le,zscale=p=bt709,tonemap=tonemap=hable:desat=0,zscale=t=bt709:m=bt709:r=tv,format=yuv420p";
+/// -3 dB, the gain for centre and surround channels folded into fewer speakers.
+const DOWNMIX_LEVEL: f64 = 0.707;
+/// Range a single `atempo` stage accepts.
+const ATEMPO_MIN: f64 = 0.5;
+const ATEMPO_MAX: f64 = 2.0;
+
+/// Audio filter chain (`-af`) for the given settings, in application order.
+pub fn audio_chain(settings: &ConversionSettings, media: &MediaInfo) -> Vec<String> {
+    let mut chain = Vec::new();
+
+    if settings.normalize_loudness {
+        chain.push(loudness::loudnorm_filter(
+            settings.loudness_target(),
+            settings.measured_loudness.as_ref(),
+        ));
+        // loudnorm upsamples to 192 kHz internally — bring it back to the source rate
+        let rate = media
+            .primary_audio()
+            .map(|a| a.sample_rate)
+            .filter(|&r| r > 0)
+            .unwrap_or(DEFAULT_SAMPLE_RATE);
+        chain.push(format!("aresample={}", rate));
+    }
+
+    if let Some(speed) = settings.speed() {
+        chain.extend(atempo_chain(speed));
+    }
+
+    // Timed on the output, so after anything that retimes the audio
+    chain.extend(fade_filters(
+        "afade",
+        settings,
+        settings.output_duration(media.duration),
+    ));
+
+    // After loudnorm, so its measurements still match the source
+    if let Some(db) = settings.volume_db() {
+        chain.push(format!("volume={}dB", db));
+    }
+
+    chain
+}
+
+/// Rematrix filter taking `source_channels` down to `target_channels`, or `None`
+/// when nothing is dropped. `-ac` alone also downmixes, but leaves the matrix to
+/// swresample's defaults for whatever layout it guesses; this names the target
+/// layout and pins centre and surrounds at -3 dB (ITU-R BS.775), so dialogue
+/// and ambience keep their balance. LFE is dropped, as in the standard downmix.
+pub fn downmix_filter(source_channels: u32, target_channels: u32) -> Option<String> {
+    if source_channels <= target_channels {
+        return None;
+    }
+    let layout = match target_channels {
+        1 => "mono",
+        2 => "stereo",
+        6 => "5.1",
+        _ => return None,
+    };
+    Some(format!(
+        "aresample=out_chlayout={}:center_mix_level={}:surround_mix_level={}",
+        layout, DOWNMIX_LEVEL, DOWNMIX_LEVEL
+    ))
+}
+
+/// Two-step GIF filtergraph: build a 256-colour palette from the whole clip,
+/// then map every frame onto it. Without the palette FFmpeg falls back to a
+/// generic one and the output is heavily dithered. `frame_width` is the width
+/// reaching the scaler (after any crop/rotation), `duration` the output length.
+pub fn gif_filter_complex(
+    settings: &ConversionSettings,
+    frame_width: Option<u32>,
+    duration: f64,
+) -> String {
+    let chain = animation_chain(settings, frame_width, duration, Some(gif_fps(settings)));
+    format!(
+        "[0:v]{},split[s0][s1];[s0]palettegen=max_colors=256:stats_mode=diff[p];[s1][p]paletteuse=dither=sierra2_4a",
+        chain.join(",")
+    )
+}
+
+/// `-vf` chain for WebP output. WebP stores full colour, so unlike GIF there
+/// is no palette step. `animated` is false for a still image, which keeps its
+/// size unless one is requested.
+pub fn webp_filters(
+    settings: &ConversionSettings,
+    frame_width: Option<u32>,
+    duration: f64,
+    animated: bool,
+) -> Vec<String> {
+    let fps = animated.then(|| webp_fps(settings));
+    animation_chain(settings, frame_width, duration, fps)
+}
+
+/// `-vf` chain for a still image output (AVIF, JPEG XL), which keeps its
+/// size unless one is requested.
+pub fn still_filters(settings: &ConversionSettings, duration: f64) -> Vec<String> {
+    animation_chain(settings, None, duration, None)
+}
+
+/// Picture filters for GIF and WebP output, ending at the output size. `fps`
+/// is `None` for a still, which has no timing to change and isn't downscaled
+/// to the default animation width.
+fn animation_chain(
+    settings: &ConversionSettings,
+    frame_width: Option<u32>,
+    duration: f64,
+    fps: Option<u32>,
+) -> Vec<String> {
+    let mut chain = Vec::new();
+
+    if let Some(deinterlacer) = settings.deinterlace {
+        chain.push(deinterlacer.filter().to_string());
+    }
+    if let Some((w, h, x, y)) = settings.crop {
+        chain.push(format!("crop={}:{}:{}:{}", w, h, x, y));
+    }
+    if let Some(rotate) = settings.rotation().and_then(rotation_filter) {
+        chain.push(rotate.to_string());
+    }
+    if let Some(flip) = settings.flip {
+        chain.push(flip.filter().to_string());
+    }
+    if let Some(fps) = fps {
+        if let Some(speed) = settings.speed() {
+            chain.push(speed_video_filter(speed));
+        }
+        chain.extend(video_fades(settings, duration));
+        chain.push(format!("fps={}", fps));
+    }
+
+    let scale = match (settings.width, settings.height) {
+        (Some(w), Some(h)) => Some(format!("{}:{}", w, h)),
+        (Some(w), None) => Some(format!("{}:-2", w)),
+        (None, Some(h)) => Some(format!("-2:{}", h)),
+        (None, None) if fps.is_none() => None,
+        // Never upscale small sources to the default width
+        (None, None) => Some(format!(
+            "{}:-2",
+            frame_width
+                .unwrap_or(ANIMATION_DEFAULT_WIDTH)
+                .min(ANIMATION_DEFAULT_WIDTH)
+        )),
+    };
+    if let Some(scale) = scale {
+        chain.push(format!("scale={}:flags=lanczos", scale));
+    }
+    // After scaling, so the float conversion runs on the small frame
+    if settings.tonemap_sdr {
+        chain.push(TONEMAP_SDR.to_string());
+    }
+
+    chain
+}
+
+/// Frame rate for GIF output: the requested one, within what GIFs can play.
+pub fn gif_fps(settings: &ConversionSettings) -> u32 {
+    settings
+        .fps
+        .unwrap_or(ANIMATION_DEFAULT_FPS)
+        .clamp(1, GIF_MAX_FPS)
+}
+
+/// Frame rate for animated WebP output.
+pub fn webp_fps(settings: &ConversionSettings) -> u32 {
+    settings
+        .fps
+        .unwrap_or(ANIMATION_DEFAULT_FPS)
+        .clamp(1, WEBP_MAX_FPS)
+}
+
+/// Video filter that plays the video `speed` times faster.
+pub fn speed_video_filter(speed: f64) -> String {
+    format!("setpts=PTS/{}", speed)
+}
+
+/// `fade` filters for the video, timed on an output of `duration` seconds.
+pub fn video_fades(settings: &ConversionSettings, duration: f64) -> Vec<String> {
+    fade_filters("fade", settings, duration)
+}
+
+/// Fade-in/out filters of type `kind` (`fade` or `afade`). The fade-out
+/// starts `fade_out` seconds before the end of the output.
+fn fade_filters(kind: &str, settings: &ConversionSettings, duration: f64) -> Vec<String> {
+    let mut filters = Vec::new();
+
+    if let Some(length) = settings.fade_in() {
+        filters.push(format!("{}=t=in:st=0:d={}", kind, length));
+    }
+    if let Some(length) = settings.fade_out() {
+        let start = (duration - length).max(0.0);
+        filters.push(format!("{}=t=out:st={}:d={}", kind, start, length));
+    }
+
+    filters
+}
+
+/// Scale into an exact `width`x`height` frame. `Fit` adds centred black bars
+/// and `Fill` crops the centre, both with square pixels; `source` is the size
+/// reaching the scaler, used to work out the scaled size and offsets. Without
+/// it FFmpeg derives them.
+pub fn scale_to_frame(
+    width: u32,
+    height: u32,
+    mode: ScaleMode,
+    source: Option<(u32, u32)>,
+) -> String {
+    let source = source.filter(|&(w, h)| w > 0 && h > 0);
+    match (mode, source) {
+        (ScaleMode::Stretch, _) => format!("scale={}:{}", width, height),
+        (ScaleMode::Fit, Some((source_w, source_h))) => {
+            let factor = (width as f64 / source_w as f64).min(height as f64 / source_h as f64);
+            let scaled_w = even(source_w as f64 * factor).min(width);
+            let scaled_h = even(source_h as f64 * factor).min(height);
+            format!(
+                "scale={}:{},pad={}:{}:{}:{}:black,setsar=1",
+                scaled_w,
+                scaled_h,
+                width,
+                height,
+                (width - scaled_w) / 2,
+                (height - scaled_h) / 2
+            )
+        }
+        (ScaleMode::Fill, Some((source_w, source_h))) => {
+            let factor = (width as f64 / source_w as f64).max(height as f64 / source_h as f64);
+            let scaled_w = even(source_w as f64 * factor).max(width);
+            let scaled_h = even(source_h as f64 * factor).max(height);
+            format!(
+                "scale={}:{},crop={}:{}:{}:{},setsar=1",
+                scaled_w,
+                scaled_h,
+                width,
+                height,
+                (scaled_w - width) / 2,
+                (scaled_h - height) / 2
+            )
+        }
+        (ScaleMode::Fit, None) => format!(
+            "scale={0}:{1}:force_original_aspect_ratio=decrease,pad={0}:{1}:(ow-iw)/2:(oh-ih)/2:black,setsar=1",
+            width, height
+        ),
+        (ScaleMode::Fill, None) => format!(
+            "scale={0}:{1}:force_original_aspect_ratio=increase,crop={0}:{1},setsar=1",
+            width, height
+        ),
+    }
+}
+
+/// Nearest even size; 4:2:0 chroma needs even dimensions.
+fn even(size: f64) -> u32 {
+    ((size / 2.0).round() as u32 * 2).max(2)
+}
+
+/// Single `eq` filter for the colour controls, or `None` when all are neutral.
+pub fn eq_filter(settings: &ConversionSettings) -> Option<String> {
+    let params: Vec<String> = settings
+        .color_adjustments()
+        .into_iter()
+        .map(|(name, value)| format!("{}={}", name, value))
+        .collect();
+    (!params.is_empty()).then(|| format!("eq={}", params.join(":")))
+}
+
+/// `transpose` chain rotating the picture clockwise by `degrees`.
+pub fn rotation_filter(degrees: u32) -> Option<&'static str> {
+    match degrees {
+        90 => Some("transpose=1"),
+        180 => Some("transpose=2,transpose=2"),
+        270 => Some("transpose=2"),
+        _ => None,
+    }
+}
+
+/// `atempo` stages changing audio speed by `speed` without shifting pitch.
+/// One stage only accepts 0.5–2.0, so larger changes are split into several.
+fn atempo_chain(speed: f64) -> Vec<String> {
+    let mut stages = Vec::new();
+    let mut remaining = speed;
+
+    while remaining > ATEMPO_MAX {
+        stages.push(format!("atempo={}", ATEMPO_MAX));
+        remaining /= ATEMPO_MAX;
+    }
+    while remaining < ATEMPO_MIN {
+        stages.push(format!("atempo={}", ATEMPO_MIN));
+        remaining /= ATEMPO_MIN;
+    }
+    stages.push(format!("atempo={}", remaining));
+
+    stages
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_atempo_chain() {
+        assert_eq!(atempo_chain(1.5), vec!["atempo=1.5"]);
+        assert_eq!(atempo_chain(3.0), vec!["atempo=2", "atempo=1.5"]);
+        assert_eq!(atempo_chain(0.25), vec!["atempo=0.5", "atempo=0.5"]);
+        assert_eq!(speed_video_filter(1.5), "setpts=PTS/1.5");
+    }
+
+    #[test]
+    fn test_downmix_filter() {
+        assert_eq!(
+            downmix_filter(6, 2).as_deref(),
+            Some("aresample=out_chlayout=stereo:center_mix_level=0.707:surround_mix_level=0.707")
+        );
+        assert!(downmix_filter(8, 6).unwrap().contains("out_chlayout=5.1:"));
+        assert!(downmix_filter(2, 1).unwrap().contains("out_chlayout=mono:"));
+        // Upmixing and same-width output are left to `-ac`
+        assert_eq!(downmix_filter(2, 2), None);
+        assert_eq!(downmix_filter(1, 2), None);
+    }
+
+    #[test]
+    fn test_fades_follow_output_duration() {
+        let settings = ConversionSettings {
+            speed: Some(2.0),
+            fade_in_seconds: Some(1.0),
+            fade_out_seconds: Some(2.0),
+            ..Default::default()
+        };
+        // A 20 s source at double speed ends at 10 s
+        assert_eq!(
+            video_fades(&settings, settings.output_duration(20.0)),
+            vec!["fade=t=in:st=0:d=1", "fade=t=out:st=8:d=2"]
+        );
+    }
+
+    #[test]
+    fn test_webp_filters() {
+        let defaults = ConversionSettings::default();
+        assert_eq!(
+            webp_filters(&defaults, Some(1920), 10.0, true),
+            vec!["fps=15", "scale=480:-2:flags=lanczos"]
+        );
+        // Stills keep their size and timing filters don't apply
+        let still = ConversionSettings {
+            fade_in_seconds: Some(1.0),
+            ..Default::default()
+        };
+        assert!(webp_filters(&still, Some(1920), 0.0, false).is_empty());
+
+        let fast = ConversionSettings {
+            fps: Some(120),
+            ..Default::default()
+        };
+        assert_eq!(webp_filters(&fast, Some(320), 10.0, true)[0], "fps=60");
+    }
+
+    #[test]
+    fn test_scale_to_frame() {
+        // Widescreen into NTSC DVD
+        assert_eq!(
+            scale_to_frame(720, 480, ScaleMode::Fit, Some((1920, 1080))),
+            "scale=720:406,pad=720:480:0:37:black,setsar=1"
+        );
+        assert_eq!(
+            scale_to_frame(720, 480, ScaleMode::Fill, Some((1920, 1080))),
+            "scale=854:480,crop=720:480:67:0,setsar=1"
+        );
+        assert_eq!(
+            scale_to_frame(720, 480, ScaleMode::Stretch, Some((1920, 1080))),
+            "scale=720:480"
+        );
+        // Pillarboxed when the source is narrower
+        assert_eq!(
+            scale_to_frame(1920, 1080, ScaleMode::Fit, Some((1440, 1080))),
+            "scale=1440:1080,pad=1920:1080:240:0:black,setsar=1"
+        );
+        assert!(scale_to_frame(720, 480, ScaleMode::Fit, None)
+            .contains("force_original_aspect_ratio=decrease,pad=720:480:"));
+    }
+
+    #[test]
+    fn test_eq_filter() {
+        assert_eq!(eq_filter(&ConversionSettings::default()), None);
+
+        let neutral = ConversionSettings {
+            brightness: Some(0.0),
+            contrast: Some(1.0),
+            ..Default::default()
+        };
+        assert_eq!(eq_filter(&neutral), None);
+
+        let adjusted = ConversionSettings {
+            brightness: Some(0.15),
+            saturation: Some(5.0),
+            gamma: Some(1.2),
+            ..Default::default()
+        };
+        // Saturation is clamped to 3
+        assert_eq!(
+            eq_filter(&adjusted).as_deref(),
+            Some("eq=brightness=0.15:saturation=3:gamma=1.2")
+        );
+    }
+
+    #[test]
+    fn test_gif_filter_complex() {
+        let defaults = ConversionSettings::default();
+        let graph = gif_filter_complex(&defaults, Some(1920), 10.0);
+        assert!(graph.starts_with("[0:v]fps=15,scale=480:-2:flags=lanczos,split[s0][s1];"));
+        assert!(graph.contains("[s0]palettegen"));
+        assert!(graph.contains("[s1][p]paletteuse"));
+
+        // Small sources keep their size
+        assert!(gif_filter_complex(&defaults, Some(320), 10.0).contains("scale=320:-2"));
+
+        let custom = ConversionSettings {
+            fps: Some(120),
+            width: Some(640),
+            rotate: Some(90),
+            ..Default::default()
+        };
+        assert!(gif_filter_complex(&custom, Some(1920), 10.0)
+            .starts_with("[0:v]transpose=1,fps=50,scale=640:-2:flags=lanczos,"));
+    }
+}
@@ -0,0 +1,110 @@
+//! Dump a video's frames to a numbered PNG sequence.
+
+use super::builder::FfmpegBuilder;
+use super::{emit_phase, spawn_ffmpeg, ConversionPhase, RunOptions};
+use crate::error::{AppError, ErrorCode};
+use crate::media;
+use crate::types::ConversionSettings;
+use crate::utils;
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+use tauri::Manager;
+use tokio::process::Child;
+use tokio::sync::Mutex;
+
+/// File name pattern of the extracted frames, numbered from 1.
+const FRAME_PATTERN: &str = "frame_%05d.png";
+
+/// Write the frames of `input` to `output_dir` as `frame_00001.png`, ... .
+/// `fps` subsamples (0.5 = one frame every two seconds); every frame is kept
+/// when unset. The directory is created if needed and must be empty unless
+/// `overwrite` is set.
+pub async fn extract(
+    window: tauri::WebviewWindow,
+    input: &str,
+    output_dir: &str,
+    fps: Option<f64>,
+    overwrite: bool,
+    settings: ConversionSettings,
+    processes: Arc<Mutex<HashMap<String, Child>>>,
+) -> Result<String> {
+    utils::validate_input_path(input)?;
+
+    if let Some(fps) = fps.filter(|f| *f <= 0.0) {
+        return Err(AppError::new(
+            ErrorCode::ValidationFailed,
+            format!("Frame rate must be greater than 0 (got {})", fps),
+        )
+        .into());
+    }
+
+    let dir = Path::new(output_dir);
+    prepare_output_dir(dir, overwrite).await?;
+
+    let task_id = settings.task_id();
+    emit_phase(&window, &task_id, ConversionPhase::Probing);
+    let media = media::detect_media_type(window.app_handle(), input).await?;
+    if media.video_streams.is_empty() {
+        anyhow::bail!("No video stream found in input file");
+    }
+
+    let pattern = dir.join(FRAME_PATTERN);
+    let mut builder = FfmpegBuilder::new(input, &pattern.to_string_lossy())
+        .hide_banner()
+        .overwrite()
+        .input_file()
+        .progress_pipe()
+        .disable_audio();
+    if let Some(fps) = fps {
+        builder = builder.video_filter(&format!("fps={}", fps));
+    }
+
+    tracing::info!(task_id = %task_id, dir = %dir.display(), ?fps, "Extracting frames");
+
+    let (args, output_path) = builder.build();
+
+    // Nothing to probe, play back or hash: the output is a directory of images
+    let options = RunOptions {
+        test_playback: false,
+        compute_checksums: false,
+        verify_output: false,
+        // The output path is a numbering pattern, not a single file
+        partial_suffix: None,
+        ..RunOptions::from_settings(&settings)
+    };
+
+    spawn_ffmpeg(
+        window,
+        task_id,
+        media.duration,
+        args,
+        output_path,
+        options,
+        processes,
+    )
+    .await
+}
+
+async fn prepare_output_dir(dir: &Path, overwrite: bool) -> Result<()> {
+    tokio::fs::create_dir_all(dir)
+        .await
+        .context("Failed to create output folder")?;
+
+    let mut entries = tokio::fs::read_dir(dir)
+        .await
+        .context("Failed to read output folder")?;
+    if !overwrite && entries.next_entry().await?.is_some() {
+        return Err(
+            AppError::new(ErrorCode::InvalidPath, "Output folder isn't empty")
+                .with_details(format!(
+                    "{} already contains files; choose an empty folder or allow overwriting",
+                    dir.display()
+                ))
+                .into(),
+        );
+    }
+
+    Ok(())
+}
@@ -0,0 +1,199 @@
+//! Limits how many hardware-encoded conversions run at once.
+//!
+//! Consumer GPUs cap concurrent encode sessions (NVENC historically allowed
+//! only 3) and share VRAM between jobs. Instead of letting extra jobs fail,
+//! GPU conversions wait for a free slot here and the frontend is told via a
+//! `gpu-throttled` event so the queue shows "waiting for GPU" instead of
+//! looking stuck.
+
+use serde::Serialize;
+use std::sync::Mutex;
+use tauri::Emitter;
+use tokio::sync::Notify;
+
+/// Conservative default matching the long-standing NVENC consumer session cap.
+pub const DEFAULT_MAX_GPU_SESSIONS: usize = 3;
+
+static THROTTLE: GpuThrottle = GpuThrottle::new(DEFAULT_MAX_GPU_SESSIONS);
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ThrottleReason {
+    /// Configured max-concurrent-GPU limit reached
+    MaxConcurrent,
+    /// Driver refused to open another encode session
+    SessionLimit,
+    /// Encoder ran out of video memory
+    VramPressure,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct ThrottleEvent<'a> {
+    task_id: &'a str,
+    reason: ThrottleReason,
+    active: usize,
+    limit: usize,
+}
+
+struct Slots {
+    active: usize,
+    limit: usize,
+}
+
+struct GpuThrottle {
+    slots: Mutex<Slots>,
+    released: Notify,
+}
+
+impl GpuThrottle {
+    const fn new(limit: usize) -> Self {
+        Self {
+            slots: Mutex::new(Slots { active: 0, limit }),
+            released: Notify::const_new(),
+        }
+    }
+
+    fn try_take(&self) -> bool {
+        let mut slots = self.slots.lock().unwrap_or_else(|e| e.into_inner());
+        if slots.active < slots.limit {
+            slots.active += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn release(&self) {
+        let mut slots = self.slots.lock().unwrap_or_else(|e| e.into_inner());
+        slots.active = slots.active.saturating_sub(1);
+        drop(slots);
+        self.released.notify_waiters();
+    }
+
+    fn snapshot(&self) -> (usize, usize) {
+        let slots = self.slots.lock().unwrap_or_else(|e| e.into_inner());
+        (slots.active, slots.limit)
+    }
+}
+
+/// A held GPU encode slot. Released automatically when dropped.
+pub struct GpuSlot(());
+
+impl Drop for GpuSlot {
+    fn drop(&mut self) {
+        THROTTLE.release();
+    }
+}
+
+/// Wait for a free GPU encode slot. Emits `gpu-throttled` once if the job has to wait.
+pub async fn acquire(window: &tauri::WebviewWindow, task_id: &str) -> GpuSlot {
+    let mut announced = false;
+
+    loop {
+        // Register interest before checking so a release in between isn't missed
+        let released = THROTTLE.released.notified();
+
+        if THROTTLE.try_take() {
+            if announced {
+                tracing::info!(task_id = %task_id, "GPU slot acquired after waiting");
+            }
+            return GpuSlot(());
+        }
+
+        if !announced {
+            let (active, limit) = THROTTLE.snapshot();
+            tracing::info!(task_id = %task_id, active, limit, "Waiting for a free GPU slot");
+            emit_throttled(window, task_id, ThrottleReason::MaxConcurrent);
+            announced = true;
+        }
+
+        released.await;
+    }
+}
+
+/// Change the maximum number of concurrent GPU conversions (minimum 1).
+pub fn set_limit(limit: usize) {
+    let mut slots = THROTTLE.slots.lock().unwrap_or_else(|e| e.into_inner());
+    slots.limit = limit.max(1);
+    drop(slots);
+    THROTTLE.released.notify_waiters();
+}
+
+/// Classify an FFmpeg stderr line as a GPU capacity problem.
+pub fn detect_pressure(line: &str) -> Option<ThrottleReason> {
+    let lower = line.to_lowercase();
+
+    if lower.contains("openencodesessionex failed")
+        || lower.contains("incompatible client key")
+        || lower.contains("no capable devices found")
+    {
+        return Some(ThrottleReason::SessionLimit);
+    }
+
+    let gpu_related = lower.contains("nvenc")
+        || lower.contains("cuda")
+        || lower.contains("qsv")
+        || lower.contains("amf");
+    if gpu_related && lower.contains("out of memory") {
+        return Some(ThrottleReason::VramPressure);
+    }
+
+    None
+}
+
+/// React to a GPU capacity error seen in FFmpeg output: shrink the limit to what
+/// the hardware actually sustains so queued jobs wait instead of failing too.
+pub fn report_pressure(window: &tauri::WebviewWindow, task_id: &str, reason: ThrottleReason) {
+    let mut slots = THROTTLE.slots.lock().unwrap_or_else(|e| e.into_inner());
+    // The failing job holds one of the active slots; the others are what fits
+    let sustainable = slots.active.saturating_sub(1).max(1);
+    if sustainable < slots.limit {
+        tracing::warn!(
+            task_id = %task_id,
+            reason = ?reason,
+            from = slots.limit,
+            to = sustainable,
+            "GPU capacity reached, lowering concurrent GPU session limit"
+        );
+        slots.limit = sustainable;
+    }
+    drop(slots);
+
+    emit_throttled(window, task_id, reason);
+}
+
+fn emit_throttled(window: &tauri::WebviewWindow, task_id: &str, reason: ThrottleReason) {
+    let (active, limit) = THROTTLE.snapshot();
+    let _ = window.emit(
+        "gpu-throttled",
+        ThrottleEvent {
+            task_id,
+            reason,
+            active,
+            limit,
+        },
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_pressure() {
+        assert_eq!(
+            detect_pressure("[h264_nvenc @ 0x55] OpenEncodeSessionEx failed: out of memory (10)"),
+            Some(ThrottleReason::SessionLimit)
+        );
+        assert_eq!(
+            detect_pressure("[hevc_nvenc @ 0x1] No capable devices found"),
+            Some(ThrottleReason::SessionLimit)
+        );
+        assert_eq!(
+            detect_pressure("[h264_qsv @ 0x2] Error initializing: out of memory"),
+            Some(ThrottleReason::VramPressure)
+        );
+        assert_eq!(detect_pressure("frame=  100 fps= 50 q=23.0"), None);
+        assert_eq!(detect_pressure("malloc: out of memory"), None);
+    }
+}
@@ -0,0 +1,97 @@
+//! Still-image output (AVIF, JPEG XL) from an image or the first frame of a
+//! clip, through the same picture filters as GIF and WebP.
+
+use super::builder::FfmpegBuilder;
+use super::filters;
+use super::video::apply_source_orientation;
+use super::{spawn_ffmpeg, RunOptions};
+use crate::codec_map;
+use crate::codec_registry;
+use crate::error::{AppError, ErrorCode};
+use crate::formats::video::VideoFormat;
+use crate::media::MediaInfo;
+use crate::types::ConversionSettings;
+use anyhow::Result;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::process::Child;
+use tokio::sync::Mutex;
+
+/// Encode one frame of `input` as `fmt`, a `still_image` format.
+#[allow(clippy::too_many_arguments)]
+pub(super) async fn convert(
+    window: tauri::WebviewWindow,
+    input: &str,
+    output: &str,
+    fmt: &VideoFormat,
+    media: &MediaInfo,
+    settings: &ConversionSettings,
+    task_id: String,
+    processes: Arc<Mutex<HashMap<String, Child>>>,
+) -> Result<String> {
+    let encoder = still_encoder(fmt)?;
+    let duration = settings.output_duration(media.duration);
+
+    tracing::info!(task_id = %task_id, encoder, format = %fmt.extension, "Converting to a still image");
+
+    let mut builder = FfmpegBuilder::new(input, output)
+        .hide_banner()
+        .on_collision(settings.on_collision)
+        .extra_args(&settings.extra_args)
+        .input_file()
+        .progress_pipe();
+    builder = apply_source_orientation(builder, media, settings)
+        .disable_audio()
+        .video_codec(encoder)
+        .still_image_preset(encoder, settings.quality);
+    for filter in filters::still_filters(settings, duration) {
+        builder = builder.video_filter(&filter);
+    }
+    builder = builder.arg("-frames:v", "1");
+    // The image muxer expects a numbered pattern unless told to write one file
+    if fmt.container == "image2" {
+        builder = builder.arg("-update", "1");
+    }
+
+    let (args, output_path) = builder.format(&fmt.container).build();
+
+    let options = RunOptions {
+        check_duration: false,
+        ..RunOptions::from_settings(settings)
+    };
+    spawn_ffmpeg(
+        window,
+        task_id,
+        duration,
+        args,
+        output_path,
+        options.expect_video(true),
+        processes,
+    )
+    .await
+}
+
+/// First encoder for `fmt` this FFmpeg build has, so a missing one is a
+/// clear error rather than FFmpeg's.
+fn still_encoder(fmt: &VideoFormat) -> Result<&'static str> {
+    let candidates = codec_map::still_image_encoders(&fmt.extension);
+    let found = if codec_registry::is_initialized() {
+        candidates
+            .iter()
+            .copied()
+            .find(|c| codec_registry::is_encoder_available(c))
+    } else {
+        candidates.first().copied()
+    };
+    found.ok_or_else(|| {
+        AppError::new(
+            ErrorCode::UnsupportedFormat,
+            format!(
+                "This FFmpeg build can't write {} images",
+                fmt.extension.to_uppercase()
+            ),
+        )
+        .with_details(format!("Needs one of: {}", candidates.join(", ")))
+        .into()
+    })
+}
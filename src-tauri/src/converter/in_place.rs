@@ -0,0 +1,209 @@
+//! Convert a file next to itself and swap the result in for the original.
+//!
+//! The output is written to a hidden temp file in the same directory, so the
+//! final rename never crosses filesystems. The original is only touched once
+//! the new file has been probed and its duration matches what was expected.
+
+use super::{audio, checksum, video};
+use crate::error::{AppError, ErrorCode};
+use crate::formats;
+use crate::gpu::GpuInfo;
+use crate::media;
+use crate::temp_files;
+use crate::types::ConversionSettings;
+use crate::utils;
+use anyhow::Result;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tauri::Manager;
+use tokio::process::Child;
+use tokio::sync::Mutex;
+
+/// Largest duration drift accepted when verifying the output, in seconds.
+/// Lossy audio codecs pad a few frames, so exact equality is too strict.
+const DURATION_TOLERANCE_SECONDS: f64 = 1.0;
+
+/// Convert `input` to `format`, then replace it with the result. The final file
+/// keeps the input's name with the new extension; when that differs from the
+/// input, the original goes to the system trash. Returns the final path.
+pub async fn convert(
+    window: tauri::WebviewWindow,
+    input: &str,
+    format: &str,
+    gpu_info: GpuInfo,
+    mut settings: ConversionSettings,
+    processes: Arc<Mutex<HashMap<String, Child>>>,
+) -> Result<String> {
+    utils::validate_input_path(input)?;
+
+    let input_path = Path::new(input);
+    let final_path = input_path.with_extension(format);
+    if final_path != input_path && final_path.exists() {
+        return Err(AppError::new(
+            ErrorCode::InvalidPath,
+            format!("{} already exists", final_path.display()),
+        )
+        .into());
+    }
+
+    // Pin the id so the converter and the temp registry agree on it
+    let task_id = settings.task_id();
+    settings.task_id = Some(task_id.clone());
+
+    let temp_path = temp_output_path(input_path, &task_id, format);
+    let temp = temp_path.to_string_lossy().into_owned();
+    // Registered under its own key: the converter clears the task's entries on
+    // success, and this file has to outlive that until it is renamed.
+    let registry_key = format!("{}-in-place", task_id);
+    temp_files::register(&registry_key, &temp_path);
+
+    let result = run(
+        &window, input, &temp, format, gpu_info, &settings, processes,
+    )
+    .await;
+
+    let result = match result {
+        Ok(()) => replace_original(&window, input_path, &temp_path, &final_path, &settings).await,
+        Err(e) => Err(e),
+    };
+
+    // The log is named after the hidden temp file; kept whatever the outcome
+    if settings.write_log {
+        let mut log = temp_path.as_os_str().to_owned();
+        log.push(".log");
+        let mut final_log = final_path.as_os_str().to_owned();
+        final_log.push(".log");
+        let _ = std::fs::rename(log, final_log);
+    }
+
+    temp_files::finish(&registry_key);
+    result.map(|_| final_path.to_string_lossy().into_owned())
+}
+
+/// Dispatch to the converter matching the target format and source streams.
+async fn run(
+    window: &tauri::WebviewWindow,
+    input: &str,
+    output: &str,
+    format: &str,
+    gpu_info: GpuInfo,
+    settings: &ConversionSettings,
+    processes: Arc<Mutex<HashMap<String, Child>>>,
+) -> Result<()> {
+    let media = media::detect_media_type(window.app_handle(), input).await?;
+    let window = window.clone();
+    let settings = settings.clone();
+
+    if formats::audio::get_format(format).is_some() {
+        if media.video_streams.is_empty() {
+            audio::convert(window, input, output, format, settings, processes).await?;
+        } else {
+            audio::extract_from_video(window, input, output, format, settings, processes).await?;
+        }
+    } else if formats::video::get_format(format).is_some() {
+        video::convert(window, input, output, format, gpu_info, settings, processes).await?;
+    } else {
+        return Err(AppError::new(
+            ErrorCode::UnsupportedFormat,
+            format!("Unknown format: {}", format),
+        )
+        .into());
+    }
+
+    Ok(())
+}
+
+/// Verify the converted file and move it into place.
+async fn replace_original(
+    window: &tauri::WebviewWindow,
+    input: &Path,
+    temp: &Path,
+    final_path: &Path,
+    settings: &ConversionSettings,
+) -> Result<()> {
+    // The converters remove their output when cancelled
+    if !temp.exists() {
+        return Err(AppError::new(
+            ErrorCode::ConversionCancelled,
+            "Conversion cancelled; original left untouched",
+        )
+        .into());
+    }
+
+    let source = media::detect_media_type(window.app_handle(), &input.to_string_lossy()).await?;
+    let output = media::detect_media_type(window.app_handle(), &temp.to_string_lossy())
+        .await
+        .map_err(|e| verification_failed(e.to_string()))?;
+
+    if output.video_streams.is_empty() && output.audio_streams.is_empty() {
+        return Err(verification_failed("output has no audio or video streams".to_string()).into());
+    }
+    let expected = settings.output_duration(source.duration);
+    if expected > 0.0 && (output.duration - expected).abs() > DURATION_TOLERANCE_SECONDS {
+        return Err(verification_failed(format!(
+            "output is {:.1}s long, expected {:.1}s",
+            output.duration, expected
+        ))
+        .into());
+    }
+
+    // Same-directory rename replaces an existing target atomically
+    std::fs::rename(temp, final_path).map_err(|e| {
+        AppError::new(
+            ErrorCode::IoError,
+            "Failed to move converted file into place",
+        )
+        .with_details(e.to_string())
+    })?;
+    tracing::info!(input = %input.display(), output = %final_path.display(), "Replaced file in place");
+
+    if final_path != input {
+        // Leave the original where it is rather than deleting it outright
+        if let Err(e) = trash::delete(input) {
+            tracing::warn!(input = %input.display(), error = %e, "Failed to move original to trash");
+        }
+    }
+
+    // The sidecar names the temp file, so rewrite it for the final name
+    if settings.compute_checksums {
+        let mut stale = temp.as_os_str().to_owned();
+        stale.push(".sha256");
+        let _ = std::fs::remove_file(stale);
+        if let Err(e) = checksum::write_sidecar(&final_path.to_string_lossy()).await {
+            tracing::warn!(output = %final_path.display(), error = %e, "Failed to write checksum");
+        }
+    }
+
+    Ok(())
+}
+
+fn verification_failed(details: String) -> AppError {
+    AppError::new(
+        ErrorCode::ConversionFailed,
+        "Converted file failed verification; original left untouched",
+    )
+    .with_details(details)
+}
+
+/// Hidden sibling of `input`, keeping the target extension last so FFmpeg
+/// still picks the right muxer.
+fn temp_output_path(input: &Path, task_id: &str, format: &str) -> PathBuf {
+    let stem = input
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    input.with_file_name(format!(".{}.{}.{}", stem, task_id, format))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_temp_output_path() {
+        let temp = temp_output_path(Path::new("/music/song.flac"), "abc", "m4a");
+        assert_eq!(temp, PathBuf::from("/music/.song.abc.m4a"));
+        assert_eq!(temp.parent(), Some(Path::new("/music")));
+    }
+}
@@ -1,34 +1,434 @@
 pub mod audio;
 pub mod builder;
+pub mod checksum;
+pub mod defaults;
+pub mod disk;
+pub mod estimate;
+pub mod filters;
+pub mod frames;
+pub mod gpu_throttle;
+pub mod image;
+pub mod in_place;
+pub mod mux;
 pub mod progress;
+pub mod stream;
+pub mod verify;
 pub mod video;
 
 use crate::binary::get_ffmpeg_path;
-use crate::utils::create_async_hidden_command;
+use crate::codec_registry;
+use crate::error::{AppError, ErrorCode};
+use crate::loudness;
+use crate::media::MediaInfo;
+use crate::task_journal;
+use crate::temp_files;
+use crate::types::{CollisionPolicy, ConversionSettings, ProcessPriority};
+use crate::utils::{create_async_priority_command, PIPE_OUTPUT};
 use anyhow::{Context, Result};
-use progress::ProgressParser;
+use progress::{LossyLines, ProgressParser};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use std::path::Path;
+use std::collections::{HashMap, VecDeque};
+use std::path::{Path, PathBuf};
 use std::process::Stdio;
 use std::sync::Arc;
+use stream::{ProgressSource, StreamSink};
 use tauri::{Emitter, Manager};
-use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::io::{AsyncWriteExt, BufWriter};
 use tokio::process::Child;
-use tokio::sync::Mutex;
+use tokio::sync::{mpsc, Mutex};
 use tokio::time::{timeout, Duration};
 
-const CONVERSION_TIMEOUT: Duration = Duration::from_secs(3600);
+/// Default per-task time limit when the settings don't specify one.
+const DEFAULT_TIMEOUT_SECONDS: u64 = 3600;
+/// FFmpeg stderr lines kept for error reports
+const STDERR_TAIL_LINES: usize = 20;
+
+/// Stage of a conversion task, sent as `conversion-phase` on each transition
+/// and on every progress event.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConversionPhase {
+    /// Analyzing the input (probe, loudness measurement)
+    Probing,
+    /// FFmpeg is running; `percent` is meaningful
+    Encoding,
+    /// Checking the finished output (playback test, faststart, checksum)
+    Finalizing,
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ConversionProgress {
     pub task_id: String,
+    pub phase: ConversionPhase,
     pub percent: f64,
+    /// The length of the output is unknown, so `percent` can't move; show
+    /// activity instead of a bar stuck at 0%
+    pub indeterminate: bool,
     pub fps: Option<f64>,
     pub speed: Option<f64>,
     pub eta_seconds: Option<u64>,
     pub current_time: f64,
     pub total_time: f64,
+    /// Frames written so far in the current pass
+    pub frame: Option<u64>,
+    /// Expected frame count: output duration × frame rate
+    pub total_frames: Option<u64>,
+    /// File being written and its size on disk so far; filled in by the runner
+    pub output_path: Option<String>,
+    pub output_size: Option<u64>,
+}
+
+/// Fail early with an actionable message when the bundled FFmpeg has no
+/// decoder for one of the source codecs (as reported by ffprobe).
+pub fn ensure_decodable<'a>(codecs: impl IntoIterator<Item = &'a str>) -> Result<()> {
+    let missing: Vec<&str> = codecs
+        .into_iter()
+        .filter(|c| !c.is_empty() && !codec_registry::can_decode(c))
+        .collect();
+
+    if missing.is_empty() {
+        return Ok(());
+    }
+
+    let names = missing
+        .iter()
+        .map(|c| c.to_uppercase())
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    Err(AppError::new(
+        ErrorCode::UnsupportedFormat,
+        format!("Your FFmpeg build can't decode {}", names),
+    )
+    .with_details(format!("No decoder found for: {}", missing.join(", ")))
+    .into())
+}
+
+/// Announce that `task_id` entered `phase`.
+pub fn emit_phase(window: &tauri::WebviewWindow, task_id: &str, phase: ConversionPhase) {
+    tracing::debug!(task_id = %task_id, ?phase, "Conversion phase");
+    let _ = window.emit(
+        "conversion-phase",
+        serde_json::json!({
+            "task_id": task_id,
+            "phase": phase,
+        }),
+    );
+}
+
+/// Check `audio_track_index` against the probed tracks and make the chosen
+/// track the primary one, so codec, copy and sample-rate decisions follow the
+/// track that actually ends up in the output.
+pub fn select_audio_track(media: &mut MediaInfo, settings: &ConversionSettings) -> Result<()> {
+    let Some(index) = settings.audio_track_index else {
+        return Ok(());
+    };
+
+    if index >= media.audio_streams.len() {
+        return Err(AppError::new(
+            ErrorCode::ValidationFailed,
+            format!(
+                "Audio track {} doesn't exist ({} available)",
+                index,
+                media.audio_streams.len()
+            ),
+        )
+        .into());
+    }
+
+    let track = media.audio_streams.remove(index);
+    media.audio_streams.insert(0, track);
+    Ok(())
+}
+
+/// Apply the collision policy to `output` before anything is written and
+/// return the path to write to. A renamed path is announced as
+/// `conversion-output-renamed` so the UI points at the right file.
+pub fn resolve_output_path(
+    window: &tauri::WebviewWindow,
+    task_id: &str,
+    output: &str,
+    policy: CollisionPolicy,
+) -> Result<String> {
+    let path = Path::new(output);
+    if policy == CollisionPolicy::Overwrite || !path.exists() {
+        return Ok(output.to_string());
+    }
+
+    if policy == CollisionPolicy::Fail {
+        return Err(
+            AppError::new(ErrorCode::InvalidPath, "Output file already exists")
+                .with_details(output.to_string())
+                .into(),
+        );
+    }
+
+    let renamed = next_free_path(path, |p| p.exists())
+        .to_string_lossy()
+        .into_owned();
+    tracing::info!(task_id = %task_id, output = %renamed, "Output exists, writing to a new name");
+    let _ = window.emit(
+        "conversion-output-renamed",
+        serde_json::json!({
+            "task_id": task_id,
+            "output": &renamed,
+        }),
+    );
+    Ok(renamed)
+}
+
+/// Make `output` end in the chosen format's extension. FFmpeg picks the
+/// muxer from the extension when no `-f` is given, so a renamed `.mkv` for an
+/// MP4 target would silently produce a different container. The corrected
+/// path is announced like a collision rename, plus a `conversion-info` note.
+pub fn match_output_extension(
+    window: &tauri::WebviewWindow,
+    task_id: &str,
+    output: &str,
+    extension: &str,
+    container: Option<&str>,
+) -> String {
+    // A stream has no file name to correct
+    if output == PIPE_OUTPUT {
+        return output.to_string();
+    }
+    let Some(corrected) = corrected_extension(Path::new(output), extension, container) else {
+        return output.to_string();
+    };
+
+    let corrected = corrected.to_string_lossy().into_owned();
+    tracing::warn!(
+        task_id = %task_id,
+        output = %output,
+        corrected = %corrected,
+        "Output extension doesn't match the format"
+    );
+    let _ = window.emit(
+        "conversion-info",
+        serde_json::json!({
+            "task_id": task_id,
+            "message": format!("Output renamed to .{} to match the chosen format", extension),
+        }),
+    );
+    let _ = window.emit(
+        "conversion-output-renamed",
+        serde_json::json!({
+            "task_id": task_id,
+            "output": &corrected,
+        }),
+    );
+    corrected
+}
+
+/// `path` with `extension` when its own is neither that nor the container's.
+fn corrected_extension(path: &Path, extension: &str, container: Option<&str>) -> Option<PathBuf> {
+    let current = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    if current == extension.to_lowercase() || container.is_some_and(|c| current == c.to_lowercase())
+    {
+        return None;
+    }
+
+    // Append rather than replace when the name has no real extension
+    Some(if current.is_empty() {
+        PathBuf::from(format!("{}.{}", path.to_string_lossy(), extension))
+    } else {
+        path.with_extension(extension)
+    })
+}
+
+/// First of `name (1).ext`, `name (2).ext`, ... that doesn't exist.
+fn next_free_path(path: &Path, exists: impl Fn(&Path) -> bool) -> PathBuf {
+    let stem = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let extension = path
+        .extension()
+        .map(|e| format!(".{}", e.to_string_lossy()))
+        .unwrap_or_default();
+
+    (1..)
+        .map(|n| path.with_file_name(format!("{} ({}){}", stem, n, extension)))
+        .find(|candidate| !exists(candidate))
+        .expect("unbounded range always yields a free name")
+}
+
+/// Reject fades that together run longer than the output (`duration` seconds).
+pub fn check_fades(settings: &ConversionSettings, duration: f64) -> Result<()> {
+    let total = settings.fade_in().unwrap_or(0.0) + settings.fade_out().unwrap_or(0.0);
+    if duration > 0.0 && total > duration {
+        return Err(AppError::new(
+            ErrorCode::ValidationFailed,
+            format!(
+                "Fades ({}s) are longer than the {:.1}s output",
+                total, duration
+            ),
+        )
+        .into());
+    }
+    Ok(())
+}
+
+/// Reject extra arguments that would change what is read rather than how it
+/// is written. No shell is involved, so anything else is passed as-is.
+pub fn check_extra_args(settings: &ConversionSettings) -> Result<()> {
+    if settings.extra_args.iter().any(|a| a == "-i") {
+        return Err(AppError::new(
+            ErrorCode::ValidationFailed,
+            "Extra arguments can't add another input (-i)",
+        )
+        .with_details(settings.extra_args.join(" "))
+        .into());
+    }
+    Ok(())
+}
+
+/// Run (or reuse) the loudness analysis pass when normalization is requested,
+/// storing the measurements in `settings`. If analysis fails, normalization
+/// falls back to loudnorm's single-pass dynamic mode. The pass is registered
+/// under `task_id`; returns `false` (after reporting the task cancelled) if
+/// it was cancelled, in which case the conversion must not start.
+pub async fn measure_loudness(
+    window: &tauri::WebviewWindow,
+    task_id: &str,
+    input: &str,
+    settings: &mut ConversionSettings,
+    processes: &Arc<Mutex<HashMap<String, Child>>>,
+) -> bool {
+    if !settings.normalize_loudness {
+        return true;
+    }
+
+    let Some(ffmpeg_path) = get_ffmpeg_path(window.app_handle())
+        .ok()
+        .and_then(|p| p.to_str().map(|s| s.to_string()))
+    else {
+        return true;
+    };
+
+    let _ = window.emit(
+        "conversion-info",
+        serde_json::json!({
+            "task_id": task_id,
+            "message": "Measuring loudness",
+        }),
+    );
+
+    match loudness::measure(
+        &ffmpeg_path,
+        input,
+        settings.audio_track_index.unwrap_or(0),
+        settings.loudness_target(),
+        settings.reuse_measurement,
+        &loudness::AnalysisRun {
+            task_id,
+            timeout: RunOptions::from_settings(settings).timeout,
+            processes,
+        },
+    )
+    .await
+    {
+        Ok(Some(stats)) => settings.measured_loudness = Some(stats),
+        Ok(None) => {
+            tracing::info!(task_id = %task_id, "Cancelled during loudness analysis");
+            let _ = window.emit("conversion-cancelled", task_id);
+            return false;
+        }
+        Err(e) => {
+            tracing::warn!(
+                task_id = %task_id,
+                error = %e,
+                "Loudness analysis failed, using single-pass normalization"
+            );
+        }
+    }
+    true
+}
+
+/// How to run a conversion task, beyond the FFmpeg arguments themselves.
+#[derive(Debug)]
+pub struct RunOptions {
+    /// Scratch directory (pass logs and similar) removed once the task ends
+    pub temp_dir: Option<PathBuf>,
+    /// Fully decode the finished output to catch corruption
+    pub test_playback: bool,
+    /// Write a `.sha256` sidecar next to the finished output
+    pub compute_checksums: bool,
+    /// Copy FFmpeg's stderr into `<output>.log`
+    pub write_log: bool,
+    /// Kill the task after this long; `None` lets it run indefinitely
+    pub timeout: Option<Duration>,
+    /// Output frame rate, for frame counts in progress events; `None` for audio
+    pub frame_rate: Option<f64>,
+    /// Probe the finished output before reporting success
+    pub verify_output: bool,
+    /// The output must have a video stream (otherwise an audio stream)
+    pub expect_video: bool,
+    /// Compare the output length with the expected one; off when trimming
+    /// makes the length unpredictable
+    pub check_duration: bool,
+    /// A software retry follows if the hardware encoder fails, so that failure
+    /// isn't reported to the frontend as the task's error
+    pub gpu_fallback: bool,
+    /// Write to `<stem>.<suffix>.<ext>` and rename to the output path once it
+    /// passed verification; `None` writes the output path directly
+    pub partial_suffix: Option<String>,
+    /// Copy the last pass's stdout (output `pipe:1`) here instead of writing a file
+    pub stream_to: Option<StreamSink>,
+    /// Scheduling priority of the FFmpeg processes, playback test included
+    pub priority: ProcessPriority,
+}
+
+impl RunOptions {
+    pub fn from_settings(settings: &ConversionSettings) -> Self {
+        let stream_to = settings.stream_sink.as_deref().map(StreamSink::parse);
+        // A stream never lands in a file to log next to, check or rename
+        let to_file = stream_to.is_none();
+        Self {
+            temp_dir: None,
+            test_playback: settings.test_playback && to_file,
+            compute_checksums: settings.compute_checksums && to_file,
+            write_log: settings.write_log && to_file,
+            timeout: match settings.timeout_seconds.unwrap_or(DEFAULT_TIMEOUT_SECONDS) {
+                0 => None,
+                secs => Some(Duration::from_secs(secs)),
+            },
+            frame_rate: None,
+            verify_output: !settings.skip_output_verification && to_file,
+            expect_video: false,
+            check_duration: !settings.trim_silence,
+            gpu_fallback: false,
+            partial_suffix: settings
+                .partial_suffix()
+                .filter(|_| to_file)
+                .map(str::to_string),
+            priority: settings.process_priority,
+            stream_to,
+        }
+    }
+
+    pub fn with_frame_rate(mut self, frame_rate: Option<f64>) -> Self {
+        self.frame_rate = frame_rate;
+        self
+    }
+
+    pub fn expect_video(mut self, expect_video: bool) -> Self {
+        self.expect_video = expect_video;
+        self
+    }
+
+    pub fn with_temp_dir(mut self, temp_dir: Option<PathBuf>) -> Self {
+        self.temp_dir = temp_dir;
+        self
+    }
+
+    pub fn with_gpu_fallback(mut self, gpu_fallback: bool) -> Self {
+        self.gpu_fallback = gpu_fallback;
+        self
+    }
 }
 
 pub async fn spawn_ffmpeg(
@@ -37,93 +437,442 @@ pub async fn spawn_ffmpeg(
     duration: f64,
     args: Vec<String>,
     output_path: String,
+    options: RunOptions,
+    processes: Arc<Mutex<HashMap<String, Child>>>,
+) -> Result<String> {
+    spawn_ffmpeg_passes(
+        window,
+        task_id,
+        duration,
+        vec![args],
+        output_path,
+        options,
+        processes,
+    )
+    .await
+}
+
+enum PassOutcome {
+    Completed,
+    /// Exit status and FFmpeg's last stderr lines
+    Failed(std::process::ExitStatus, Vec<String>),
+    Cancelled,
+    /// FFmpeg succeeded but the output didn't pass verification
+    Unverified(AppError),
+}
+
+/// Run one or more FFmpeg invocations as a single conversion task (e.g. two-pass
+/// encoding). Progress is spread evenly across passes, `conversion-started` and
+/// `conversion-completed` fire once for the whole task, and the options' temp dir
+/// (pass logs and similar artifacts) is removed when the task ends, whatever the outcome.
+pub async fn spawn_ffmpeg_passes(
+    window: tauri::WebviewWindow,
+    task_id: String,
+    duration: f64,
+    mut passes: Vec<Vec<String>>,
+    output_path: String,
+    options: RunOptions,
     processes: Arc<Mutex<HashMap<String, Child>>>,
 ) -> Result<String> {
+    let temp_dir = options.temp_dir;
+    let frame_rate = options.frame_rate;
     let ffmpeg_path = get_ffmpeg_path(window.app_handle())
         .map_err(|e| anyhow::anyhow!("FFmpeg not found: {}", e))?;
 
-    let mut cmd = create_async_hidden_command(ffmpeg_path.to_str().unwrap());
-    cmd.args(&args)
-        .stdout(Stdio::piped())
-        .stderr(Stdio::piped());
+    // Until it is verified, the output lives under a name that can't be
+    // mistaken for a finished file, even if the app dies mid-write
+    let write_path = match &options.partial_suffix {
+        Some(suffix) => partial_path(Path::new(&output_path), suffix)
+            .to_string_lossy()
+            .into_owned(),
+        None => output_path.clone(),
+    };
+    if write_path != output_path {
+        for args in passes.iter_mut() {
+            if let Some(last) = args.last_mut().filter(|last| **last == output_path) {
+                *last = write_path.clone();
+            }
+        }
+        // Left over from an interrupted run; `-n` would refuse to replace it
+        if Path::new(&write_path).exists() {
+            let _ = tokio::fs::remove_file(&write_path).await;
+        }
+    }
+    if options.stream_to.is_some() {
+        if let Some(last_pass) = passes.pop() {
+            passes.push(stream::retarget_for_pipe(last_pass));
+        }
+    }
+
+    // Started fresh for every run; later passes append to it
+    let log_path = if options.write_log {
+        let path = PathBuf::from(format!("{}.log", output_path));
+        match tokio::fs::File::create(&path).await {
+            Ok(_) => Some(path),
+            Err(e) => {
+                tracing::warn!(task_id = %task_id, error = %e, "Failed to create conversion log");
+                None
+            }
+        }
+    } else {
+        None
+    };
+
+    // A stream leaves nothing on disk to clean up after a crash
+    if options.stream_to.is_none() {
+        task_journal::record(&task_id, &write_path);
+    }
+
+    let pass_count = passes.len() as u32;
+    let run_passes = async {
+        for (index, args) in passes.iter().enumerate() {
+            let pass = index as u32 + 1;
+
+            let mut cmd =
+                create_async_priority_command(ffmpeg_path.to_str().unwrap(), options.priority);
+            cmd.args(args).stdout(Stdio::piped()).stderr(Stdio::piped());
+
+            let mut child = cmd.spawn().context("Failed to spawn FFmpeg")?;
+            let stdout = child.stdout.take().expect("Failed to capture stdout");
+            let stderr = child.stderr.take().expect("Failed to capture stderr");
+
+            processes.lock().await.insert(task_id.clone(), child);
+
+            if pass == 1 {
+                let _ = window.emit("conversion-started", &task_id);
+                emit_phase(&window, &task_id, ConversionPhase::Encoding);
+            } else {
+                tracing::info!(task_id = %task_id, pass, pass_count, "Starting next FFmpeg pass");
+            }
+
+            // When streaming, stdout carries the output and progress shares stderr
+            let (progress_tx, progress_rx) = mpsc::unbounded_channel();
+            let (mut progress_lines, stream_copy, progress_tx) =
+                match options.stream_to.clone().filter(|_| pass == pass_count) {
+                    Some(sink) => (
+                        ProgressSource::Stderr(progress_rx),
+                        Some(tokio::spawn(stream::copy_to_sink(stdout, sink))),
+                        Some(progress_tx),
+                    ),
+                    None => (ProgressSource::Stdout(LossyLines::new(stdout)), None, None),
+                };
+
+            let task_id_err = task_id.clone();
+            let window_err = window.clone();
+            let mut log = match &log_path {
+                Some(path) => open_log(path, pass, args).await,
+                None => None,
+            };
+            let stderr_monitor = tokio::spawn(async move {
+                let mut tail = VecDeque::with_capacity(STDERR_TAIL_LINES);
+                let mut reader = LossyLines::new(stderr);
+                while let Some(line) = reader.next_line().await {
+                    if let Some(tx) = progress_tx
+                        .as_ref()
+                        .filter(|_| stream::is_progress_line(&line))
+                    {
+                        let _ = tx.send(line);
+                        continue;
+                    }
+
+                    if let Some(log) = log.as_mut() {
+                        let _ = log.write_all(format!("{}\n", line).as_bytes()).await;
+                    }
+
+                    if let Some(reason) = gpu_throttle::detect_pressure(&line) {
+                        gpu_throttle::report_pressure(&window_err, &task_id_err, reason);
+                    }
+
+                    if line.contains("Error") || line.contains("Invalid") || line.contains("failed")
+                    {
+                        tracing::warn!(task_id = %task_id_err, stderr = %line);
+                    } else if !line.is_empty() {
+                        tracing::trace!(task_id = %task_id_err, stderr = %line);
+                    }
+
+                    if !line.trim().is_empty() {
+                        if tail.len() == STDERR_TAIL_LINES {
+                            tail.pop_front();
+                        }
+                        tail.push_back(line);
+                    }
+                }
+                if let Some(log) = log.as_mut() {
+                    let _ = log.flush().await;
+                }
+                Vec::from(tail)
+            });
+
+            let mut parser = ProgressParser::new(task_id.clone(), duration, frame_rate)
+                .for_pass(pass, pass_count);
+            // `conversion-started` fires at spawn; this one once real output begins
+            let mut encoding_announced = pass > 1;
+            while let Some(line) = progress_lines.next_line().await {
+                let progress = parser.parse_line(&line);
+                if !encoding_announced && parser.encoding_started() {
+                    encoding_announced = true;
+                    let _ = window.emit("conversion-encoding", &task_id);
+                }
+                // The parser throttles updates, which also bounds how often the file is stat'ed
+                if let Some(mut progress) = progress {
+                    progress.output_size =
+                        tokio::fs::metadata(&write_path).await.ok().map(|m| m.len());
+                    progress.output_path = Some(output_path.clone());
+                    let _ = window.emit("conversion-progress", &progress);
+                    crate::batch::report_progress(&window, &task_id, progress.percent);
+                }
+            }
+
+            let Some(mut child) = processes.lock().await.remove(&task_id) else {
+                return Ok(PassOutcome::Cancelled);
+            };
 
-    let mut child = cmd.spawn().context("Failed to spawn FFmpeg")?;
-    let stdout = child.stdout.take().expect("Failed to capture stdout");
-    let stderr = child.stderr.take().expect("Failed to capture stderr");
+            let status = child.wait().await?;
+            // A sink that went away is the cause of FFmpeg's broken-pipe failure
+            if let Some(copy) = stream_copy {
+                let bytes = copy.await.context("Stream copy task panicked")??;
+                tracing::info!(task_id = %task_id, bytes, "Output streamed");
+            }
+            // Also flushes the log, before the next pass appends to it or the
+            // output is reported finished
+            let stderr_tail = stderr_monitor.await.unwrap_or_default();
+            if !status.success() {
+                return Ok(PassOutcome::Failed(status, stderr_tail));
+            }
+        }
 
-    processes.lock().await.insert(task_id.clone(), child);
+        if options.stream_to.is_some() {
+            return Ok(PassOutcome::Completed);
+        }
 
-    let _ = window.emit("conversion-started", &task_id);
+        emit_phase(&window, &task_id, ConversionPhase::Finalizing);
 
-    let task_id_err = task_id.clone();
-    tokio::spawn(async move {
-        let mut reader = BufReader::new(stderr).lines();
-        while let Ok(Some(line)) = reader.next_line().await {
-            if line.contains("Error") || line.contains("Invalid") || line.contains("failed") {
-                tracing::warn!(task_id = %task_id_err, stderr = %line);
-            } else if !line.is_empty() {
-                tracing::trace!(task_id = %task_id_err, stderr = %line);
+        if options.verify_output {
+            if let Err(error) = verify::check_output(
+                window.app_handle(),
+                &write_path,
+                if options.check_duration {
+                    duration
+                } else {
+                    0.0
+                },
+                options.expect_video,
+            )
+            .await
+            {
+                return Ok(PassOutcome::Unverified(error));
             }
         }
-    });
-
-    let window_progress = window.clone();
-    let task_id_progress = task_id.clone();
-    let processes_monitor = processes.clone();
-    let monitor_future = async move {
-        let mut reader = BufReader::new(stdout).lines();
-        let mut parser = ProgressParser::new(task_id_progress.clone(), duration);
-        while let Ok(Some(line)) = reader.next_line().await {
-            if let Some(progress) = parser.parse_line(&line) {
-                let _ = window_progress.emit("conversion-progress", &progress);
+
+        if options.test_playback {
+            let Some(report) = verify::decode_test(
+                &ffmpeg_path,
+                &write_path,
+                &task_id,
+                options.priority,
+                &processes,
+            )
+            .await?
+            else {
+                return Ok(PassOutcome::Cancelled);
+            };
+            verify::emit_playback_report(&window, &task_id, &report);
+        }
+
+        if write_path != output_path {
+            tokio::fs::rename(&write_path, &output_path)
+                .await
+                .with_context(|| format!("Failed to rename {} to {}", write_path, output_path))?;
+        }
+
+        if verify::is_faststart(&output_path).await == Some(false) {
+            verify::emit_faststart_advisory(&window, &task_id);
+        }
+
+        // The output itself is fine, so a failed sidecar doesn't fail the task
+        if options.compute_checksums {
+            match checksum::write_sidecar(&output_path).await {
+                Ok(sidecar) => {
+                    tracing::info!(task_id = %task_id, sidecar = %sidecar.display(), "Checksum written")
+                }
+                Err(e) => {
+                    tracing::warn!(task_id = %task_id, error = %e, "Failed to write checksum")
+                }
             }
         }
-        processes_monitor.lock().await.remove(&task_id_progress)
+
+        Ok::<_, anyhow::Error>(PassOutcome::Completed)
     };
 
-    match timeout(CONVERSION_TIMEOUT, monitor_future).await {
-        Ok(Some(mut child)) => {
-            let status = child.wait().await?;
-            if status.success() {
-                let _ = window.emit("conversion-completed", &task_id);
-                Ok(task_id)
-            } else {
-                cleanup_failed(&output_path).await;
-                let error = format!("FFmpeg exited with code: {}", status);
+    let outcome = match options.timeout {
+        Some(limit) => timeout(limit, run_passes).await,
+        None => Ok(run_passes.await),
+    };
+    task_journal::finish(&task_id);
+
+    match outcome {
+        Ok(Ok(PassOutcome::Completed)) => {
+            remove_task_files(&task_id, temp_dir.as_deref()).await;
+            let _ = window.emit("conversion-completed", &task_id);
+            Ok(task_id)
+        }
+        Ok(Ok(PassOutcome::Failed(status, stderr_tail))) => {
+            cleanup_failed(&task_id, &write_path, temp_dir.as_deref()).await;
+            let error = AppError::from_ffmpeg_failure(status, &stderr_tail);
+            if !(options.gpu_fallback && error.code == ErrorCode::GpuEncoderFailed) {
                 emit_error(&window, &task_id, &error);
-                anyhow::bail!(error)
             }
+            Err(error.into())
         }
-        Ok(None) => {
-            cleanup_failed(&output_path).await;
+        Ok(Ok(PassOutcome::Unverified(error))) => {
+            tracing::warn!(task_id = %task_id, error = %error, "Output failed verification");
+            cleanup_failed(&task_id, &write_path, temp_dir.as_deref()).await;
+            emit_error(&window, &task_id, &error);
+            Err(error.into())
+        }
+        Ok(Ok(PassOutcome::Cancelled)) => {
+            cleanup_failed(&task_id, &write_path, temp_dir.as_deref()).await;
             let _ = window.emit("conversion-cancelled", &task_id);
             Ok(task_id)
         }
+        Ok(Err(e)) => {
+            if let Some(mut child) = processes.lock().await.remove(&task_id) {
+                let _ = child.kill().await;
+            }
+            cleanup_failed(&task_id, &write_path, temp_dir.as_deref()).await;
+            Err(e)
+        }
         Err(_) => {
             if let Some(mut child) = processes.lock().await.remove(&task_id) {
                 let _ = child.kill().await;
             }
-            cleanup_failed(&output_path).await;
-            let error = "Conversion timed out (limit: 1 hour)";
-            emit_error(&window, &task_id, error);
-            anyhow::bail!(error)
+            cleanup_failed(&task_id, &write_path, temp_dir.as_deref()).await;
+            let limit = options.timeout.unwrap_or_default().as_secs();
+            let error = AppError::new(
+                ErrorCode::ConversionTimeout,
+                format!(
+                    "Conversion timed out (limit: {}:{:02}:{:02})",
+                    limit / 3600,
+                    limit % 3600 / 60,
+                    limit % 60
+                ),
+            );
+            emit_error(&window, &task_id, &error);
+            Err(error.into())
         }
     }
 }
 
-async fn cleanup_failed(path: &str) {
+/// Where the output is written while in progress: `movie.part.mp4` for
+/// `movie.mp4`. The real extension stays last because FFmpeg picks the muxer
+/// from it, and the file stays in the same directory so the final rename
+/// doesn't cross filesystems.
+fn partial_path(output: &Path, suffix: &str) -> PathBuf {
+    let stem = output.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match output.extension() {
+        Some(ext) => format!("{}.{}.{}", stem, suffix, ext.to_string_lossy()),
+        None => format!("{}.{}", stem, suffix),
+    };
+    output.with_file_name(name)
+}
+
+/// Open the conversion log for appending and note which FFmpeg run follows.
+async fn open_log(path: &Path, pass: u32, args: &[String]) -> Option<BufWriter<tokio::fs::File>> {
+    let file = tokio::fs::OpenOptions::new()
+        .append(true)
+        .open(path)
+        .await
+        .ok()?;
+    let mut log = BufWriter::new(file);
+    let header = format!("=== Pass {}: ffmpeg {}\n", pass, args.join(" "));
+    log.write_all(header.as_bytes()).await.ok()?;
+    Some(log)
+}
+
+/// Remove the partial output and any per-task temporary artifacts (pass logs).
+async fn cleanup_failed(task_id: &str, path: &str, temp_dir: Option<&Path>) {
     let path = Path::new(path);
     if path.exists() {
         let _ = tokio::fs::remove_file(path).await;
     }
+    remove_task_files(task_id, temp_dir).await;
 }
 
-fn emit_error(window: &tauri::WebviewWindow, task_id: &str, error: &str) {
+/// Remove the task's temp dir and everything it registered in the temp file registry.
+async fn remove_task_files(task_id: &str, temp_dir: Option<&Path>) {
+    if let Some(dir) = temp_dir {
+        if dir.exists() {
+            let _ = tokio::fs::remove_dir_all(dir).await;
+        }
+    }
+    temp_files::finish(task_id);
+}
+
+fn emit_error(window: &tauri::WebviewWindow, task_id: &str, error: &AppError) {
     let _ = window.emit(
         "conversion-error",
         serde_json::json!({
             "task_id": task_id,
-            "error": error
+            "error": error.message,
+            "code": error.code,
+            "details": error.details
         }),
     );
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_partial_path() {
+        assert_eq!(
+            partial_path(Path::new("/out/movie.mp4"), "part"),
+            PathBuf::from("/out/movie.part.mp4")
+        );
+        assert_eq!(
+            partial_path(Path::new("/out/clip.final.mkv"), "tmp"),
+            PathBuf::from("/out/clip.final.tmp.mkv")
+        );
+        assert_eq!(
+            partial_path(Path::new("/out/notes"), "part"),
+            PathBuf::from("/out/notes.part")
+        );
+    }
+
+    #[test]
+    fn test_next_free_path() {
+        let taken = [
+            PathBuf::from("/out/clip.mp4"),
+            PathBuf::from("/out/clip (1).mp4"),
+        ];
+        assert_eq!(
+            next_free_path(Path::new("/out/clip.mp4"), |p| taken.iter().any(|t| t == p)),
+            PathBuf::from("/out/clip (2).mp4")
+        );
+        assert_eq!(
+            next_free_path(Path::new("/out/notes"), |_| false),
+            PathBuf::from("/out/notes (1)")
+        );
+    }
+
+    #[test]
+    fn test_corrected_extension() {
+        assert_eq!(
+            corrected_extension(Path::new("/out/a.MP4"), "mp4", None),
+            None
+        );
+        assert_eq!(
+            corrected_extension(Path::new("/out/a.mkv"), "mp4", Some("mp4")),
+            Some(PathBuf::from("/out/a.mp4"))
+        );
+        assert_eq!(
+            corrected_extension(Path::new("/out/a.m4a"), "m4a", Some("mp4")),
+            None
+        );
+        assert_eq!(
+            corrected_extension(Path::new("/out/take 1"), "webm", Some("webm")),
+            Some(PathBuf::from("/out/take 1.webm"))
+        );
+    }
+}
@@ -0,0 +1,154 @@
+//! Put a separate audio file under a video's picture.
+//!
+//! The video stream is copied as-is; the audio is copied too when the
+//! container takes its codec, and encoded with the usual audio settings
+//! otherwise. By default the output ends with the shorter input.
+
+use super::builder::FfmpegBuilder;
+use super::disk;
+use super::video::{apply_audio_settings, apply_container_settings};
+use super::{
+    check_extra_args, emit_phase, ensure_decodable, match_output_extension, measure_loudness,
+    resolve_output_path, select_audio_track, spawn_ffmpeg, ConversionPhase, RunOptions,
+};
+use crate::error::{AppError, ErrorCode};
+use crate::formats::video;
+use crate::media;
+use crate::types::ConversionSettings;
+use crate::utils;
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tauri::Manager;
+use tokio::process::Child;
+use tokio::sync::Mutex;
+
+/// Options in front of an input that repeat it until the other one ends.
+const LOOP_INPUT: [&str; 2] = ["-stream_loop", "-1"];
+
+/// Mux the first video stream of `video_input` with the audio of
+/// `audio_input` (track `audio_track_index`, first by default). With
+/// `loop_shorter`, the shorter input repeats to fill the longer one instead
+/// of cutting the output short.
+#[allow(clippy::too_many_arguments)]
+pub async fn mux_audio(
+    window: tauri::WebviewWindow,
+    video_input: &str,
+    audio_input: &str,
+    output: &str,
+    format: &str,
+    mut settings: ConversionSettings,
+    loop_shorter: bool,
+    processes: Arc<Mutex<HashMap<String, Child>>>,
+) -> Result<String> {
+    utils::validate_input_path(video_input)?;
+    utils::validate_input_path(audio_input)?;
+    utils::validate_output_path(output)?;
+    check_extra_args(&settings)?;
+    settings.resolve_legacy_bitrate(false);
+
+    let task_id = settings.task_id();
+    let fmt = video::get_format(format).context("Unknown video format")?;
+    let output = &match_output_extension(
+        &window,
+        &task_id,
+        output,
+        &fmt.extension,
+        Some(&fmt.container),
+    );
+    let output = &resolve_output_path(&window, &task_id, output, settings.on_collision)?;
+
+    emit_phase(&window, &task_id, ConversionPhase::Probing);
+    let video_media = media::detect_media_type(window.app_handle(), video_input).await?;
+    let mut audio_media = media::detect_media_type(window.app_handle(), audio_input).await?;
+    select_audio_track(&mut audio_media, &settings)?;
+
+    let Some(picture) = video_media.primary_video() else {
+        return Err(AppError::new(
+            ErrorCode::ValidationFailed,
+            "Video input has no video stream",
+        )
+        .with_details(video_input.to_string())
+        .into());
+    };
+    if audio_media.audio_streams.is_empty() {
+        return Err(AppError::new(
+            ErrorCode::ValidationFailed,
+            "Audio input has no audio stream",
+        )
+        .with_details(audio_input.to_string())
+        .into());
+    }
+    if !fmt.supports_video_codec(&picture.codec) || fmt.audio_codecs.is_empty() {
+        return Err(AppError::new(
+            ErrorCode::UnsupportedFormat,
+            format!(
+                "{} can't hold {} video with audio without re-encoding",
+                fmt.extension, picture.codec
+            ),
+        )
+        .into());
+    }
+
+    let (video_duration, audio_duration) = (video_media.duration, audio_media.duration);
+    let duration = if loop_shorter {
+        video_duration.max(audio_duration)
+    } else {
+        video_duration.min(audio_duration)
+    };
+    disk::ensure_free_space(
+        output,
+        disk::estimate_output_size(&video_media, &settings, true),
+    )?;
+
+    ensure_decodable(audio_media.audio_codec())?;
+    if !measure_loudness(&window, &task_id, audio_input, &mut settings, &processes).await {
+        return Ok(task_id);
+    }
+
+    // The track is mapped from the second input here, not from the first
+    let audio_map = format!("1:a:{}", settings.audio_track_index.unwrap_or(0));
+    settings.audio_track_index = None;
+
+    let loop_args: Vec<String> = LOOP_INPUT.iter().map(|a| a.to_string()).collect();
+    let mut builder = FfmpegBuilder::new(video_input, output)
+        .hide_banner()
+        .on_collision(settings.on_collision)
+        .extra_args(&settings.extra_args)
+        .input_file();
+    if loop_shorter && video_duration < audio_duration {
+        builder = builder.input_args(&loop_args);
+    }
+    let audio_options = if loop_shorter && audio_duration < video_duration {
+        loop_args.as_slice()
+    } else {
+        &[]
+    };
+
+    builder = builder
+        .extra_input(audio_input, audio_options)
+        .progress_pipe()
+        .metadata(&settings.metadata, settings.preserve_metadata)
+        .map("0:v:0")
+        .map(&audio_map)
+        .video_codec("copy");
+    builder = apply_audio_settings(builder, &fmt, &audio_media, &settings);
+    // Ends with the shorter input, or the longer one when the other loops forever
+    builder = builder.flag("-shortest");
+    builder = apply_container_settings(builder, &fmt, &settings);
+
+    let (args, output_path) = builder.build();
+
+    spawn_ffmpeg(
+        window,
+        task_id,
+        duration,
+        args,
+        output_path,
+        RunOptions::from_settings(&settings)
+            .with_frame_rate(Some(picture.fps))
+            .expect_video(true),
+        processes,
+    )
+    .await
+}
@@ -1,13 +1,16 @@
-use super::ConversionProgress;
+use super::{ConversionPhase, ConversionProgress};
 use lazy_static::lazy_static;
 use regex::Regex;
 use std::time::Instant;
+use tokio::io::{AsyncBufReadExt, AsyncRead, BufReader};
 
 lazy_static! {
+    static ref OUT_TIME_REGEX: Regex = Regex::new(r"out_time=(\d+):(\d+):(\d+(?:\.\d+)?)").unwrap();
     static ref TIME_US_REGEX: Regex = Regex::new(r"out_time_us=(\d+)").unwrap();
     static ref TIME_MS_REGEX: Regex = Regex::new(r"out_time_ms=(\d+)").unwrap();
-    static ref FPS_REGEX: Regex = Regex::new(r"fps=([\d.]+)").unwrap();
-    static ref SPEED_REGEX: Regex = Regex::new(r"speed=([\d.]+)x").unwrap();
+    static ref FRAME_REGEX: Regex = Regex::new(r"^frame=(\d+)").unwrap();
+    static ref FPS_REGEX: Regex = Regex::new(r"^fps=([\d.]+)").unwrap();
+    static ref SPEED_REGEX: Regex = Regex::new(r"^speed=\s*([\d.]+)x").unwrap();
 }
 
 const UPDATE_INTERVAL_MS: u128 = 100;
@@ -15,51 +18,115 @@ const UPDATE_INTERVAL_MS: u128 = 100;
 pub struct ProgressParser {
     task_id: String,
     total_duration: f64,
+    /// 1-based index of the current FFmpeg pass and total number of passes.
+    /// Each pass covers an equal slice of the overall 0–100% range.
+    pass: u32,
+    pass_count: u32,
     start_time: Instant,
     last_update: Instant,
     last_progress: Option<ConversionProgress>,
+    /// Set once an `out_time=` line was seen; the numeric fields are then skipped
+    /// so each progress block reports its time once.
+    has_out_time: bool,
+    /// Set at the first time past zero: FFmpeg is past probing and seeking
+    /// and has written media
+    encoding_started: bool,
+    /// Latest `frame=` value; it comes before the time in each progress block
+    frame: Option<u64>,
+    /// Latest `fps=` and `speed=` values. `speed=` comes after the time, so a
+    /// block reports the speed of the one before it.
+    fps: Option<f64>,
+    speed: Option<f64>,
+    total_frames: Option<u64>,
 }
 
 impl ProgressParser {
-    pub fn new(task_id: String, total_duration: f64) -> Self {
+    /// `frame_rate` is the output's, used to estimate the total frame count.
+    pub fn new(task_id: String, total_duration: f64, frame_rate: Option<f64>) -> Self {
+        let total_frames = frame_rate
+            .filter(|fps| *fps > 0.0 && total_duration > 0.0)
+            .map(|fps| (total_duration * fps).round() as u64);
         Self {
             task_id,
             total_duration,
+            pass: 1,
+            pass_count: 1,
             start_time: Instant::now(),
             last_update: Instant::now(),
             last_progress: None,
+            has_out_time: false,
+            encoding_started: false,
+            frame: None,
+            fps: None,
+            speed: None,
+            total_frames,
         }
     }
 
+    /// Report progress for pass `pass` of `pass_count` (e.g. two-pass encoding:
+    /// pass 1 maps to 0–50%, pass 2 to 50–100%).
+    pub fn for_pass(mut self, pass: u32, pass_count: u32) -> Self {
+        self.pass_count = pass_count.max(1);
+        self.pass = pass.clamp(1, self.pass_count);
+        self
+    }
+
+    /// Whether a time past zero has been seen yet.
+    pub fn encoding_started(&self) -> bool {
+        self.encoding_started
+    }
+
+    fn is_final_pass(&self) -> bool {
+        self.pass >= self.pass_count
+    }
+
+    /// Map a percentage within the current pass onto the overall range.
+    fn overall_percent(&self, pass_percent: f64) -> f64 {
+        let slice = 100.0 / self.pass_count as f64;
+        (self.pass - 1) as f64 * slice + pass_percent * slice / 100.0
+    }
+
     pub fn parse_line(&mut self, line: &str) -> Option<ConversionProgress> {
         if line.contains("progress=end") {
             return self.make_progress(true, None, None, None);
         }
 
-        let current_us = TIME_US_REGEX
+        if let Some(frame) = FRAME_REGEX
             .captures(line)
-            .and_then(|c| c[1].parse::<i64>().ok());
+            .and_then(|c| c[1].parse::<u64>().ok())
+        {
+            self.frame = Some(frame);
+            return None;
+        }
+        if let Some(fps) = FPS_REGEX.captures(line).and_then(|c| c[1].parse().ok()) {
+            self.fps = Some(fps);
+            return None;
+        }
+        if let Some(speed) = SPEED_REGEX.captures(line).and_then(|c| c[1].parse().ok()) {
+            self.speed = Some(speed);
+            return None;
+        }
 
-        let current_ms = if current_us.is_none() {
-            TIME_MS_REGEX
-                .captures(line)
-                .and_then(|c| c[1].parse::<i64>().ok())
+        let current_time = if let Some(seconds) = parse_out_time(line) {
+            self.has_out_time = true;
+            seconds
+        } else if self.has_out_time {
+            return None;
         } else {
-            None
-        };
-
-        let current_time = if let Some(us) = current_us {
+            // Fallback for builds without `out_time=`. Despite its name,
+            // `out_time_ms` is in microseconds too.
+            let us = TIME_US_REGEX
+                .captures(line)
+                .or_else(|| TIME_MS_REGEX.captures(line))
+                .and_then(|c| c[1].parse::<i64>().ok())?;
             us as f64 / 1_000_000.0
-        } else if let Some(ms) = current_ms {
-            ms as f64 / 1_000.0
-        } else {
-            return None;
         };
 
-        let fps = FPS_REGEX.captures(line).and_then(|c| c[1].parse().ok());
-        let speed = SPEED_REGEX.captures(line).and_then(|c| c[1].parse().ok());
+        if current_time > 0.0 {
+            self.encoding_started = true;
+        }
 
-        self.make_progress(false, Some(current_time), fps, speed)
+        self.make_progress(false, Some(current_time), self.fps, self.speed)
     }
 
     fn make_progress(
@@ -70,14 +137,26 @@ impl ProgressParser {
         speed: Option<f64>,
     ) -> Option<ConversionProgress> {
         if is_end {
+            // Only the final pass completes the task; earlier passes end at their slice boundary
+            let (percent, eta_seconds) = if self.is_final_pass() {
+                (100.0, Some(0))
+            } else {
+                (self.overall_percent(100.0), None)
+            };
             let progress = ConversionProgress {
                 task_id: self.task_id.clone(),
-                percent: 100.0,
+                phase: ConversionPhase::Encoding,
+                percent,
+                indeterminate: false,
                 fps: None,
                 speed: None,
-                eta_seconds: Some(0),
+                eta_seconds,
                 current_time: self.total_duration,
                 total_time: self.total_duration,
+                frame: self.frame,
+                total_frames: self.total_frames,
+                output_path: None,
+                output_size: None,
             };
             self.last_progress = Some(progress.clone());
             return Some(progress);
@@ -91,27 +170,36 @@ impl ProgressParser {
 
         let current_time = current_time.unwrap_or(0.0);
 
-        let mut percent = if self.total_duration > 0.0001 {
-            (current_time / self.total_duration) * 100.0
-        } else {
+        // ffprobe finds no duration for some streams; time still advances
+        let indeterminate = self.total_duration <= 0.0001;
+        let mut percent = if indeterminate {
             0.0
+        } else {
+            (current_time / self.total_duration) * 100.0
         };
 
         if percent > 99.0 {
             percent = 99.0;
         }
+        let percent = self.overall_percent(percent);
 
         let eta_seconds = self.calculate_eta(current_time, speed);
         self.last_update = Instant::now();
 
         let progress = ConversionProgress {
             task_id: self.task_id.clone(),
+            phase: ConversionPhase::Encoding,
             percent,
+            indeterminate,
             fps,
             speed,
             eta_seconds,
             current_time,
             total_time: self.total_duration,
+            frame: self.frame,
+            total_frames: self.total_frames,
+            output_path: None,
+            output_size: None,
         };
 
         self.last_progress = Some(progress.clone());
@@ -123,7 +211,9 @@ impl ProgressParser {
             return None;
         }
 
-        let remaining = self.total_duration - current_time;
+        // Remaining passes each re-process the full duration
+        let remaining_passes = (self.pass_count - self.pass) as f64;
+        let remaining = self.total_duration - current_time + remaining_passes * self.total_duration;
 
         if let Some(s) = speed {
             if s > 0.0 {
@@ -142,3 +232,165 @@ impl ProgressParser {
         None
     }
 }
+
+/// Seconds from an `out_time=HH:MM:SS.micro` line. Negative or `N/A` values,
+/// which FFmpeg prints before the first frame, don't match.
+fn parse_out_time(line: &str) -> Option<f64> {
+    let caps = OUT_TIME_REGEX.captures(line)?;
+    let hours: f64 = caps[1].parse().ok()?;
+    let minutes: f64 = caps[2].parse().ok()?;
+    let seconds: f64 = caps[3].parse().ok()?;
+    Some(hours * 3600.0 + minutes * 60.0 + seconds)
+}
+
+/// Line reader for FFmpeg output that decodes lossily. `Lines::next_line` gives
+/// up at the first invalid UTF-8 byte (locale-encoded filenames, odd metadata),
+/// which would silently stop progress monitoring mid-conversion.
+pub struct LossyLines<R> {
+    reader: BufReader<R>,
+    buf: Vec<u8>,
+}
+
+impl<R: AsyncRead + Unpin> LossyLines<R> {
+    pub fn new(inner: R) -> Self {
+        Self {
+            reader: BufReader::new(inner),
+            buf: Vec::new(),
+        }
+    }
+
+    /// Next line without its terminator, or `None` at end of stream.
+    pub async fn next_line(&mut self) -> Option<String> {
+        self.buf.clear();
+        match self.reader.read_until(b'\n', &mut self.buf).await {
+            Ok(0) | Err(_) => None,
+            Ok(_) => Some(
+                String::from_utf8_lossy(&self.buf)
+                    .trim_end_matches(['\n', '\r'])
+                    .to_string(),
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_lossy_lines_survive_invalid_utf8() {
+        let input: &[u8] = b"frame=1\r\nInput #0, from 'caf\xe9.mp4':\nprogress=end";
+        let mut lines = LossyLines::new(input);
+        assert_eq!(lines.next_line().await.as_deref(), Some("frame=1"));
+        assert_eq!(
+            lines.next_line().await.as_deref(),
+            Some("Input #0, from 'caf\u{FFFD}.mp4':")
+        );
+        assert_eq!(lines.next_line().await.as_deref(), Some("progress=end"));
+        assert_eq!(lines.next_line().await, None);
+    }
+
+    #[test]
+    fn test_out_time_ms_is_microseconds() {
+        // Progress block from a build that reports `out_time_ms` without `out_time_us`
+        let block = "frame=120\nfps=48.00\nstream_0_0_q=28.0\nbitrate=1843.2kbits/s\n\
+                     total_size=1153024\nout_time_ms=5005000\nout_time=00:00:05.005000\n\
+                     dup_frames=0\ndrop_frames=0\nspeed=2.00x\nprogress=continue";
+        let mut parser = ProgressParser::new("t".to_string(), 10.0, Some(24.0));
+        let progress: Vec<_> = block.lines().filter_map(|l| parser.parse_line(l)).collect();
+        assert_eq!(progress.len(), 1);
+        assert!((progress[0].current_time - 5.005).abs() < 1e-9);
+        assert!((progress[0].percent - 50.05).abs() < 0.01);
+        assert_eq!(progress[0].frame, Some(120));
+        assert_eq!(progress[0].total_frames, Some(240));
+        assert_eq!(progress[0].fps, Some(48.0));
+        // `speed=` follows the time, so the first block has none yet
+        assert_eq!(progress[0].speed, None);
+
+        parser.last_progress = None;
+        let next = block
+            .replace("05.005000", "06.006000")
+            .replace("5005000", "6006000");
+        let progress: Vec<_> = next.lines().filter_map(|l| parser.parse_line(l)).collect();
+        assert_eq!(progress.len(), 1);
+        assert_eq!(
+            (progress[0].fps, progress[0].speed),
+            (Some(48.0), Some(2.0))
+        );
+    }
+
+    #[test]
+    fn test_unknown_duration_is_indeterminate() {
+        let mut parser = ProgressParser::new("t".to_string(), 0.0, Some(24.0));
+        let progress = parser.parse_line("out_time=00:00:05.000000").unwrap();
+        assert!(progress.indeterminate);
+        assert_eq!((progress.percent, progress.current_time), (0.0, 5.0));
+        assert_eq!(progress.total_frames, None);
+
+        let end = parser.parse_line("progress=end").unwrap();
+        assert!(!end.indeterminate);
+        assert_eq!(end.percent, 100.0);
+    }
+
+    #[test]
+    fn test_time_formats() {
+        let time = |line: &str| {
+            ProgressParser::new("t".to_string(), 10_000.0, None)
+                .parse_line(line)
+                .map(|p| p.current_time)
+        };
+        assert_eq!(time("out_time=01:02:03.500000"), Some(3723.5));
+        assert_eq!(time("out_time_us=3723500000"), Some(3723.5));
+        assert_eq!(time("out_time_ms=3723500000"), Some(3723.5));
+        assert_eq!(time("out_time=N/A"), None);
+        assert_eq!(time("out_time=-577014:32:22.771192"), None);
+    }
+
+    #[test]
+    fn test_out_time_preferred_over_numeric_fields() {
+        // Full block from a current build: all three time fields present
+        let block = "frame=240\nfps=60.00\nbitrate=2048.0kbits/s\nout_time_us=4004000\n\
+                     out_time_ms=4004000\nout_time=00:00:04.004000\nspeed=2.5x\n\
+                     progress=continue";
+        let mut parser = ProgressParser::new("t".to_string(), 8.0, None);
+        let progress: Vec<_> = block.lines().filter_map(|l| parser.parse_line(l)).collect();
+        // `out_time_us` comes first and reports; the rest of the block is throttled
+        assert_eq!(progress.len(), 1);
+        assert!((progress[0].current_time - 4.004).abs() < 1e-9);
+        assert_eq!(progress[0].fps, Some(60.0));
+        assert_eq!(parser.speed, Some(2.5));
+        // Once `out_time=` has been seen, the numeric fields are ignored
+        assert!(parser.has_out_time);
+        parser.last_progress = None;
+        assert!(parser.parse_line("out_time_us=6000000").is_none());
+        // `speed=N/A` leaves the cached value alone
+        assert!(parser.parse_line("speed=N/A").is_none());
+        let p = parser.parse_line("out_time=00:00:06.000000").unwrap();
+        assert_eq!((p.fps, p.speed), (Some(60.0), Some(2.5)));
+    }
+
+    #[test]
+    fn test_encoding_started_after_first_time() {
+        let mut parser = ProgressParser::new("t".to_string(), 10.0, None);
+        parser.parse_line("out_time=N/A");
+        parser.parse_line("out_time=00:00:00.000000");
+        assert!(!parser.encoding_started());
+        parser.parse_line("out_time=00:00:00.040000");
+        assert!(parser.encoding_started());
+    }
+
+    #[test]
+    fn test_two_pass_percent_mapping() {
+        let mut first = ProgressParser::new("t".to_string(), 100.0, None).for_pass(1, 2);
+        let p = first.parse_line("out_time_us=50000000").unwrap();
+        assert!((p.percent - 25.0).abs() < 0.01);
+        let end = first.parse_line("progress=end").unwrap();
+        assert!((end.percent - 50.0).abs() < 0.01);
+
+        let mut second = ProgressParser::new("t".to_string(), 100.0, None).for_pass(2, 2);
+        let p = second.parse_line("out_time_us=50000000").unwrap();
+        assert!((p.percent - 75.0).abs() < 0.01);
+        let end = second.parse_line("progress=end").unwrap();
+        assert_eq!(end.percent, 100.0);
+    }
+}
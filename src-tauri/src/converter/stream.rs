@@ -0,0 +1,207 @@
+//! Streamed conversions, for embedding the converter in a larger pipeline.
+//!
+//! FFmpeg writes the output to its stdout (`pipe:1`) and the bytes are copied
+//! to a sink another process reads from. Only the `convert_to_stream` command
+//! starts one. With stdout carrying the data, progress moves to stderr
+//! (`-progress pipe:2`) and is picked out of FFmpeg's messages there. Nothing
+//! is verified, renamed or checksummed afterwards: there is no file to check.
+
+use super::progress::LossyLines;
+use anyhow::{Context, Result};
+use std::path::PathBuf;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use tokio::process::ChildStdout;
+use tokio::sync::mpsc;
+
+/// MP4-family muxers, which can only write to a pipe when fragmented.
+const ISOBMFF_MUXERS: [&str; 5] = ["mp4", "mov", "ipod", "ismv", "3gp"];
+const FRAGMENTED_MOVFLAGS: &str = "+frag_keyframe+empty_moov+default_base_moof";
+
+/// Where a streamed conversion's bytes go.
+#[derive(Debug, Clone, PartialEq)]
+pub enum StreamSink {
+    /// The app's own stdout, when it runs as a child of the reading process
+    Stdout,
+    /// A FIFO, a Windows named pipe (`\\.\pipe\name`) or a plain file
+    Path(PathBuf),
+}
+
+impl StreamSink {
+    /// `-` is stdout, anything else a path.
+    pub fn parse(spec: &str) -> Self {
+        match spec {
+            "-" => StreamSink::Stdout,
+            path => StreamSink::Path(PathBuf::from(path)),
+        }
+    }
+
+    async fn open(&self) -> std::io::Result<Box<dyn AsyncWrite + Send + Unpin>> {
+        match self {
+            StreamSink::Stdout => Ok(Box::new(tokio::io::stdout())),
+            StreamSink::Path(path) => {
+                // Pipes can't be created or truncated, a leftover file must be
+                let regular_file = tokio::fs::metadata(path)
+                    .await
+                    .map_or(true, |m| m.is_file());
+                let file = tokio::fs::OpenOptions::new()
+                    .write(true)
+                    .create(regular_file)
+                    .truncate(regular_file)
+                    .open(path)
+                    .await?;
+                Ok(Box::new(file))
+            }
+        }
+    }
+}
+
+/// Copy FFmpeg's stdout into `sink` until FFmpeg closes it. Returns the
+/// number of bytes streamed.
+pub(super) async fn copy_to_sink(mut stdout: ChildStdout, sink: StreamSink) -> Result<u64> {
+    let mut writer = sink
+        .open()
+        .await
+        .with_context(|| format!("Failed to open stream sink {:?}", sink))?;
+    let bytes = tokio::io::copy(&mut stdout, &mut writer)
+        .await
+        .context("Failed to write to the stream sink")?;
+    writer.flush().await?;
+    Ok(bytes)
+}
+
+/// Adapt the arguments of a run writing to `pipe:1`: progress goes to stderr
+/// instead, and since an MP4 index can't be moved to the front of a stream,
+/// MP4-family outputs are fragmented rather than faststarted.
+pub(super) fn retarget_for_pipe(args: Vec<String>) -> Vec<String> {
+    let mut retargeted = Vec::with_capacity(args.len() + 2);
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-progress" => {
+                args.next();
+                retargeted.extend(["-progress".to_string(), "pipe:2".to_string()]);
+            }
+            "-movflags" => {
+                args.next();
+            }
+            _ => retargeted.push(arg),
+        }
+    }
+
+    let muxer = retargeted
+        .iter()
+        .rposition(|a| a == "-f")
+        .and_then(|i| retargeted.get(i + 1));
+    if muxer.is_some_and(|m| ISOBMFF_MUXERS.contains(&m.as_str())) {
+        let output = retargeted.len().saturating_sub(1);
+        retargeted.splice(
+            output..output,
+            ["-movflags".to_string(), FRAGMENTED_MOVFLAGS.to_string()],
+        );
+    }
+    retargeted
+}
+
+/// A `-progress` report line (`out_time_us=1000000`), as opposed to one of
+/// FFmpeg's messages; the stats line (`frame=  12 fps=...`) has spaces.
+pub(super) fn is_progress_line(line: &str) -> bool {
+    line.split_once('=').is_some_and(|(key, value)| {
+        !key.is_empty()
+            && key.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'_')
+            && !value.contains(char::is_whitespace)
+    })
+}
+
+/// Where a run's progress lines come from: FFmpeg's stdout normally, or the
+/// lines the stderr reader picked out while stdout carries a stream.
+pub(super) enum ProgressSource {
+    Stdout(LossyLines<ChildStdout>),
+    Stderr(mpsc::UnboundedReceiver<String>),
+}
+
+impl ProgressSource {
+    pub(super) async fn next_line(&mut self) -> Option<String> {
+        match self {
+            ProgressSource::Stdout(lines) => lines.next_line().await,
+            ProgressSource::Stderr(lines) => lines.recv().await,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|a| a.to_string()).collect()
+    }
+
+    #[test]
+    fn test_retarget_for_pipe() {
+        let retargeted = retarget_for_pipe(args(&[
+            "-i",
+            "in.mkv",
+            "-progress",
+            "pipe:1",
+            "-c:a",
+            "aac",
+            "-f",
+            "mp4",
+            "-movflags",
+            "+faststart",
+            "pipe:1",
+        ]));
+        assert_eq!(
+            retargeted,
+            args(&[
+                "-i",
+                "in.mkv",
+                "-progress",
+                "pipe:2",
+                "-c:a",
+                "aac",
+                "-f",
+                "mp4",
+                "-movflags",
+                FRAGMENTED_MOVFLAGS,
+                "pipe:1",
+            ])
+        );
+
+        // Other muxers stream as they are
+        let retargeted = retarget_for_pipe(args(&[
+            "-i",
+            "in.wav",
+            "-progress",
+            "pipe:1",
+            "-f",
+            "flac",
+            "pipe:1",
+        ]));
+        assert_eq!(
+            retargeted,
+            args(&[
+                "-i",
+                "in.wav",
+                "-progress",
+                "pipe:2",
+                "-f",
+                "flac",
+                "pipe:1"
+            ])
+        );
+    }
+
+    #[test]
+    fn test_is_progress_line() {
+        assert!(is_progress_line("out_time_us=1000000"));
+        assert!(is_progress_line("stream_0_0_q=23.0"));
+        assert!(is_progress_line("bitrate=N/A"));
+        assert!(is_progress_line("progress=end"));
+        assert!(!is_progress_line(
+            "frame=   12 fps=0.0 q=28.0 size=       0kB time=00:00:00.40"
+        ));
+        assert!(!is_progress_line("[aac @ 0x55d] Too many bits per frame"));
+        assert!(!is_progress_line(""));
+    }
+}
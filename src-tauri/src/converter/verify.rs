@@ -0,0 +1,293 @@
+//! Post-conversion checks on the finished output.
+
+use super::progress::LossyLines;
+use crate::error::{AppError, ErrorCode};
+use crate::media::{self, MediaInfo};
+use crate::types::ProcessPriority;
+use crate::utils::{create_async_priority_command, null_device};
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::Arc;
+use tauri::Emitter;
+use tokio::process::Child;
+use tokio::sync::Mutex;
+
+/// Decode errors beyond this are counted but not sent to the frontend.
+const MAX_REPORTED_ERRORS: usize = 20;
+/// Shortest output accepted, as a share of the expected duration.
+const MIN_DURATION_RATIO: f64 = 0.9;
+/// Expected durations below this aren't checked; container rounding dominates.
+const MIN_CHECKED_DURATION: f64 = 1.0;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PlaybackReport {
+    pub passed: bool,
+    pub error_count: usize,
+    /// First decode errors FFmpeg reported
+    pub errors: Vec<String>,
+}
+
+#[derive(Clone, Serialize)]
+struct PlaybackEvent<'a> {
+    task_id: &'a str,
+    #[serde(flatten)]
+    report: &'a PlaybackReport,
+}
+
+/// Probe the finished output to confirm FFmpeg really produced what its exit
+/// status claims: a non-empty file with the expected stream, not cut short.
+pub async fn check_output(
+    app: &tauri::AppHandle,
+    output: &str,
+    expected_duration: f64,
+    expect_video: bool,
+) -> Result<(), AppError> {
+    let size = tokio::fs::metadata(output)
+        .await
+        .map(|m| m.len())
+        .unwrap_or(0);
+    if size == 0 {
+        return Err(unverified("output file is empty or missing".to_string()));
+    }
+
+    let info = media::detect_media_type(app, output)
+        .await
+        .map_err(|e| unverified(format!("output can't be read: {}", e)))?;
+    check_probe(&info, expected_duration, expect_video).map_err(unverified)
+}
+
+fn check_probe(info: &MediaInfo, expected_duration: f64, expect_video: bool) -> Result<(), String> {
+    if expect_video && info.video_streams.is_empty() {
+        return Err("output has no video stream".to_string());
+    }
+    if !expect_video && info.audio_streams.is_empty() {
+        return Err("output has no audio stream".to_string());
+    }
+    if expected_duration >= MIN_CHECKED_DURATION
+        && info.duration < expected_duration * MIN_DURATION_RATIO
+    {
+        return Err(format!(
+            "output is {:.1}s long, expected {:.1}s",
+            info.duration, expected_duration
+        ));
+    }
+    Ok(())
+}
+
+fn unverified(details: String) -> AppError {
+    AppError::new(
+        ErrorCode::ConversionFailed,
+        "FFmpeg finished but the output is incomplete",
+    )
+    .with_details(details)
+}
+
+/// Decode the whole output as a player would, discarding the frames. Catches
+/// mid-file corruption that stream-level checks miss. The process is registered
+/// under `task_id` so cancelling the task stops it; returns `None` if cancelled.
+pub async fn decode_test(
+    ffmpeg_path: &Path,
+    output: &str,
+    task_id: &str,
+    priority: ProcessPriority,
+    processes: &Arc<Mutex<HashMap<String, Child>>>,
+) -> Result<Option<PlaybackReport>> {
+    let mut child = create_async_priority_command(ffmpeg_path.to_str().unwrap(), priority)
+        .args([
+            "-hide_banner",
+            "-v",
+            "error",
+            "-i",
+            output,
+            "-f",
+            "null",
+            null_device(),
+        ])
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("Failed to start playback test")?;
+    let stderr = child.stderr.take().expect("Failed to capture stderr");
+
+    processes.lock().await.insert(task_id.to_string(), child);
+    tracing::info!(task_id = %task_id, "Running playback test on output");
+
+    let mut errors = Vec::new();
+    let mut error_count = 0;
+    let mut reader = LossyLines::new(stderr);
+    while let Some(line) = reader.next_line().await {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        error_count += 1;
+        if errors.len() < MAX_REPORTED_ERRORS {
+            errors.push(line.to_string());
+        }
+    }
+
+    let Some(mut child) = processes.lock().await.remove(task_id) else {
+        return Ok(None);
+    };
+
+    let status = child.wait().await?;
+    if !status.success() && error_count == 0 {
+        error_count = 1;
+        errors.push(format!("Decoder exited with code: {}", status));
+    }
+
+    Ok(Some(PlaybackReport {
+        passed: error_count == 0,
+        error_count,
+        errors,
+    }))
+}
+
+/// Send the playback test result as `conversion-playback-test`, ahead of `conversion-completed`.
+pub fn emit_playback_report(window: &tauri::WebviewWindow, task_id: &str, report: &PlaybackReport) {
+    if report.passed {
+        tracing::info!(task_id = %task_id, "Playback test passed");
+    } else {
+        tracing::warn!(
+            task_id = %task_id,
+            errors = report.error_count,
+            first = ?report.errors.first(),
+            "Playback test found decode errors"
+        );
+    }
+
+    let _ = window.emit(
+        "conversion-playback-test",
+        PlaybackEvent { task_id, report },
+    );
+}
+
+/// Whether an MP4/MOV file has its index (`moov`) ahead of the media data, so
+/// players can start and seek before the whole file has arrived. `None` when the
+/// file isn't ISOBMFF or its atoms can't be read.
+pub async fn is_faststart(output: &str) -> Option<bool> {
+    let path = PathBuf::from(output);
+    tokio::task::spawn_blocking(move || {
+        let mut file = File::open(path).ok()?;
+        moov_before_mdat(&mut file)
+    })
+    .await
+    .ok()?
+}
+
+/// Warn that the output won't stream or seek until fully downloaded.
+pub fn emit_faststart_advisory(window: &tauri::WebviewWindow, task_id: &str) {
+    tracing::warn!(task_id = %task_id, "Output index is at the end of the file (no faststart)");
+
+    let _ = window.emit(
+        "conversion-advisory",
+        serde_json::json!({
+            "task_id": task_id,
+            "message": "The file's index is stored at the end, so it won't play or seek while streaming until fully downloaded. Remux it with faststart to fix.",
+        }),
+    );
+}
+
+/// Walk the top-level atoms and report which of `moov` / `mdat` comes first.
+fn moov_before_mdat<R: Read + Seek>(reader: &mut R) -> Option<bool> {
+    let mut first = true;
+
+    loop {
+        let mut header = [0u8; 8];
+        reader.read_exact(&mut header).ok()?;
+        let size = u32::from_be_bytes(header[..4].try_into().ok()?) as u64;
+        let kind = &header[4..];
+
+        // Every ISOBMFF file opens with `ftyp`
+        if first && kind != b"ftyp" {
+            return None;
+        }
+        first = false;
+
+        match kind {
+            b"moov" => return Some(true),
+            b"mdat" => return Some(false),
+            _ => {}
+        }
+
+        let remaining = match size {
+            // Atom runs to the end of the file
+            0 => return None,
+            1 => {
+                let mut large = [0u8; 8];
+                reader.read_exact(&mut large).ok()?;
+                u64::from_be_bytes(large).checked_sub(16)?
+            }
+            n => n.checked_sub(8)?,
+        };
+        reader.seek(SeekFrom::Current(remaining as i64)).ok()?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn atom(kind: &[u8; 4], payload: usize) -> Vec<u8> {
+        let mut bytes = ((payload + 8) as u32).to_be_bytes().to_vec();
+        bytes.extend_from_slice(kind);
+        bytes.resize(payload + 8, 0);
+        bytes
+    }
+
+    #[test]
+    fn test_check_probe() {
+        let mut info = MediaInfo {
+            media_type: media::MediaType::Audio,
+            duration: 9.5,
+            file_size: 1,
+            format_name: "mp3".to_string(),
+            bitrate: None,
+            video_streams: Vec::new(),
+            audio_streams: vec![media::AudioStream {
+                codec: "mp3".to_string(),
+                sample_rate: 44100,
+                channels: 2,
+                bitrate: None,
+                language: None,
+                title: None,
+            }],
+            subtitle_streams: Vec::new(),
+            attachments: Vec::new(),
+            tags: None,
+            chapter_count: 0,
+            mismatch_warning: None,
+        };
+        assert!(check_probe(&info, 10.0, false).is_ok());
+        assert!(check_probe(&info, 10.0, true).is_err());
+
+        // Truncated to under 90% of the expected length
+        info.duration = 4.0;
+        assert!(check_probe(&info, 10.0, false).is_err());
+        assert!(check_probe(&info, 0.5, false).is_ok());
+    }
+
+    #[test]
+    fn test_moov_before_mdat() {
+        let faststart = [atom(b"ftyp", 12), atom(b"moov", 40), atom(b"mdat", 100)].concat();
+        assert_eq!(moov_before_mdat(&mut Cursor::new(faststart)), Some(true));
+
+        let trailing = [
+            atom(b"ftyp", 12),
+            atom(b"free", 0),
+            atom(b"mdat", 100),
+            atom(b"moov", 40),
+        ]
+        .concat();
+        assert_eq!(moov_before_mdat(&mut Cursor::new(trailing)), Some(false));
+
+        let matroska = [0x1A, 0x45, 0xDF, 0xA3, 0, 0, 0, 0].to_vec();
+        assert_eq!(moov_before_mdat(&mut Cursor::new(matroska)), None);
+    }
+}
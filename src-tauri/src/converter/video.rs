@@ -1,40 +1,247 @@
 use super::builder::FfmpegBuilder;
-use super::spawn_ffmpeg;
+use super::disk;
+use super::gpu_throttle;
+use super::{
+    check_extra_args, check_fades, emit_phase, ensure_decodable, filters, image,
+    match_output_extension, measure_loudness, resolve_output_path, select_audio_track,
+    spawn_ffmpeg, spawn_ffmpeg_passes, ConversionPhase, RunOptions,
+};
 use crate::codec_map;
 use crate::codec_registry;
+use crate::error::{AppError, ErrorCode};
 use crate::formats::video::{self, VideoFormat};
 use crate::gpu::GpuInfo;
 use crate::media::{self, MediaInfo};
-use crate::types::{ConversionSettings, Quality};
+use crate::temp_files;
+use crate::types::{ConversionSettings, Quality, RateControl, TvStandard};
 use crate::utils;
 use anyhow::{Context, Result};
 use std::collections::HashMap;
+use std::path::PathBuf;
 use std::sync::Arc;
 use tauri::{Emitter, Manager};
 use tokio::process::Child;
 use tokio::sync::Mutex;
 
+/// FFV1 slice count when none is requested: good parallelism up to 4K.
+const DEFAULT_FFV1_SLICES: u32 = 16;
+
 pub async fn convert(
     window: tauri::WebviewWindow,
     input: &str,
     output: &str,
     format: &str,
     gpu_info: GpuInfo,
-    settings: ConversionSettings,
+    mut settings: ConversionSettings,
     processes: Arc<Mutex<HashMap<String, Child>>>,
 ) -> Result<String> {
-    // Pre-flight validation: ensure input file still exists
+    // Pre-flight validation: input still there, output folder writable
     utils::validate_input_path(input)?;
+    utils::validate_output_path(output)?;
+    check_extra_args(&settings)?;
+    settings.resolve_legacy_bitrate(false);
+    if let Some(subtitles) = &settings.burn_subtitle_path {
+        utils::validate_input_path(subtitles)?;
+    }
 
     let task_id = settings.task_id();
     let fmt = video::get_format(format).context("Unknown video format")?;
-    let media = media::detect_media_type(window.app_handle(), input).await?;
+    let output = &match_output_extension(
+        &window,
+        &task_id,
+        output,
+        &fmt.extension,
+        Some(&fmt.container),
+    );
+    let output = &resolve_output_path(&window, &task_id, output, settings.on_collision)?;
+    emit_phase(&window, &task_id, ConversionPhase::Probing);
+    let mut media = media::detect_media_type(window.app_handle(), input).await?;
+    select_audio_track(&mut media, &settings)?;
+    if !settings.keep_orientation {
+        settings.source_rotation = media.primary_video().and_then(|v| v.rotation);
+    }
+    let required = if fmt.still_image {
+        disk::estimate_still_size(&media, &settings)
+    } else {
+        disk::estimate_output_size(&media, &settings, true)
+    };
+    disk::ensure_free_space(output, required)?;
+
+    if settings.tonemap_sdr && !media.primary_video().is_some_and(|v| v.is_hdr()) {
+        tracing::info!(task_id = %task_id, "Source isn't HDR, skipping tone mapping");
+        settings.tonemap_sdr = false;
+    }
+
+    // A percentage or preset becomes a plain size once the source is known
+    if let Some((width, height)) =
+        frame_size(&media, &settings).and_then(|source| settings.relative_size(source))
+    {
+        settings.width = Some(width);
+        settings.height = Some(height);
+    }
+
+    if settings.rate_control == RateControl::Cbr && settings.video_bitrate.is_none() {
+        return Err(AppError::new(
+            ErrorCode::ValidationFailed,
+            "Constant bitrate needs a target bitrate",
+        )
+        .into());
+    }
+
+    if let (Some((width, height, x, y)), Some(video)) = (settings.crop, media.primary_video()) {
+        if x.saturating_add(width) > video.width || y.saturating_add(height) > video.height {
+            return Err(AppError::new(
+                ErrorCode::ValidationFailed,
+                format!(
+                    "Crop region {}x{} at ({}, {}) extends past the {}x{} source",
+                    width, height, x, y, video.width, video.height
+                ),
+            )
+            .into());
+        }
+    }
+
+    // ========== Remux-only path ==========
+    // Keeping every stream is a remux too, one that maps all of them
+    if settings.remux_only || settings.keep_all_streams {
+        let mut reasons = fmt.remux_incompatibilities(
+            media.primary_video().map(|v| v.codec.as_str()),
+            media.audio_codec(),
+        );
+        reasons.extend(video_reencode_reasons(&settings));
+        if settings.keep_all_streams {
+            reasons.extend(passthrough_incompatibilities(&fmt, &media));
+        }
+        if !reasons.is_empty() {
+            return Err(AppError::new(
+                ErrorCode::UnsupportedFormat,
+                format!("Cannot remux into {} without re-encoding", fmt.extension),
+            )
+            .with_details(reasons.join("; "))
+            .into());
+        }
+
+        tracing::info!(task_id = %task_id, container = %fmt.container, "Remuxing without re-encoding");
+
+        let builder = FfmpegBuilder::new(input, output)
+            .hide_banner()
+            .on_collision(settings.on_collision)
+            .extra_args(&settings.extra_args)
+            .input_file()
+            .progress_pipe()
+            .metadata(&settings.metadata, remux_preserve_metadata(&settings))
+            .arg("-c", "copy");
+        let builder = if settings.keep_all_streams {
+            builder.map("0")
+        } else {
+            match settings.audio_track_index {
+                Some(_) => map_audio_track(builder, &settings),
+                None => builder,
+            }
+        };
+
+        let (args, output_path) = apply_container_settings(builder, &fmt, &settings).build();
+
+        return spawn_ffmpeg(
+            window,
+            task_id,
+            media.duration,
+            args,
+            output_path,
+            RunOptions::from_settings(&settings)
+                .with_frame_rate(media.primary_video().map(|v| v.fps))
+                .expect_video(media.primary_video().is_some()),
+            processes,
+        )
+        .await;
+    }
+
+    // ========== Audio-only re-encode path ==========
+    if settings.replace_audio_only {
+        let mut reasons =
+            fmt.remux_incompatibilities(media.primary_video().map(|v| v.codec.as_str()), None);
+        reasons.extend(video_reencode_reasons(&settings));
+        if media.audio_streams.is_empty() {
+            reasons.push("source has no audio stream".to_string());
+        } else if fmt.audio_codecs.is_empty() {
+            reasons.push(format!("{} cannot carry audio", fmt.extension));
+        }
+        if !reasons.is_empty() {
+            return Err(AppError::new(
+                ErrorCode::UnsupportedFormat,
+                format!(
+                    "Cannot copy the video into {} and re-encode only the audio",
+                    fmt.extension
+                ),
+            )
+            .with_details(reasons.join("; "))
+            .into());
+        }
+
+        tracing::info!(task_id = %task_id, "Copying video, re-encoding audio only");
+        ensure_decodable(media.audio_codec())?;
+        if !measure_loudness(&window, &task_id, input, &mut settings, &processes).await {
+            return Ok(task_id);
+        }
+
+        let mut builder = FfmpegBuilder::new(input, output)
+            .hide_banner()
+            .on_collision(settings.on_collision)
+            .extra_args(&settings.extra_args)
+            .input_file()
+            .progress_pipe()
+            .metadata(&settings.metadata, settings.preserve_metadata)
+            .video_codec("copy");
+
+        builder = apply_audio_settings(builder, &fmt, &media, &settings);
+        builder = apply_subtitle_settings(builder, &fmt, &media, &settings);
+        builder = apply_container_settings(builder, &fmt, &settings);
+
+        let (args, output_path) = builder.build();
+
+        return spawn_ffmpeg(
+            window,
+            task_id,
+            media.duration,
+            args,
+            output_path,
+            RunOptions::from_settings(&settings)
+                .with_frame_rate(media.primary_video().map(|v| v.fps))
+                .expect_video(true),
+            processes,
+        )
+        .await;
+    }
+
+    check_fades(&settings, settings.output_duration(media.duration))?;
 
     // ========== GIF special path ==========
     if format == "gif" {
+        ensure_decodable(media.primary_video().map(|v| v.codec.as_str()))?;
         return convert_to_gif(window, input, output, &media, &settings, task_id, processes).await;
     }
 
+    // ========== Still image path ==========
+    if fmt.still_image {
+        ensure_decodable(media.primary_video().map(|v| v.codec.as_str()))?;
+        return image::convert(
+            window, input, output, &fmt, &media, &settings, task_id, processes,
+        )
+        .await;
+    }
+
+    // ========== WebP special path ==========
+    if format == "webp" {
+        ensure_decodable(media.primary_video().map(|v| v.codec.as_str()))?;
+        return convert_to_webp(window, input, output, &media, &settings, task_id, processes).await;
+    }
+
+    if !media.audio_streams.is_empty()
+        && !measure_loudness(&window, &task_id, input, &mut settings, &processes).await
+    {
+        return Ok(task_id);
+    }
+
     // ========== Stream copy fast path ==========
     if can_copy_video_stream(&media, &fmt, &settings) {
         tracing::info!(
@@ -52,29 +259,36 @@ pub async fn convert(
 
         let mut builder = FfmpegBuilder::new(input, output)
             .hide_banner()
-            .overwrite()
+            .on_collision(settings.on_collision)
+            .extra_args(&settings.extra_args)
             .input_file()
             .progress_pipe()
-            .metadata(&settings.metadata)
+            .metadata(&settings.metadata, settings.preserve_metadata)
             .video_codec("copy");
 
         builder = apply_audio_settings(builder, &fmt, &media, &settings);
-        builder = apply_container_settings(builder, &fmt);
+        builder = apply_subtitle_settings(builder, &fmt, &media, &settings);
+        builder = apply_container_settings(builder, &fmt, &settings);
 
         let (args, output_path) = builder.build();
 
         return spawn_ffmpeg(
             window,
             task_id,
-            media.duration,
+            settings.output_duration(media.duration),
             args,
             output_path,
+            RunOptions::from_settings(&settings)
+                .with_frame_rate(output_frame_rate(&media, &settings))
+                .expect_video(media.primary_video().is_some()),
             processes,
         )
         .await;
     }
 
     // ========== Normal conversion path ==========
+    ensure_decodable(media.primary_video().map(|v| v.codec.as_str()))?;
+
     let use_gpu = should_use_gpu(&gpu_info, &settings, &fmt);
     let mut video_codec = determine_video_codec(&fmt, &gpu_info, use_gpu, &settings);
 
@@ -143,124 +357,94 @@ pub async fn convert(
         }
     }
 
-    let mut builder = FfmpegBuilder::new(input, output)
-        .hide_banner()
-        .overwrite()
-        .input_file()
-        .progress_pipe()
-        .metadata(&settings.metadata)
-        .video_codec(&video_codec)
-        .apply_video_codec_preset(&video_codec, settings.quality);
-
-    // Auto-bitrate for codecs that need explicit bitrate (AMF)
-    if settings.bitrate.is_none() && video_codec.contains("amf") {
-        let width = settings
-            .width
-            .unwrap_or_else(|| media.primary_video().map(|v| v.width).unwrap_or(1920));
-        let height = settings
-            .height
-            .unwrap_or_else(|| media.primary_video().map(|v| v.height).unwrap_or(1080));
-        let fps = settings.fps.unwrap_or_else(|| {
-            media
-                .primary_video()
-                .map(|v| v.fps.round() as u32)
-                .unwrap_or(30)
-        });
-
-        let target_bitrate =
-            calculate_auto_bitrate(width, height, fps, settings.quality, &video_codec);
-        builder = builder
-            .arg("-b:v", &format!("{}k", target_bitrate))
-            .arg(
-                "-maxrate",
-                &format!("{}k", (target_bitrate as f64 * 1.5) as u32),
-            )
-            .arg("-bufsize", &format!("{}k", target_bitrate * 2));
-    } else if let Some(br) = settings.bitrate {
-        builder = builder.arg("-b:v", &format!("{}k", br));
-    }
-
-    builder = apply_resolution(builder, &fmt, &media, &settings);
+    settings.hwaccel_args = if settings.gpu_decode() {
+        gpu_info.hwaccel_args(&video_codec)
+    } else {
+        gpu_info.hw_device_args(&video_codec)
+    };
 
-    if let Some(fps) = settings.fps {
-        builder = builder.fps(fps);
-    }
+    // Hardware encoders have a limited number of concurrent sessions — wait for a slot
+    let gpu_slot = if codec_map::is_gpu_encoder(&video_codec) {
+        Some(gpu_throttle::acquire(&window, &task_id).await)
+    } else {
+        None
+    };
 
-    // Pixel format
-    if video_codec.contains("amf") {
-        builder = builder.pixel_format("nv12");
-    } else if let Some(pix_fmt) = &fmt.default_pixel_format {
-        builder = builder.pixel_format(pix_fmt);
+    let job = build_encode_job(
+        input,
+        output,
+        &video_codec,
+        &fmt,
+        &media,
+        &settings,
+        &task_id,
+    )?;
+
+    if job.frames_downloaded {
+        emit_frame_download_fallback(&window, &task_id);
     }
 
-    builder = apply_audio_settings(builder, &fmt, &media, &settings);
-    builder = apply_container_settings(builder, &fmt);
-
-    let (args, output_path) = builder.build();
-
-    // Try conversion, with automatic GPU→software fallback on failure
-    match spawn_ffmpeg(
+    // Try conversion; if the hardware encoder can't start (no driver, session
+    // limit, out of VRAM), retry once on the CPU
+    let cpu_fallback = if codec_map::is_gpu_encoder(&video_codec) {
+        codec_map::software_fallback_for_encoder(&video_codec)
+            .map(str::to_string)
+            .or_else(|| fmt.get_software_codec())
+    } else {
+        None
+    };
+    match spawn_ffmpeg_passes(
         window.clone(),
         task_id.clone(),
-        media.duration,
-        args,
-        output_path.clone(),
+        settings.output_duration(media.duration),
+        job.passes,
+        job.output_path,
+        RunOptions::from_settings(&settings)
+            .with_temp_dir(job.temp_dir)
+            .with_frame_rate(output_frame_rate(&media, &settings))
+            .expect_video(media.primary_video().is_some())
+            .with_gpu_fallback(cpu_fallback.is_some()),
         processes.clone(),
     )
     .await
     {
         Ok(result) => Ok(result),
-        Err(e) if codec_map::software_fallback_for_encoder(&video_codec).is_some() => {
-            let sw_codec = codec_map::software_fallback_for_encoder(&video_codec).unwrap();
+        Err(e) if cpu_fallback.is_some() && is_gpu_encoder_failure(&e) => {
+            let sw_codec = cpu_fallback.unwrap();
+
+            // The software retry doesn't need the GPU — let queued GPU jobs proceed
+            drop(gpu_slot);
 
             tracing::warn!(
                 encoder = %video_codec,
                 fallback = %sw_codec,
                 error = %e,
-                "GPU encoder failed at runtime, retrying with software fallback"
+                "GPU encoder failed to start, retrying on the CPU"
             );
 
             let _ = window.emit(
-                "conversion-fallback",
+                "fell-back-to-cpu",
                 serde_json::json!({
                     "task_id": &task_id,
                     "from": &video_codec,
                     "to": &sw_codec,
-                    "reason": format!("Runtime error: {}", e),
+                    "reason": e.downcast_ref::<AppError>().and_then(|err| err.details.clone()),
                 }),
             );
 
-            let mut retry = FfmpegBuilder::new(input, output)
-                .hide_banner()
-                .overwrite()
-                .input_file()
-                .progress_pipe()
-                .metadata(&settings.metadata)
-                .video_codec(sw_codec)
-                .apply_video_codec_preset(sw_codec, settings.quality);
-
-            if let Some(br) = settings.bitrate {
-                retry = retry.arg("-b:v", &format!("{}k", br));
-            }
+            let retry =
+                build_encode_job(input, output, &sw_codec, &fmt, &media, &settings, &task_id)?;
 
-            retry = apply_resolution(retry, &fmt, &media, &settings);
-            if let Some(fps) = settings.fps {
-                retry = retry.fps(fps);
-            }
-            if let Some(pix_fmt) = &fmt.default_pixel_format {
-                retry = retry.pixel_format(pix_fmt);
-            }
-            retry = apply_audio_settings(retry, &fmt, &media, &settings);
-            retry = apply_container_settings(retry, &fmt);
-
-            let (retry_args, retry_output) = retry.build();
-
-            spawn_ffmpeg(
+            spawn_ffmpeg_passes(
                 window,
                 task_id,
-                media.duration,
-                retry_args,
-                retry_output,
+                settings.output_duration(media.duration),
+                retry.passes,
+                retry.output_path,
+                RunOptions::from_settings(&settings)
+                    .with_temp_dir(retry.temp_dir)
+                    .with_frame_rate(output_frame_rate(&media, &settings))
+                    .expect_video(media.primary_video().is_some()),
                 processes,
             )
             .await
@@ -269,6 +453,29 @@ pub async fn convert(
     }
 }
 
+fn is_gpu_encoder_failure(error: &anyhow::Error) -> bool {
+    error
+        .downcast_ref::<AppError>()
+        .is_some_and(|e| e.code == ErrorCode::GpuEncoderFailed)
+}
+
+fn emit_frame_download_fallback(window: &tauri::WebviewWindow, task_id: &str) {
+    tracing::warn!(
+        task_id = %task_id,
+        "Video filters need frames in system memory, copying back from the GPU decoder"
+    );
+
+    let _ = window.emit(
+        "conversion-fallback",
+        serde_json::json!({
+            "task_id": task_id,
+            "from": "GPU frames",
+            "to": "system memory frames",
+            "reason": "Cropping, rotation, denoising, sharpening, subtitles and scaling can't be applied to frames in GPU memory",
+        }),
+    );
+}
+
 // ============ GIF conversion ============
 
 async fn convert_to_gif(
@@ -280,25 +487,20 @@ async fn convert_to_gif(
     task_id: String,
     processes: Arc<Mutex<HashMap<String, Child>>>,
 ) -> Result<String> {
-    let gif_fps = settings.fps.unwrap_or(15).min(20);
-
-    let scale_part = match (settings.width, settings.height) {
-        (Some(w), Some(h)) => format!("scale={}:{}:flags=lanczos", w, h),
-        (Some(w), None) => format!("scale={}:-2:flags=lanczos", w),
-        (None, Some(h)) => format!("scale=-2:{}:flags=lanczos", h),
-        (None, None) => {
-            let source_width = media.primary_video().map(|v| v.width).unwrap_or(640);
-            if source_width > 480 {
-                "scale=480:-2:flags=lanczos".to_string()
-            } else {
-                format!("scale={}:-2:flags=lanczos", source_width)
-            }
-        }
-    };
+    let gif_fps = filters::gif_fps(settings);
+    if settings.fps.is_some_and(|fps| fps != gif_fps) {
+        tracing::warn!(
+            task_id = %task_id,
+            requested = settings.fps,
+            fps = gif_fps,
+            "GIF frame rate out of range, clamped"
+        );
+    }
 
-    let filter_complex = format!(
-        "[0:v]fps={},{},split[s0][s1];[s0]palettegen=max_colors=256:stats_mode=diff[p];[s1][p]paletteuse=dither=sierra2_4a",
-        gif_fps, scale_part
+    let filter_complex = filters::gif_filter_complex(
+        settings,
+        frame_size(media, settings).map(|(w, _)| w),
+        settings.output_duration(media.duration),
     );
 
     tracing::info!(
@@ -309,12 +511,14 @@ async fn convert_to_gif(
 
     let builder = FfmpegBuilder::new(input, output)
         .hide_banner()
-        .overwrite()
+        .on_collision(settings.on_collision)
+        .extra_args(&settings.extra_args)
         .input_file()
-        .progress_pipe()
+        .progress_pipe();
+    let builder = apply_source_orientation(builder, media, settings)
         .filter_complex(&filter_complex)
         .disable_audio()
-        .arg("-loop", "0")
+        .loop_count("gif", settings.loop_count.unwrap_or(0))
         .format("gif");
 
     let (args, output_path) = builder.build();
@@ -322,14 +526,110 @@ async fn convert_to_gif(
     spawn_ffmpeg(
         window,
         task_id,
-        media.duration,
+        settings.output_duration(media.duration),
+        args,
+        output_path,
+        RunOptions::from_settings(settings)
+            .with_frame_rate(Some(gif_fps as f64))
+            .expect_video(true),
+        processes,
+    )
+    .await
+}
+
+// ============ WebP conversion ============
+
+async fn convert_to_webp(
+    window: tauri::WebviewWindow,
+    input: &str,
+    output: &str,
+    media: &MediaInfo,
+    settings: &ConversionSettings,
+    task_id: String,
+    processes: Arc<Mutex<HashMap<String, Child>>>,
+) -> Result<String> {
+    let animated = !is_still_image(media);
+    let encoder = webp_encoder(animated).ok_or_else(|| {
+        AppError::new(
+            ErrorCode::UnsupportedFormat,
+            "This FFmpeg build has no WebP encoder (libwebp)",
+        )
+    })?;
+    let duration = settings.output_duration(media.duration);
+    let frame_rate = animated.then(|| filters::webp_fps(settings));
+
+    tracing::info!(task_id = %task_id, encoder, animated, fps = frame_rate, "Converting to WebP");
+
+    let mut builder = FfmpegBuilder::new(input, output)
+        .hide_banner()
+        .on_collision(settings.on_collision)
+        .extra_args(&settings.extra_args)
+        .input_file()
+        .progress_pipe();
+    builder = apply_source_orientation(builder, media, settings)
+        .disable_audio()
+        .video_codec(encoder)
+        .arg("-quality", settings.quality.webp_quality());
+    for filter in filters::webp_filters(
+        settings,
+        frame_size(media, settings).map(|(w, _)| w),
+        duration,
+        animated,
+    ) {
+        builder = builder.video_filter(&filter);
+    }
+    builder = if animated {
+        builder.loop_count("webp", settings.loop_count.unwrap_or(0))
+    } else {
+        builder.arg("-frames:v", "1")
+    };
+
+    let (args, output_path) = builder.format("webp").build();
+
+    // ffprobe doesn't report the length of animated WebP, so only the stream is checked
+    let options = RunOptions {
+        check_duration: false,
+        ..RunOptions::from_settings(settings)
+    };
+    spawn_ffmpeg(
+        window,
+        task_id,
+        duration,
         args,
         output_path,
+        options
+            .with_frame_rate(frame_rate.map(f64::from))
+            .expect_video(true),
         processes,
     )
     .await
 }
 
+/// Single-frame image input (PNG, JPEG, ...) rather than a clip.
+fn is_still_image(media: &MediaInfo) -> bool {
+    media.audio_streams.is_empty()
+        && (media.duration <= 0.0
+            || media.format_name == "image2"
+            || media.format_name.ends_with("_pipe"))
+}
+
+/// WebP encoder for the output. `libwebp` writes animations too, through the
+/// muxer, so it is the fallback when `libwebp_anim` is missing.
+fn webp_encoder(animated: bool) -> Option<&'static str> {
+    let candidates: &[&'static str] = if animated {
+        &["libwebp_anim", "libwebp"]
+    } else {
+        &["libwebp"]
+    };
+    if !codec_registry::is_initialized() {
+        return candidates.first().copied();
+    }
+    candidates
+        .iter()
+        .copied()
+        .find(|c| codec_registry::is_encoder_available(c))
+}
+
 // ============ Stream copy detection ============
 
 fn can_copy_video_stream(
@@ -354,6 +654,37 @@ fn can_copy_video_stream(
         return false;
     }
 
+    if settings.b_frames.is_some() || settings.ref_frames.is_some() {
+        return false;
+    }
+
+    if settings.keyframe_interval.is_some() || settings.force_keyframes_every().is_some() {
+        return false;
+    }
+
+    if settings.pixel_format.is_some() {
+        return false;
+    }
+
+    // Only MP4/MOV reliably carry the rotation tag, elsewhere a copied
+    // phone clip plays sideways
+    if settings.source_rotation.is_some() && !fmt.is_isobmff() {
+        return false;
+    }
+
+    if settings.burn_subtitle_path.is_some()
+        || settings.speed().is_some()
+        || settings.deinterlace.is_some()
+        || settings.tonemap_sdr
+        || settings.crop.is_some()
+        || settings.has_orientation_change()
+        || settings.has_fades()
+        || settings.has_cleanup_filters()
+        || !settings.color_adjustments().is_empty()
+    {
+        return false;
+    }
+
     if fmt.requires_fixed_resolution {
         return false;
     }
@@ -369,6 +700,140 @@ fn can_copy_video_stream(
 
 // ============ Helpers ============
 
+/// Streams besides the main video and audio that `fmt` can't take as-is,
+/// when every stream of the source is to be kept.
+fn passthrough_incompatibilities(fmt: &VideoFormat, media: &MediaInfo) -> Vec<String> {
+    let mut reasons = Vec::new();
+
+    for (index, audio) in media.audio_streams.iter().enumerate().skip(1) {
+        if !fmt.supports_audio_codec(&audio.codec) {
+            reasons.push(format!(
+                "audio track {} ({}) is not supported by {}",
+                index + 1,
+                audio.codec,
+                fmt.extension
+            ));
+        }
+    }
+
+    match fmt.subtitle_codec() {
+        _ if media.subtitle_streams.is_empty() => {}
+        None => reasons.push(format!("{} cannot carry subtitles", fmt.extension)),
+        Some(("copy", _)) => {}
+        Some((codec, _)) => {
+            for (index, subtitle) in media.subtitle_streams.iter().enumerate() {
+                if subtitle.codec != codec {
+                    reasons.push(format!(
+                        "subtitle track {} ({}) would have to be converted to {}",
+                        index + 1,
+                        subtitle.codec,
+                        codec
+                    ));
+                }
+            }
+        }
+    }
+
+    // Only Matroska stores attached files
+    if !media.attachments.is_empty() && fmt.container != "matroska" {
+        reasons.push(format!(
+            "{} cannot carry the {} attachment(s) (fonts)",
+            fmt.extension,
+            media.attachments.len()
+        ));
+    }
+
+    reasons
+}
+
+/// Settings that change the picture, so the video stream can't be copied.
+fn video_reencode_reasons(settings: &ConversionSettings) -> Vec<String> {
+    let mut reasons = Vec::new();
+    if settings.burn_subtitle_path.is_some() {
+        reasons.push("burning in subtitles requires re-encoding".to_string());
+    }
+    if settings.speed().is_some() {
+        reasons.push("changing playback speed requires re-encoding".to_string());
+    }
+    if settings.deinterlace.is_some() {
+        reasons.push("deinterlacing requires re-encoding".to_string());
+    }
+    if settings.tonemap_sdr {
+        reasons.push("tone mapping requires re-encoding".to_string());
+    }
+    if settings.crop.is_some() {
+        reasons.push("cropping requires re-encoding".to_string());
+    }
+    if settings.has_orientation_change() {
+        reasons.push("rotating or flipping requires re-encoding".to_string());
+    }
+    if settings.has_fades() {
+        reasons.push("fading in or out requires re-encoding".to_string());
+    }
+    if settings.has_cleanup_filters() {
+        reasons.push("denoising or sharpening requires re-encoding".to_string());
+    }
+    if !settings.color_adjustments().is_empty() {
+        reasons.push("colour adjustments require re-encoding".to_string());
+    }
+    reasons
+}
+
+/// Frame rate of the encoded output: the requested one, otherwise the source's.
+fn output_frame_rate(media: &MediaInfo, settings: &ConversionSettings) -> Option<f64> {
+    settings
+        .fps
+        .map(f64::from)
+        .or_else(|| media.primary_video().map(|v| v.fps))
+        .filter(|fps| *fps > 0.0)
+}
+
+/// Size of the picture reaching the scaler: the crop region if one is set,
+/// otherwise the source frame as decoded (upright unless `keep_orientation`),
+/// swapped for requested quarter-turn rotations.
+pub(super) fn frame_size(media: &MediaInfo, settings: &ConversionSettings) -> Option<(u32, u32)> {
+    let decoded = media
+        .primary_video()
+        .map(|v| match settings.source_rotation {
+            Some(90 | 270) => (v.height, v.width),
+            _ => (v.width, v.height),
+        });
+    let (width, height) = settings
+        .crop
+        .map(|(width, height, _, _)| (width, height))
+        .or(decoded)?;
+
+    match settings.rotation() {
+        Some(90 | 270) => Some((height, width)),
+        _ => Some((width, height)),
+    }
+}
+
+/// Metadata policy of a remux. Copying into a new container keeps the tags
+/// when every stream is kept, and otherwise follows the app-wide default
+/// unless the settings say otherwise.
+pub(super) fn remux_preserve_metadata(settings: &ConversionSettings) -> Option<bool> {
+    settings
+        .preserve_metadata
+        .or(settings.keep_all_streams.then_some(true))
+}
+
+/// FFmpeg's auto-rotation turns a rotation-tagged source upright on decode
+/// and drops the tag from the re-encoded stream; only `keep_orientation`
+/// switches it off, keeping the stored picture and its tag.
+pub(super) fn apply_source_orientation(
+    builder: FfmpegBuilder,
+    media: &MediaInfo,
+    settings: &ConversionSettings,
+) -> FfmpegBuilder {
+    let tagged = media.primary_video().is_some_and(|v| v.rotation.is_some());
+    if tagged && settings.keep_orientation {
+        builder.no_autorotate()
+    } else {
+        builder
+    }
+}
+
 fn find_available_encoder(fmt: &VideoFormat) -> Option<String> {
     for codec_type in &fmt.video_codecs {
         let sw_name = codec_map::software_encoder_for_codec(codec_type).unwrap_or(codec_type);
@@ -379,7 +844,13 @@ fn find_available_encoder(fmt: &VideoFormat) -> Option<String> {
     None
 }
 
-fn calculate_auto_bitrate(width: u32, height: u32, fps: u32, quality: Quality, codec: &str) -> u32 {
+pub(super) fn calculate_auto_bitrate(
+    width: u32,
+    height: u32,
+    fps: u32,
+    quality: Quality,
+    codec: &str,
+) -> u32 {
     let pixels = width as f64 * height as f64;
 
     let base_bpp = match quality {
@@ -404,7 +875,7 @@ fn calculate_auto_bitrate(width: u32, height: u32, fps: u32, quality: Quality, c
 }
 
 fn should_use_gpu(gpu: &GpuInfo, settings: &ConversionSettings, fmt: &VideoFormat) -> bool {
-    gpu.available && settings.use_gpu && !fmt.requires_fixed_resolution
+    gpu.available && settings.gpu_encode() && !fmt.requires_fixed_resolution
 }
 
 fn determine_video_codec(
@@ -421,6 +892,322 @@ fn determine_video_codec(
         .unwrap_or_else(|| "libx264".to_string())
 }
 
+/// Fully prepared FFmpeg invocation(s) for one video encode.
+struct EncodeJob {
+    passes: Vec<Vec<String>>,
+    output_path: String,
+    /// Scratch directory (pass logs) removed once the task ends
+    temp_dir: Option<PathBuf>,
+    /// Decoded frames had to leave GPU memory because a filter runs on the CPU
+    frames_downloaded: bool,
+}
+
+/// Build the FFmpeg arguments for encoding with `video_codec`: a single run
+/// normally, or an analysis pass plus the real encode when two-pass is requested.
+fn build_encode_job(
+    input: &str,
+    output: &str,
+    video_codec: &str,
+    fmt: &VideoFormat,
+    media: &MediaInfo,
+    settings: &ConversionSettings,
+    task_id: &str,
+) -> Result<EncodeJob> {
+    let two_pass = settings.two_pass && settings.video_bitrate.is_some();
+    if two_pass && !codec_map::supports_two_pass(video_codec) {
+        tracing::info!(
+            encoder = %video_codec,
+            "Two-pass requested but not supported by encoder, using single pass"
+        );
+    }
+
+    let mut builder = FfmpegBuilder::new(input, output)
+        .hide_banner()
+        .on_collision(settings.on_collision)
+        .extra_args(&settings.extra_args)
+        .input_file()
+        .progress_pipe()
+        .metadata(&settings.metadata, settings.preserve_metadata);
+
+    builder = apply_video_encoding(builder, video_codec, fmt, media, settings);
+
+    let frames_downloaded = settings
+        .hwaccel_args
+        .iter()
+        .any(|a| a == "-hwaccel_output_format")
+        && !builder.has_hw_frames_output();
+
+    if !two_pass || !codec_map::supports_two_pass(video_codec) {
+        builder = apply_audio_settings(builder, fmt, media, settings);
+        builder = apply_subtitle_settings(builder, fmt, media, settings);
+        builder = apply_container_settings(builder, fmt, settings);
+
+        let (args, output_path) = builder.build();
+        return Ok(EncodeJob {
+            passes: vec![args],
+            output_path,
+            temp_dir: None,
+            frames_downloaded,
+        });
+    }
+
+    let temp_dir = temp_files::task_dir(task_id).context("Failed to create pass log directory")?;
+    let log_prefix = temp_dir.join("passlog");
+
+    // Pass 1: analysis only — no audio, output discarded
+    let mut first = FfmpegBuilder::new(input, utils::null_device())
+        .hide_banner()
+        .overwrite()
+        .input_file()
+        .progress_pipe();
+    first = apply_video_encoding(first, video_codec, fmt, media, settings);
+    let (first_args, _) = without_crf(first, video_codec)
+        .two_pass(1, &log_prefix, video_codec)
+        .disable_audio()
+        .format("null")
+        .build();
+
+    // Pass 2: the real encode using the pass 1 statistics
+    builder = without_crf(builder, video_codec).two_pass(2, &log_prefix, video_codec);
+    builder = apply_audio_settings(builder, fmt, media, settings);
+    builder = apply_subtitle_settings(builder, fmt, media, settings);
+    builder = apply_container_settings(builder, fmt, settings);
+    let (second_args, output_path) = builder.build();
+
+    tracing::info!(task_id = %task_id, encoder = %video_codec, "Using two-pass encoding");
+
+    Ok(EncodeJob {
+        passes: vec![first_args, second_args],
+        output_path,
+        temp_dir: Some(temp_dir),
+        frames_downloaded,
+    })
+}
+
+/// x264/x265 presets add `-crf`, which would override the target bitrate
+/// (and libx264 rejects CRF combined with two-pass).
+fn without_crf(builder: FfmpegBuilder, video_codec: &str) -> FfmpegBuilder {
+    if video_codec.contains("libx264") || video_codec.contains("libx265") {
+        builder.remove_arg("-crf")
+    } else {
+        builder
+    }
+}
+
+/// Apply the video encoder, its quality preset and all stream-shaping options
+/// (bitrate, frame structure, resolution, fps, pixel format).
+/// Shared by the primary path and the GPU→software retry so both stay in sync.
+fn apply_video_encoding(
+    builder: FfmpegBuilder,
+    video_codec: &str,
+    fmt: &VideoFormat,
+    media: &MediaInfo,
+    settings: &ConversionSettings,
+) -> FfmpegBuilder {
+    let mut builder = builder;
+    if codec_map::is_gpu_encoder(video_codec) {
+        builder = builder.input_args(&settings.hwaccel_args);
+    }
+    builder = apply_source_orientation(builder, media, settings);
+
+    builder = builder.video_codec(video_codec).apply_video_codec_preset(
+        video_codec,
+        settings.quality,
+        settings.rate_control,
+        settings.video_bitrate,
+    );
+    if let Some(preset) = &settings.encoder_preset {
+        builder = builder.encoder_preset(video_codec, preset);
+    }
+    let bitrate_targeted =
+        settings.rate_control != RateControl::Crf && settings.video_bitrate.is_some();
+    if let Some(crf) = settings.crf {
+        if bitrate_targeted {
+            tracing::warn!(encoder = %video_codec, "Constant-quality value ignored, encoding to a bitrate target");
+        } else {
+            builder = builder.constant_quality(video_codec, crf);
+        }
+    }
+
+    // Auto-bitrate for codecs that need explicit bitrate (AMF), unless
+    // constant QP was opted into
+    let amf_unset =
+        settings.video_bitrate.is_none() && settings.crf.is_none() && video_codec.contains("amf");
+    if amf_unset && settings.amf_use_cqp {
+        builder = builder.amf_cqp(video_codec, settings.quality);
+    } else if amf_unset {
+        let (source_width, source_height) = frame_size(media, settings).unwrap_or((1920, 1080));
+        let width = settings.width.unwrap_or(source_width);
+        let height = settings.height.unwrap_or(source_height);
+        let fps = settings.fps.unwrap_or_else(|| {
+            media
+                .primary_video()
+                .map(|v| v.fps.round() as u32)
+                .unwrap_or(30)
+        });
+
+        let target_bitrate =
+            calculate_auto_bitrate(width, height, fps, settings.quality, video_codec);
+        builder = builder
+            .arg("-b:v", &format!("{}k", target_bitrate))
+            .arg(
+                "-maxrate",
+                &format!("{}k", (target_bitrate as f64 * 1.5) as u32),
+            )
+            .arg("-bufsize", &format!("{}k", target_bitrate * 2));
+    } else if let Some(br) = settings.video_bitrate.filter(|_| !bitrate_targeted) {
+        // VBR/CBR targets were already set with the preset
+        builder = builder.arg("-b:v", &format!("{}k", br));
+    }
+
+    builder = apply_frame_structure(builder, video_codec, media, settings);
+
+    if let Some(threads) = settings.threads() {
+        if codec_map::is_gpu_encoder(video_codec) {
+            tracing::debug!(encoder = %video_codec, "Thread count ignored for hardware encoder");
+        } else {
+            builder = builder.threads(video_codec, threads);
+        }
+    }
+
+    // Deinterlacing needs the untouched fields; crop offsets are in source
+    // pixels, so it comes next; the picture is then rotated as requested,
+    // denoised, colour-adjusted and subtitles laid out on it, all before any scaling
+    if let Some(deinterlacer) = settings.deinterlace {
+        builder = builder.deinterlace(deinterlacer);
+    } else if media.primary_video().is_some_and(|v| v.is_interlaced()) {
+        tracing::info!("Source is interlaced and deinterlacing is off, combing will remain");
+    }
+    if let Some((width, height, x, y)) = settings.crop {
+        builder = builder.crop(width, height, x, y);
+    }
+    if let Some(degrees) = settings.rotation() {
+        builder = builder.rotate(degrees);
+    }
+    if let Some(flip) = settings.flip {
+        builder = builder.flip(flip);
+    }
+    builder = builder.denoise(settings.denoise);
+    // Before the fades, so they still reach black
+    if let Some(eq) = filters::eq_filter(settings) {
+        builder = builder.video_filter(&eq);
+    }
+
+    if let Some(path) = &settings.burn_subtitle_path {
+        builder = builder.burn_subtitles(path);
+    }
+
+    if let Some(speed) = settings.speed() {
+        builder = builder.video_filter(&filters::speed_video_filter(speed));
+    }
+    for fade in filters::video_fades(settings, settings.output_duration(media.duration)) {
+        builder = builder.video_filter(&fade);
+    }
+
+    builder = apply_resolution(builder, fmt, media, settings);
+
+    // After scaling, so the float conversion runs on the smaller frame
+    if settings.tonemap_sdr {
+        builder = builder.tonemap_sdr();
+    } else if media.primary_video().is_some_and(|v| v.is_hdr()) {
+        tracing::info!("Source is HDR and tone mapping is off, colours may look washed out");
+    }
+    // Last, on the final picture: scaling and tone mapping both soften it
+    builder = builder.sharpen(settings.sharpen);
+
+    if let Some(fps) = settings.fps {
+        builder = builder.fps(fps);
+    }
+
+    if video_codec == "ffv1" {
+        builder = builder.ffv1_slices(settings.ffv1_slices.unwrap_or(DEFAULT_FFV1_SLICES));
+    }
+
+    // CPU-side filters (crop, transpose, denoise, subtitles, scaling) can't read frames
+    // left in CUDA/QSV memory, so decode into system memory instead
+    if builder.has_video_filters() && builder.has_hw_frames_output() {
+        builder = builder.download_frames();
+    }
+
+    // Pixel format: the user's choice first, if the encoder takes it
+    let requested = settings.pixel_format.as_deref().filter(|pix_fmt| {
+        let usable = !codec_map::is_vaapi_encoder(video_codec)
+            && codec_map::supported_pixel_formats(video_codec)
+                .is_none_or(|supported| supported.contains(pix_fmt));
+        if !usable {
+            tracing::warn!(encoder = %video_codec, pixel_format = %pix_fmt, "Pixel format not supported by the encoder, ignored");
+        }
+        usable
+    });
+    if let Some(pix_fmt) = requested {
+        builder = builder.pixel_format(pix_fmt);
+    } else if codec_map::is_vaapi_encoder(video_codec) {
+        // Accepts GPU surfaces as-is and uploads system-memory frames
+        builder = builder.pixel_format("nv12|vaapi").hw_upload();
+    } else if video_codec.contains("amf") {
+        builder = builder.pixel_format("nv12");
+    } else if let Some(pix_fmt) = codec_map::required_pixel_format(video_codec) {
+        builder = builder.pixel_format(pix_fmt);
+    } else if let Some(profile) = codec_map::intermediate_profile(video_codec, settings.quality) {
+        builder = builder.pixel_format(profile.pixel_format);
+    } else if codec_map::preserves_source_pixel_format(video_codec) {
+        // Lossless: keep the source's bit depth and chroma subsampling
+    } else if let Some(pix_fmt) = &fmt.default_pixel_format {
+        builder = builder.pixel_format(pix_fmt);
+    }
+
+    builder
+}
+
+/// B-frame and reference frame counts are only forwarded to software encoders
+/// that honour them — hardware encoders have strict per-generation limits.
+/// Keyframe spacing applies to every encoder.
+fn apply_frame_structure(
+    builder: FfmpegBuilder,
+    video_codec: &str,
+    media: &MediaInfo,
+    settings: &ConversionSettings,
+) -> FfmpegBuilder {
+    let mut builder = builder;
+
+    if settings.keyframe_interval.is_some() {
+        match settings.gop_frames(output_frame_rate(media, settings)) {
+            Some(frames) => {
+                builder = builder.gop(frames);
+                // Fixed spacing; x264 still caps the minimum at half the interval
+                if matches!(video_codec, "libx264" | "libx265") {
+                    builder = builder.keyint_min(frames);
+                }
+            }
+            None => {
+                tracing::warn!("Keyframe interval in seconds ignored, output frame rate unknown")
+            }
+        }
+    }
+
+    if let Some(seconds) = settings.force_keyframes_every() {
+        builder = builder.force_keyframes_every(seconds);
+    }
+
+    if let Some(bf) = settings.b_frames {
+        if codec_map::supports_b_frames(video_codec) {
+            builder = builder.b_frames(bf.min(codec_map::MAX_FRAME_STRUCTURE_COUNT));
+        } else {
+            tracing::warn!(encoder = %video_codec, "B-frame count ignored for this encoder");
+        }
+    }
+
+    if let Some(refs) = settings.ref_frames {
+        if codec_map::supports_ref_frames(video_codec) {
+            builder = builder.ref_frames(refs.clamp(1, codec_map::MAX_FRAME_STRUCTURE_COUNT));
+        } else {
+            tracing::warn!(encoder = %video_codec, "Reference frame count ignored for this encoder");
+        }
+    }
+
+    builder
+}
+
 fn apply_resolution(
     builder: FfmpegBuilder,
     fmt: &VideoFormat,
@@ -429,23 +1216,33 @@ fn apply_resolution(
 ) -> FfmpegBuilder {
     if fmt.requires_fixed_resolution {
         let source_height = media.primary_video().map(|v| v.height).unwrap_or(576);
-        let height = if source_height <= 480 { 480 } else { 576 };
-        let fps = if height == 480 { "30000/1001" } else { "25" };
+        let standard = settings
+            .tv_standard
+            .unwrap_or_else(|| TvStandard::from_source_height(source_height));
 
-        return builder
-            .resolution(Some(720), Some(height), true)
-            .arg("-r", fps);
+        let builder = match settings.scale_mode {
+            Some(mode) => {
+                builder.scale_to_frame(720, standard.height(), mode, frame_size(media, settings))
+            }
+            None => builder.resolution(Some(720), Some(standard.height()), true),
+        };
+        return builder.arg("-r", standard.frame_rate());
     }
 
+    if let (Some(width), Some(height), Some(mode)) =
+        (settings.width, settings.height, settings.scale_mode)
+    {
+        return builder.scale_to_frame(width, height, mode, frame_size(media, settings));
+    }
     if settings.width.is_some() || settings.height.is_some() {
         return builder.resolution(settings.width, settings.height, false);
     }
 
     if let Some((max_w, max_h)) = fmt.max_resolution {
-        if let Some(video) = media.primary_video() {
-            if video.width > max_w || video.height > max_h {
+        if let Some((width, height)) = frame_size(media, settings) {
+            if width > max_w || height > max_h {
                 tracing::info!(
-                    source = format!("{}x{}", video.width, video.height),
+                    source = format!("{}x{}", width, height),
                     max = format!("{}x{}", max_w, max_h),
                     format = %fmt.extension,
                     "Auto-downscaling to fit format resolution limits"
@@ -458,7 +1255,7 @@ fn apply_resolution(
     builder
 }
 
-fn apply_audio_settings(
+pub(super) fn apply_audio_settings(
     builder: FfmpegBuilder,
     fmt: &VideoFormat,
     media: &MediaInfo,
@@ -468,19 +1265,45 @@ fn apply_audio_settings(
         return builder.disable_audio();
     }
 
+    let builder = match settings.audio_track_index {
+        Some(_) => map_audio_track(builder, settings),
+        None => builder,
+    };
+
     let input_codec = media.audio_codec().unwrap_or("");
+    let audio_filters = filters::audio_chain(settings, media);
 
     if let Some(requested) = &settings.audio_codec {
-        if fmt.supports_audio_codec(requested) {
-            return builder.audio_codec(requested);
+        // FFmpeg can't filter a copied stream, so a requested copy gives way
+        // to a re-encode below
+        let filtered_copy = requested == "copy" && !audio_filters.is_empty();
+        if filtered_copy {
+            tracing::info!("Audio filters need a re-encode, ignoring the requested stream copy");
+        }
+        if fmt.supports_audio_codec(requested) && !filtered_copy {
+            let builder = builder.audio_codec(requested).audio_filters(&audio_filters);
+            return match settings.audio_bitrate {
+                Some(kbps) if requested != "copy" && !requested.starts_with("pcm") => {
+                    builder.audio_bitrate(kbps)
+                }
+                _ => builder,
+            };
         }
     }
 
-    if !input_codec.is_empty() && can_copy_audio(&fmt.audio_codecs, input_codec) {
+    // Filtering requires re-encoding, so stream copy is off the table, as it
+    // is when re-encoding the audio is the whole point or a bitrate is asked for
+    if audio_filters.is_empty()
+        && settings.audio_bitrate.is_none()
+        && !settings.replace_audio_only
+        && !input_codec.is_empty()
+        && fmt.supports_audio_codec(input_codec)
+    {
         return builder.audio_codec("copy");
     }
 
     if let Some(rec) = fmt.get_recommended_audio_codec() {
+        let rec = settings.preferred_audio_encoder(&rec).to_string();
         let actual_codec =
             if codec_registry::is_initialized() && !codec_registry::is_encoder_available(&rec) {
                 tracing::warn!(
@@ -494,13 +1317,24 @@ fn apply_audio_settings(
                 rec.clone()
             };
 
-        let mut b = builder.audio_codec(&actual_codec);
+        let mut b = builder
+            .audio_codec(&actual_codec)
+            .audio_filters(&audio_filters);
+        if codec_map::is_experimental_encoder(&actual_codec) {
+            b = b.arg("-strict", "experimental");
+        }
+        if let Some(rate) = codec_map::required_sample_rate(&actual_codec) {
+            b = b.sample_rate(rate);
+        }
         if !actual_codec.starts_with("pcm") && actual_codec != "copy" {
-            let bitrate = match actual_codec.as_str() {
-                "libopus" => 128,
-                "ac3" => 448,
-                _ => 192,
-            };
+            let bitrate = settings
+                .audio_bitrate
+                .unwrap_or(match actual_codec.as_str() {
+                    "libopus" | "opus" => 128,
+                    "ac3" | "eac3" => 448,
+                    "dca" => 1509,
+                    _ => 192,
+                });
             b = b.audio_bitrate(bitrate);
         }
         return b;
@@ -509,12 +1343,93 @@ fn apply_audio_settings(
     builder
 }
 
-fn can_copy_audio(supported: &[String], input_codec: &str) -> bool {
-    supported
+fn apply_subtitle_settings(
+    builder: FfmpegBuilder,
+    fmt: &VideoFormat,
+    media: &MediaInfo,
+    settings: &ConversionSettings,
+) -> FfmpegBuilder {
+    if !settings.copy_subtitles || media.subtitle_streams.is_empty() {
+        return builder;
+    }
+
+    let Some((target, text_only)) = fmt.subtitle_codec() else {
+        tracing::warn!(format = %fmt.extension, "Container cannot hold subtitles, dropping them");
+        return builder;
+    };
+
+    let usable: Vec<_> = media
+        .subtitle_streams
         .iter()
-        .any(|s| input_codec.contains(s) || s.contains(input_codec))
+        .enumerate()
+        .filter(|(_, s)| !text_only || s.is_text())
+        .collect();
+
+    if usable.len() < media.subtitle_streams.len() {
+        tracing::warn!(
+            format = %fmt.extension,
+            skipped = media.subtitle_streams.len() - usable.len(),
+            "Bitmap subtitles cannot be converted to text, skipping them"
+        );
+    }
+
+    if usable.is_empty() {
+        return builder;
+    }
+
+    // Explicit mapping replaces FFmpeg's default stream selection
+    let mut builder = if builder.has_maps() {
+        builder
+    } else {
+        map_audio_track(builder, settings)
+    };
+
+    for (out_index, (in_index, stream)) in usable.into_iter().enumerate() {
+        // Matroska can't store mov_text, everything else copies straight over
+        let codec = if target == "copy" && stream.codec == "mov_text" {
+            "srt"
+        } else {
+            target
+        };
+        builder = builder
+            .map(&format!("0:s:{}", in_index))
+            .subtitle_codec(out_index, codec);
+    }
+
+    builder
+}
+
+/// Map the video and the selected audio track (the first by default). Once
+/// any `-map` is given FFmpeg stops choosing streams itself, so the video has
+/// to be listed as well.
+fn map_audio_track(builder: FfmpegBuilder, settings: &ConversionSettings) -> FfmpegBuilder {
+    builder
+        .map("0:v:0")
+        .map(&format!("0:a:{}?", settings.audio_track_index.unwrap_or(0)))
 }
 
-fn apply_container_settings(builder: FfmpegBuilder, fmt: &VideoFormat) -> FfmpegBuilder {
-    builder.format(&fmt.container).args_vec(&fmt.special_params)
+pub(super) fn apply_container_settings(
+    builder: FfmpegBuilder,
+    fmt: &VideoFormat,
+    settings: &ConversionSettings,
+) -> FfmpegBuilder {
+    let mut builder = builder.format(&fmt.container).args_vec(&fmt.special_params);
+    if !fmt.is_isobmff() {
+        if settings.web_optimized {
+            tracing::warn!(
+                container = %fmt.container,
+                "Web optimization only applies to MP4/MOV, ignored"
+            );
+        }
+        return builder;
+    }
+
+    if settings.web_optimized && settings.fragmented {
+        builder = builder.fragmented();
+    }
+
+    match settings.mp4_brand() {
+        Some(brand) => builder.mp4_brand(brand),
+        None => builder.faststart(),
+    }
 }
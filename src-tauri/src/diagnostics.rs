@@ -0,0 +1,279 @@
+//! Toolchain health check for the "Help > Diagnostics" panel.
+//!
+//! Each check reports on its own, so a missing GPU or encoder doesn't hide a
+//! broken FFmpeg install behind it (or the other way round). The report's
+//! overall status is the worst of its checks.
+
+use crate::binary;
+use crate::codec_map;
+use crate::codec_registry;
+use crate::formats::{audio::AudioFormat, video::VideoFormat};
+use crate::gpu::GpuInfo;
+use crate::temp_files;
+use crate::utils;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+use tauri::AppHandle;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CheckStatus {
+    Ok,
+    /// Conversions work, with something missing (no GPU, some encoders)
+    Warning,
+    /// Conversions will fail until this is fixed
+    Error,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DiagnosticCheck {
+    pub name: &'static str,
+    pub status: CheckStatus,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details: Option<String>,
+}
+
+impl DiagnosticCheck {
+    fn new(name: &'static str, status: CheckStatus, message: impl Into<String>) -> Self {
+        Self {
+            name,
+            status,
+            message: message.into(),
+            details: None,
+        }
+    }
+
+    fn with_details(mut self, details: impl Into<String>) -> Self {
+        self.details = Some(details.into());
+        self
+    }
+}
+
+/// An encoder the format definitions rely on that this FFmpeg build lacks.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MissingEncoder {
+    pub encoder: String,
+    /// Extensions of the formats that use it
+    pub formats: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DiagnosticsReport {
+    pub status: CheckStatus,
+    pub checks: Vec<DiagnosticCheck>,
+    pub gpus: Vec<GpuInfo>,
+    pub missing_encoders: Vec<MissingEncoder>,
+}
+
+impl DiagnosticsReport {
+    pub fn new(
+        binaries: DiagnosticCheck,
+        gpus: &[GpuInfo],
+        audio_formats: &[AudioFormat],
+        video_formats: &[VideoFormat],
+    ) -> Self {
+        let missing_encoders = missing_encoders(audio_formats, video_formats, |encoder| {
+            codec_registry::lacks_encoder(encoder)
+        });
+        let checks = vec![
+            binaries,
+            check_gpus(gpus),
+            check_temp_dir(&temp_files::temp_root()),
+            check_encoders(&missing_encoders),
+        ];
+        Self {
+            status: checks
+                .iter()
+                .map(|c| c.status)
+                .max()
+                .unwrap_or(CheckStatus::Ok),
+            checks,
+            gpus: gpus.to_vec(),
+            missing_encoders,
+        }
+    }
+}
+
+/// FFmpeg and ffprobe are found and run, with the versions in use.
+pub async fn check_binaries(app: &AppHandle) -> DiagnosticCheck {
+    const NAME: &str = "FFmpeg binaries";
+    if let Err(e) = binary::check_binaries(app) {
+        let check = DiagnosticCheck::new(NAME, CheckStatus::Error, e.message);
+        return match e.details {
+            Some(details) => check.with_details(details),
+            None => check,
+        };
+    }
+
+    match binary::get_ffmpeg_info(app).await {
+        Ok(info) => DiagnosticCheck::new(
+            NAME,
+            CheckStatus::Ok,
+            format!(
+                "FFmpeg {}, ffprobe {}",
+                info.ffmpeg_version,
+                info.ffprobe_version.as_deref().unwrap_or("unknown")
+            ),
+        ),
+        Err(e) => DiagnosticCheck::new(NAME, CheckStatus::Ok, "FFmpeg and ffprobe run")
+            .with_details(format!("Version unknown: {}", e.message)),
+    }
+}
+
+fn check_gpus(gpus: &[GpuInfo]) -> DiagnosticCheck {
+    const NAME: &str = "GPU encoding";
+    if gpus.is_empty() {
+        return DiagnosticCheck::new(
+            NAME,
+            CheckStatus::Warning,
+            "No usable GPU encoder found, conversions run on the CPU",
+        );
+    }
+
+    let summary: Vec<String> = gpus
+        .iter()
+        .map(|gpu| {
+            let mut working: Vec<&str> = gpu
+                .encoders
+                .iter()
+                .filter(|(_, works)| **works)
+                .map(|(encoder, _)| encoder.as_str())
+                .collect();
+            working.sort_unstable();
+            format!("{} ({})", gpu.name, working.join(", "))
+        })
+        .collect();
+    DiagnosticCheck::new(NAME, CheckStatus::Ok, summary.join("; "))
+}
+
+/// The scratch directory (pass logs, palettes, in-place temp files) exists or
+/// can be created, and accepts new files.
+fn check_temp_dir(dir: &Path) -> DiagnosticCheck {
+    const NAME: &str = "Temp directory";
+    let result = std::fs::create_dir_all(dir)
+        .map_err(|e| e.to_string())
+        .and_then(|_| {
+            utils::validate_output_path(&dir.join("probe").to_string_lossy())
+                .map_err(|e| e.to_string())
+        });
+    match result {
+        Ok(()) => DiagnosticCheck::new(NAME, CheckStatus::Ok, dir.display().to_string()),
+        Err(e) => DiagnosticCheck::new(
+            NAME,
+            CheckStatus::Error,
+            format!("{} is not writable", dir.display()),
+        )
+        .with_details(e),
+    }
+}
+
+fn check_encoders(missing: &[MissingEncoder]) -> DiagnosticCheck {
+    const NAME: &str = "Encoders";
+    if !codec_registry::is_initialized() {
+        return DiagnosticCheck::new(
+            NAME,
+            CheckStatus::Warning,
+            "The encoder list couldn't be read from FFmpeg",
+        );
+    }
+    if missing.is_empty() {
+        return DiagnosticCheck::new(
+            NAME,
+            CheckStatus::Ok,
+            "Every encoder the formats use is built in",
+        );
+    }
+
+    let details: Vec<String> = missing
+        .iter()
+        .map(|m| format!("{} ({})", m.encoder, m.formats.join(", ")))
+        .collect();
+    DiagnosticCheck::new(
+        NAME,
+        CheckStatus::Warning,
+        format!(
+            "{} encoder(s) missing from this FFmpeg build",
+            missing.len()
+        ),
+    )
+    .with_details(details.join("; "))
+}
+
+/// Software encoders named by the format definitions that `lacks` rules out,
+/// with the formats using each. Audio encoders with a built-in fallback
+/// (`mp3` for `libmp3lame`) aren't missing; GPU encoders depend on the
+/// hardware, not the build, and are covered by GPU detection.
+fn missing_encoders(
+    audio_formats: &[AudioFormat],
+    video_formats: &[VideoFormat],
+    lacks: impl Fn(&str) -> bool,
+) -> Vec<MissingEncoder> {
+    let mut missing: BTreeMap<String, Vec<String>> = BTreeMap::new();
+
+    for fmt in audio_formats {
+        if fmt.codec != "copy"
+            && lacks(&fmt.codec)
+            && codec_registry::get_audio_fallback(&fmt.codec).is_none()
+        {
+            missing
+                .entry(fmt.codec.clone())
+                .or_default()
+                .push(fmt.extension.clone());
+        }
+    }
+    for fmt in video_formats {
+        for codec in &fmt.video_codecs {
+            let encoder = codec_map::software_encoder_for_codec(codec).unwrap_or(codec);
+            if lacks(encoder) {
+                missing
+                    .entry(encoder.to_string())
+                    .or_default()
+                    .push(fmt.extension.clone());
+            }
+        }
+    }
+
+    missing
+        .into_iter()
+        .map(|(encoder, formats)| MissingEncoder { encoder, formats })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::formats::{audio, video};
+
+    #[test]
+    fn test_missing_encoders() {
+        let audio_formats: Vec<AudioFormat> = ["wav", "flac"]
+            .iter()
+            .filter_map(|ext| audio::get_format(ext))
+            .collect();
+        let video_formats: Vec<VideoFormat> = ["mp4", "mkv"]
+            .iter()
+            .filter_map(|ext| video::get_format(ext))
+            .collect();
+
+        let missing = missing_encoders(&audio_formats, &video_formats, |encoder| {
+            encoder == "libx265" || encoder == "flac"
+        });
+        assert_eq!(
+            missing,
+            vec![
+                MissingEncoder {
+                    encoder: "flac".to_string(),
+                    formats: vec!["flac".to_string()],
+                },
+                MissingEncoder {
+                    encoder: "libx265".to_string(),
+                    formats: vec!["mp4".to_string(), "mkv".to_string()],
+                },
+            ]
+        );
+
+        assert!(missing_encoders(&audio_formats, &video_formats, |_| false).is_empty());
+    }
+}
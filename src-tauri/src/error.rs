@@ -1,3 +1,4 @@
+use crate::converter::gpu_throttle;
 use serde::Serialize;
 use std::fmt;
 
@@ -21,6 +22,15 @@ pub enum ErrorCode {
     UnsupportedFormat,
     ValidationFailed,
     IoError,
+    /// Output drive is out of space, or would be before the conversion finishes
+    DiskFull,
+    /// FFmpeg build lacks the requested encoder
+    EncoderUnavailable,
+    /// Input is corrupt or not really the media type it claims to be
+    InvalidInputData,
+    /// Hardware encoder couldn't start: driver missing, session limit reached
+    /// or out of video memory
+    GpuEncoderFailed,
     Unknown,
 }
 
@@ -41,6 +51,57 @@ impl AppError {
     pub fn conversion_failed(reason: &str) -> Self {
         Self::new(ErrorCode::ConversionFailed, reason)
     }
+
+    /// Turn a failed FFmpeg run into an actionable error. `stderr_tail` holds
+    /// FFmpeg's last output lines and is passed along as the details.
+    pub fn from_ffmpeg_failure(exit: impl fmt::Display, stderr_tail: &[String]) -> Self {
+        let code = classify_ffmpeg_error(stderr_tail);
+        let message = match code {
+            ErrorCode::DiskFull => "Not enough disk space to write the output".to_string(),
+            ErrorCode::EncoderUnavailable => {
+                "Your FFmpeg build doesn't include the encoder this conversion needs".to_string()
+            }
+            ErrorCode::InvalidInputData => {
+                "The input file is damaged or isn't a supported media file".to_string()
+            }
+            ErrorCode::GpuEncoderFailed => {
+                "The hardware encoder couldn't start or ran out of resources".to_string()
+            }
+            _ => format!("FFmpeg exited with code: {}", exit),
+        };
+
+        let error = Self::new(code, message);
+        if stderr_tail.is_empty() {
+            error
+        } else {
+            error.with_details(stderr_tail.join("\n"))
+        }
+    }
+}
+
+/// Recognize common FFmpeg failure messages. The last matching line wins, since
+/// FFmpeg's final lines describe the error that actually stopped it.
+fn classify_ffmpeg_error(stderr: &[String]) -> ErrorCode {
+    stderr
+        .iter()
+        .rev()
+        .find_map(|line| {
+            if line.contains("No space left on device") {
+                Some(ErrorCode::DiskFull)
+            } else if line.contains("Unknown encoder") || line.contains("Encoder not found") {
+                Some(ErrorCode::EncoderUnavailable)
+            } else if line.contains("Invalid data found when processing input") {
+                Some(ErrorCode::InvalidInputData)
+            } else if line.contains("Cannot load nvcuda")
+                || line.contains("Cannot load libcuda")
+                || gpu_throttle::detect_pressure(line).is_some()
+            {
+                Some(ErrorCode::GpuEncoderFailed)
+            } else {
+                None
+            }
+        })
+        .unwrap_or(ErrorCode::ConversionFailed)
 }
 
 impl fmt::Display for AppError {
@@ -76,3 +137,54 @@ impl From<AppError> for String {
 }
 
 pub type AppResult<T> = Result<T, AppError>;
+
+/// Map a converter error for a command result: an `AppError` raised inside the
+/// converter keeps its code (serialized as JSON), anything else stays plain text.
+pub fn command_error(err: anyhow::Error) -> String {
+    match err.downcast::<AppError>() {
+        Ok(app) => app.into(),
+        Err(other) => other.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(text: &str) -> Vec<String> {
+        text.lines().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn test_classify_ffmpeg_error() {
+        let disk = lines("frame= 100 fps=30\nav_interleaved_write_frame(): No space left on device\nConversion failed!");
+        assert_eq!(classify_ffmpeg_error(&disk), ErrorCode::DiskFull);
+
+        let encoder = lines("Unknown encoder 'libfdk_aac'");
+        assert_eq!(
+            classify_ffmpeg_error(&encoder),
+            ErrorCode::EncoderUnavailable
+        );
+
+        let input = lines("in.mp4: Invalid data found when processing input");
+        assert_eq!(classify_ffmpeg_error(&input), ErrorCode::InvalidInputData);
+
+        let session = lines("[h264_nvenc @ 0x55] OpenEncodeSessionEx failed: out of memory (10)\nConversion failed!");
+        assert_eq!(classify_ffmpeg_error(&session), ErrorCode::GpuEncoderFailed);
+        let driver = lines("[AVHWDeviceContext @ 0x1] Cannot load nvcuda.dll");
+        assert_eq!(classify_ffmpeg_error(&driver), ErrorCode::GpuEncoderFailed);
+
+        assert_eq!(
+            classify_ffmpeg_error(&lines("Conversion failed!")),
+            ErrorCode::ConversionFailed
+        );
+    }
+
+    #[test]
+    fn test_ffmpeg_failure_details() {
+        let err = AppError::from_ffmpeg_failure("exit status: 1", &lines("a\nb"));
+        assert_eq!(err.code, ErrorCode::ConversionFailed);
+        assert_eq!(err.message, "FFmpeg exited with code: exit status: 1");
+        assert_eq!(err.details.as_deref(), Some("a\nb"));
+    }
+}
@@ -28,6 +28,14 @@ pub struct AudioFormat {
 }
 
 impl AudioFormat {
+    /// Whether this FFmpeg build can encode the format at all, directly or
+    /// through an alternative encoder. `true` until the codec registry is read.
+    pub fn is_available(&self) -> bool {
+        self.codec == "copy"
+            || !crate::codec_registry::lacks_encoder(&self.codec)
+            || crate::codec_registry::get_audio_fallback(&self.codec).is_some()
+    }
+
     #[inline]
     pub fn supports_sample_rate(&self, rate: u32) -> bool {
         self.sample_rates.contains(&rate)
@@ -78,6 +86,15 @@ impl AudioFormat {
         }
     }
 
+    /// Supported channel counts as a readable list ("1, 2, 6, 8").
+    pub fn channels_list(&self) -> String {
+        self.channels_support
+            .iter()
+            .map(|c| c.to_string())
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
     pub fn best_channels(&self, requested: u32) -> u32 {
         if self.supports_channels(requested) {
             requested
@@ -31,9 +31,20 @@ pub struct VideoFormat {
     #[serde(default)]
     pub default_pixel_format: Option<String>,
     pub special_params: Vec<String>,
+    /// Single-image format: one frame of the input is encoded
+    #[serde(default)]
+    pub still_image: bool,
 }
 
+/// Major brands accepted by the MP4/MOV muxers' `-brand` option.
+pub const MP4_BRANDS: [&str; 6] = ["isom", "iso2", "mp41", "mp42", "avc1", "M4V "];
+
 impl VideoFormat {
+    /// Whether the container is written by FFmpeg's ISO-BMFF (mov/mp4) muxer family.
+    pub fn is_isobmff(&self) -> bool {
+        matches!(self.container.as_str(), "mp4" | "mov" | "3gp")
+    }
+
     pub fn supports_video_codec(&self, codec: &str) -> bool {
         self.video_codecs.iter().any(|c| codec_matches(c, codec))
     }
@@ -41,13 +52,57 @@ impl VideoFormat {
     #[inline]
     pub fn supports_audio_codec(&self, codec: &str) -> bool {
         self.audio_codecs.is_empty()
-            || self.audio_codecs.iter().any(|c| {
-                c == codec
-                    || codec.contains(c)
-                    || (c == "aac" && codec.starts_with("aac"))
-                    || (c == "opus" && codec.contains("opus"))
-                    || (c == "vorbis" && codec.contains("vorbis"))
-            })
+            || self
+                .audio_codecs
+                .iter()
+                .any(|c| codec_map::audio_codec_matches(c, codec))
+    }
+
+    /// Reasons the given source streams can't be copied into this container
+    /// as-is. Empty when a pure remux is possible.
+    pub fn remux_incompatibilities(
+        &self,
+        video_codec: Option<&str>,
+        audio_codec: Option<&str>,
+    ) -> Vec<String> {
+        let mut reasons = Vec::new();
+
+        match video_codec {
+            Some(codec) if !self.supports_video_codec(codec) => reasons.push(format!(
+                "video codec '{}' is not supported by {} (supports: {})",
+                codec,
+                self.extension,
+                self.video_codecs.join(", ")
+            )),
+            None => reasons.push("source has no video stream".to_string()),
+            _ => {}
+        }
+
+        if let Some(codec) = audio_codec {
+            if self.audio_codecs.is_empty() {
+                reasons.push(format!("{} cannot carry audio", self.extension));
+            } else if !self.supports_audio_codec(codec) {
+                reasons.push(format!(
+                    "audio codec '{}' is not supported by {} (supports: {})",
+                    codec,
+                    self.extension,
+                    self.audio_codecs.join(", ")
+                ));
+            }
+        }
+
+        reasons
+    }
+
+    /// Subtitle codec to write into this container, and whether it only
+    /// accepts text-based subtitles. `None` if the container can't hold them.
+    pub fn subtitle_codec(&self) -> Option<(&'static str, bool)> {
+        match self.container.as_str() {
+            "matroska" => Some(("copy", false)),
+            "mp4" | "mov" => Some(("mov_text", true)),
+            "webm" => Some(("webvtt", true)),
+            _ => None,
+        }
     }
 
     /// Get the best video codec for this format, considering GPU availability
@@ -64,7 +119,19 @@ impl VideoFormat {
         self.get_software_codec()
     }
 
-    fn get_software_codec(&self) -> Option<String> {
+    /// Whether this FFmpeg build has a software encoder for any of the
+    /// format's video codecs. GPU encoders aren't counted: they depend on the
+    /// hardware, not the build. `true` until the codec registry is read.
+    pub fn is_available(&self) -> bool {
+        self.video_codecs.is_empty()
+            || self.video_codecs.iter().any(|codec| {
+                let encoder = codec_map::software_encoder_for_codec(codec).unwrap_or(codec);
+                !crate::codec_registry::lacks_encoder(encoder)
+            })
+    }
+
+    /// Software encoder for the container's preferred video codec
+    pub fn get_software_codec(&self) -> Option<String> {
         self.video_codecs.first().map(|codec| {
             codec_map::software_encoder_for_codec(codec)
                 .unwrap_or(codec)
@@ -80,6 +147,8 @@ impl VideoFormat {
                 "vorbis" => "libvorbis",
                 "mp3" => "libmp3lame",
                 "ac3" => "ac3",
+                "eac3" => "eac3",
+                "dts" => "dca",
                 "pcm_s16le" => "pcm_s16le",
                 other => other,
             }
@@ -167,6 +236,8 @@ struct TomlVideoFormat {
     #[serde(default)]
     default_pixel_format: Option<String>,
     special_params: Vec<String>,
+    #[serde(default)]
+    still_image: bool,
 }
 
 #[derive(Debug, Deserialize)]
@@ -194,6 +265,7 @@ impl From<TomlVideoFormat> for VideoFormat {
             requires_fixed_resolution: t.requires_fixed_resolution,
             default_pixel_format: t.default_pixel_format,
             special_params: t.special_params,
+            still_image: t.still_image,
         }
     }
 }
@@ -1,5 +1,6 @@
 use crate::codec_map;
-use crate::utils::create_hidden_command;
+use crate::codec_registry;
+use crate::utils::{create_hidden_command, null_device};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use tokio::time::{timeout, Duration};
@@ -7,6 +8,13 @@ use tokio::time::{timeout, Duration};
 const GPU_DETECT_TIMEOUT: Duration = Duration::from_secs(10);
 const ENCODER_TEST_TIMEOUT: Duration = Duration::from_secs(8);
 
+/// First DRM render node — the integrated/primary GPU on virtually every Linux system.
+#[cfg(target_os = "linux")]
+const VAAPI_RENDER_NODE: &str = "/dev/dri/renderD128";
+/// Render node minors the kernel hands out (renderD128 and up), one per GPU.
+#[cfg(target_os = "linux")]
+const RENDER_NODE_MINORS: std::ops::Range<u32> = 128..136;
+
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "lowercase")]
 pub enum GpuVendor {
@@ -36,10 +44,16 @@ pub struct GpuInfo {
     pub name: String,
     pub encoder_h264: Option<String>,
     pub encoder_h265: Option<String>,
+    /// AV1 hardware encoder (RTX 40xx, Arc, RX 7000 and newer)
+    #[serde(default)]
+    pub encoder_av1: Option<String>,
     pub decoder: Option<String>,
     pub available: bool,
     /// Map of encoder name → actually works on this hardware
     pub encoders: HashMap<String, bool>,
+    /// DRM render node used for VAAPI encoding (Linux only)
+    #[serde(default)]
+    pub vaapi_device: Option<String>,
 }
 
 impl Default for GpuInfo {
@@ -49,9 +63,11 @@ impl Default for GpuInfo {
             name: "CPU Only".to_string(),
             encoder_h264: None,
             encoder_h265: None,
+            encoder_av1: None,
             decoder: None,
             available: false,
             encoders: HashMap::new(),
+            vaapi_device: None,
         }
     }
 }
@@ -64,12 +80,52 @@ impl GpuInfo {
 
     /// Get the best GPU encoder for a given codec type (h264, hevc, vp9, av1).
     /// Returns None if no GPU encoder is available for this codec.
+    /// The vendor's own API (QSV/AMF) is preferred; VAAPI is the fallback on Linux.
     pub fn get_encoder_for(&self, codec: &str) -> Option<String> {
+        // AV1 support depends on the GPU generation, so use what detection proved works
+        if codec == "av1" {
+            return self.encoder_av1.clone();
+        }
+
         let vendor_str = self.vendor.as_str()?;
         codec_map::gpu_encoder_for_codec(codec, vendor_str)
             .filter(|enc| self.is_encoder_available(enc))
+            .or_else(|| {
+                codec_map::gpu_encoder_for_codec(codec, "vaapi")
+                    .filter(|enc| self.is_encoder_available(enc))
+            })
             .map(|s| s.to_string())
     }
+
+    /// Input options that decode straight into GPU memory for `encoder`.
+    /// Empty for encoders that take system-memory frames.
+    pub fn hwaccel_args(&self, encoder: &str) -> Vec<String> {
+        match &self.vaapi_device {
+            Some(device) if codec_map::is_vaapi_encoder(encoder) => [
+                "-hwaccel",
+                "vaapi",
+                "-hwaccel_device",
+                device,
+                "-hwaccel_output_format",
+                "vaapi",
+            ]
+            .iter()
+            .map(|s| s.to_string())
+            .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Input options for `encoder` with hardware decoding turned off: VAAPI
+    /// encoders still need their device to upload the software-decoded frames.
+    pub fn hw_device_args(&self, encoder: &str) -> Vec<String> {
+        match &self.vaapi_device {
+            Some(device) if codec_map::is_vaapi_encoder(encoder) => {
+                vec!["-vaapi_device".to_string(), device.clone()]
+            }
+            _ => Vec::new(),
+        }
+    }
 }
 
 /// Candidate GPU encoders per vendor — only real, existing encoders
@@ -99,29 +155,65 @@ fn get_candidates(vendor: GpuVendor) -> Vec<&'static str> {
     }
 }
 
+/// VAAPI encoders to try alongside the vendor's own API (Intel/AMD on Linux)
+const VAAPI_CANDIDATES: [&str; 4] = ["h264_vaapi", "hevc_vaapi", "vp9_vaapi", "av1_vaapi"];
+
+/// Render node to use for VAAPI, if this system exposes one.
+///
+/// With several GPUs each has its own node, so pick the one whose PCI vendor
+/// matches. Falls back to the first node when sysfs can't tell them apart.
+fn vaapi_render_node(vendor: GpuVendor) -> Option<String> {
+    #[cfg(target_os = "linux")]
+    {
+        let pci_vendor = match vendor {
+            GpuVendor::Intel => "0x8086",
+            GpuVendor::Amd => "0x1002",
+            _ => return None,
+        };
+
+        let mut vendors_known = false;
+        for minor in RENDER_NODE_MINORS {
+            let sysfs = format!("/sys/class/drm/renderD{}/device/vendor", minor);
+            let Ok(id) = std::fs::read_to_string(sysfs) else {
+                continue;
+            };
+            vendors_known = true;
+            if id.trim() == pci_vendor {
+                return Some(format!("/dev/dri/renderD{}", minor));
+            }
+        }
+
+        (!vendors_known && std::path::Path::new(VAAPI_RENDER_NODE).exists())
+            .then(|| VAAPI_RENDER_NODE.to_string())
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = vendor;
+        None
+    }
+}
+
 // ============ Main detection entry point ============
 
-/// GPU detection priority: NVIDIA > AMD > Intel > Apple.
+/// Every usable GPU (one per vendor), in priority order: NVIDIA > AMD > Intel > Apple.
 /// NVIDIA first because NVENC is the most widely available and reliable.
 /// AMD before Intel because AMF is more common on desktop GPUs.
 /// Apple last because it only applies to macOS.
-pub async fn detect_gpu(ffmpeg_path: Option<String>) -> GpuInfo {
-    // Try each vendor in priority order
-    if let Some(gpu) = try_detect(GpuVendor::Nvidia, ffmpeg_path.clone()).await {
-        return gpu;
-    }
-    if let Some(gpu) = try_detect(GpuVendor::Amd, ffmpeg_path.clone()).await {
-        return gpu;
-    }
-    if let Some(gpu) = try_detect(GpuVendor::Intel, ffmpeg_path.clone()).await {
-        return gpu;
-    }
+pub async fn detect_all_gpus(ffmpeg_path: Option<String>) -> Vec<GpuInfo> {
+    // Vendors are independent, so probe them concurrently (iGPU + dGPU setups)
+    let (nvidia, amd, intel) = tokio::join!(
+        try_detect(GpuVendor::Nvidia, ffmpeg_path.clone()),
+        try_detect(GpuVendor::Amd, ffmpeg_path.clone()),
+        try_detect(GpuVendor::Intel, ffmpeg_path.clone()),
+    );
+
     #[cfg(target_os = "macos")]
-    if let Some(gpu) = try_detect(GpuVendor::Apple, ffmpeg_path.clone()).await {
-        return gpu;
-    }
+    let apple = try_detect(GpuVendor::Apple, ffmpeg_path).await;
+    #[cfg(not(target_os = "macos"))]
+    let apple = None;
 
-    GpuInfo::default()
+    [nvidia, amd, intel, apple].into_iter().flatten().collect()
 }
 
 async fn try_detect(vendor: GpuVendor, ffmpeg_path: Option<String>) -> Option<GpuInfo> {
@@ -140,12 +232,17 @@ async fn try_detect(vendor: GpuVendor, ffmpeg_path: Option<String>) -> Option<Gp
     };
 
     // Step 2: Test each candidate encoder on real hardware
-    let candidates = get_candidates(vendor);
+    let mut candidates = get_candidates(vendor);
     if candidates.is_empty() {
         return None;
     }
 
-    let encoders = test_encoders_parallel(&candidates, ffmpeg_path).await;
+    let vaapi_device = vaapi_render_node(vendor);
+    if vaapi_device.is_some() {
+        candidates.extend(VAAPI_CANDIDATES);
+    }
+
+    let encoders = test_encoders_parallel(&candidates, ffmpeg_path, vaapi_device.clone()).await;
 
     // At least one encoder must work
     let any_available = encoders.values().any(|&v| v);
@@ -156,13 +253,28 @@ async fn try_detect(vendor: GpuVendor, ffmpeg_path: Option<String>) -> Option<Gp
     // Step 3: Build GpuInfo with tested results
     let encoder_h264 = find_first_available(
         &encoders,
-        &["h264_nvenc", "h264_qsv", "h264_amf", "h264_videotoolbox"],
+        &[
+            "h264_nvenc",
+            "h264_qsv",
+            "h264_amf",
+            "h264_videotoolbox",
+            "h264_vaapi",
+        ],
     );
     let encoder_h265 = find_first_available(
         &encoders,
-        &["hevc_nvenc", "hevc_qsv", "hevc_amf", "hevc_videotoolbox"],
+        &[
+            "hevc_nvenc",
+            "hevc_qsv",
+            "hevc_amf",
+            "hevc_videotoolbox",
+            "hevc_vaapi",
+        ],
     );
 
+    let encoder_av1 =
+        find_first_available(&encoders, &["av1_nvenc", "av1_qsv", "av1_amf", "av1_vaapi"]);
+
     let decoder = match vendor {
         GpuVendor::Nvidia => Some("h264_cuvid".to_string()),
         GpuVendor::Intel => Some("h264_qsv".to_string()),
@@ -185,9 +297,11 @@ async fn try_detect(vendor: GpuVendor, ffmpeg_path: Option<String>) -> Option<Gp
         name,
         encoder_h264,
         encoder_h265,
+        encoder_av1,
         decoder,
         available: true,
         encoders,
+        vaapi_device,
     })
 }
 
@@ -204,19 +318,29 @@ fn find_first_available(encoders: &HashMap<String, bool>, candidates: &[&str]) -
 async fn test_encoders_parallel(
     candidates: &[&str],
     ffmpeg_path: Option<String>,
+    vaapi_device: Option<String>,
 ) -> HashMap<String, bool> {
     let mut handles = Vec::new();
+    let mut results = HashMap::new();
 
     for &enc in candidates {
+        // The cached `-encoders` list already rules out encoders this build
+        // wasn't compiled with, so they don't each cost a test encode
+        if codec_registry::lacks_encoder(enc) {
+            results.insert(enc.to_string(), false);
+            continue;
+        }
+
         let encoder_name = enc.to_string();
         let path_clone = ffmpeg_path.clone();
+        let device = vaapi_device.clone();
         handles.push(tokio::spawn(async move {
-            let available = test_encoder_real(&encoder_name, path_clone.as_deref()).await;
+            let available =
+                test_encoder_real(&encoder_name, path_clone.as_deref(), device.as_deref()).await;
             (encoder_name, available)
         }));
     }
 
-    let mut results = HashMap::new();
     for handle in handles {
         if let Ok((name, available)) = handle.await {
             results.insert(name, available);
@@ -228,30 +352,43 @@ async fn test_encoders_parallel(
 
 /// Actually test if an encoder works by trying a minimal encode.
 /// This catches: wrong GPU generation, missing drivers, FFmpeg not compiled with support.
-async fn test_encoder_real(encoder: &str, ffmpeg_path: Option<&str>) -> bool {
+async fn test_encoder_real(
+    encoder: &str,
+    ffmpeg_path: Option<&str>,
+    vaapi_device: Option<&str>,
+) -> bool {
     let encoder_owned = encoder.to_string();
     let cmd_str = ffmpeg_path.unwrap_or("ffmpeg").to_string();
-    let null_output = if cfg!(windows) { "NUL" } else { "/dev/null" };
+    let null_output = null_device();
+
+    // VAAPI encoders only accept frames uploaded to the device
+    let is_vaapi = codec_map::is_vaapi_encoder(encoder);
+    if is_vaapi && vaapi_device.is_none() {
+        return false;
+    }
+    let vaapi = vaapi_device
+        .filter(|_| is_vaapi)
+        .map(|device| device.to_string());
 
     let future = tokio::task::spawn_blocking(move || {
-        create_hidden_command(&cmd_str)
-            .args([
-                "-hide_banner",
-                "-loglevel",
-                "error",
-                "-f",
-                "lavfi",
-                "-i",
-                "nullsrc=s=256x256:d=0.1:r=1",
-                "-frames:v",
-                "1",
-                "-an",
-                "-c:v",
-                &encoder_owned,
-                "-f",
-                "null",
-                null_output,
-            ])
+        let mut cmd = create_hidden_command(&cmd_str);
+        cmd.args(["-hide_banner", "-loglevel", "error"]);
+        if let Some(device) = &vaapi {
+            cmd.args(["-vaapi_device", device]);
+        }
+        cmd.args([
+            "-f",
+            "lavfi",
+            "-i",
+            "nullsrc=s=256x256:d=0.1:r=1",
+            "-frames:v",
+            "1",
+            "-an",
+        ]);
+        if vaapi.is_some() {
+            cmd.args(["-vf", "format=nv12,hwupload"]);
+        }
+        cmd.args(["-c:v", &encoder_owned, "-f", "null", null_output])
             .output()
     });
 
@@ -271,12 +408,12 @@ async fn detect_nvidia_name() -> Option<String> {
         return None;
     }
 
-    let name = String::from_utf8_lossy(&output.stdout).trim().to_string();
-    if name.is_empty() {
-        None
-    } else {
-        Some(name)
-    }
+    // One line per card; NVENC encodes on the first one by default
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(str::trim)
+        .find(|name| !name.is_empty())
+        .map(|name| name.to_string())
 }
 
 async fn run_command_timeout(program: &str, args: &[&str]) -> Option<std::process::Output> {
@@ -292,8 +429,23 @@ async fn run_command_timeout(program: &str, args: &[&str]) -> Option<std::proces
 async fn get_gpu_name(keywords: &[&str]) -> Option<String> {
     #[cfg(target_os = "windows")]
     {
-        let output =
-            run_command_timeout("wmic", &["path", "win32_VideoController", "get", "name"]).await?;
+        // wmic is deprecated and missing on fresh Windows 11 installs
+        let output = match run_command_timeout(
+            "powershell",
+            &[
+                "-NoProfile",
+                "-Command",
+                "Get-CimInstance Win32_VideoController | Select-Object -ExpandProperty Name",
+            ],
+        )
+        .await
+        {
+            Some(output) if output.status.success() => output,
+            _ => {
+                run_command_timeout("wmic", &["path", "win32_VideoController", "get", "name"])
+                    .await?
+            }
+        };
         String::from_utf8_lossy(&output.stdout)
             .lines()
             .find(|line| keywords.iter().any(|kw| line.to_lowercase().contains(kw)))
@@ -1,17 +1,24 @@
+mod batch;
 mod binary;
 mod codec_map;
 mod codec_registry;
 mod commands;
 mod converter;
+mod diagnostics;
 mod error;
 mod formats;
 mod gpu;
+mod loudness;
 mod media;
+mod profiles;
+mod silence;
+mod task_journal;
+mod temp_files;
 mod types;
 mod utils;
 mod validator;
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 use tauri::Manager;
 use tokio::process::Child;
@@ -23,6 +30,18 @@ pub struct AppState {
     pub active_processes: Arc<Mutex<HashMap<String, Child>>>,
 }
 
+/// Kill every running FFmpeg process and forget it. Each conversion then sees
+/// its process gone and ends as cancelled. Returns the affected task ids.
+pub(crate) async fn kill_all_processes(processes: &Mutex<HashMap<String, Child>>) -> Vec<String> {
+    let drained: Vec<(String, Child)> = processes.lock().await.drain().collect();
+    let mut task_ids = Vec::with_capacity(drained.len());
+    for (task_id, mut child) in drained {
+        let _ = child.kill().await;
+        task_ids.push(task_id);
+    }
+    task_ids
+}
+
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     tracing_subscriber::fmt()
@@ -47,19 +66,46 @@ pub fn run() {
             commands::window_is_maximized,
             commands::close_splash,
             commands::check_ffmpeg,
+            commands::get_ffmpeg_info,
+            commands::run_diagnostics,
             commands::detect_gpu,
+            commands::detect_all_gpus,
+            commands::get_cpu_count,
             commands::open_folder,
             commands::reveal_in_folder,
             commands::check_paths_exist,
             commands::detect_media_type,
+            commands::detect_media_type_batch,
+            commands::probe_batch,
+            commands::clear_probe_cache,
             commands::get_audio_formats,
             commands::get_video_formats,
+            commands::get_all_extensions,
+            commands::get_format_defaults,
             commands::get_recommended_formats,
             commands::validate_conversion,
+            commands::estimate_output_size,
             commands::convert_audio,
             commands::convert_video,
             commands::extract_audio,
+            commands::mux_audio,
+            commands::convert_to_stream,
+            commands::start_batch,
+            commands::end_batch,
+            commands::list_interrupted_tasks,
+            commands::resolve_interrupted_task,
+            commands::extract_frames,
+            commands::detect_silence,
+            commands::list_profiles,
+            commands::load_profile,
+            commands::save_profile,
+            commands::delete_profile,
+            commands::convert_in_place,
             commands::cancel_conversion,
+            commands::cancel_all_conversions,
+            commands::cleanup_temp_files,
+            commands::set_gpu_session_limit,
+            commands::set_default_preserve_metadata,
         ])
         .setup(|app| {
             // Initialize codec registry as early as possible if FFmpeg is available
@@ -69,6 +115,16 @@ pub fn run() {
                 }
             }
 
+            // Nothing is running yet, so anything registered is left over from a crash
+            tauri::async_runtime::spawn_blocking(|| {
+                temp_files::cleanup_stale(&HashSet::new());
+            });
+
+            // Tasks still listed as running were interrupted the same way
+            if let Ok(dir) = app.path().app_config_dir() {
+                task_journal::init(&dir);
+            }
+
             let main_window = app.get_webview_window("main");
             let state = app.state::<AppState>();
             let processes = state.active_processes.clone();
@@ -80,12 +136,7 @@ pub fn run() {
                         if let tauri::WindowEvent::CloseRequested { .. } = event {
                             let procs = processes.clone();
                             std::thread::spawn(move || {
-                                tauri::async_runtime::block_on(async {
-                                    let mut map = procs.lock().await;
-                                    for (_, mut child) in map.drain() {
-                                        let _ = child.kill().await;
-                                    }
-                                });
+                                tauri::async_runtime::block_on(kill_all_processes(&procs));
                             });
                         }
                     }
@@ -0,0 +1,265 @@
+//! EBU R128 loudness normalization via FFmpeg's `loudnorm` filter.
+//!
+//! Accurate normalization needs two passes: an analysis run that measures the
+//! source, then the real encode with those measurements fed back into
+//! `loudnorm` (linear mode). Measurements are cached per source file (keyed by
+//! path + modification time) so repeated conversions of the same file skip the
+//! analysis and run as a single pass.
+
+use crate::utils::{create_async_hidden_command, null_device};
+use anyhow::{Context, Result};
+use lazy_static::lazy_static;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::process::Stdio;
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+use tokio::io::AsyncReadExt;
+use tokio::process::Child;
+use tokio::time::{timeout, Duration};
+
+/// Integrated loudness target (LUFS) — common streaming platform level.
+pub const DEFAULT_TARGET_LUFS: f64 = -16.0;
+const TARGET_TRUE_PEAK: f64 = -1.5;
+const TARGET_LRA: f64 = 11.0;
+
+/// Values measured by a `loudnorm` analysis pass.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LoudnessStats {
+    pub input_i: f64,
+    pub input_tp: f64,
+    pub input_lra: f64,
+    pub input_thresh: f64,
+    pub target_offset: f64,
+}
+
+/// How the analysis pass runs: registered in `processes` under `task_id`, so
+/// cancelling the task stops it, and killed after `timeout`.
+pub struct AnalysisRun<'a> {
+    pub task_id: &'a str,
+    pub timeout: Option<Duration>,
+    pub processes: &'a Arc<tokio::sync::Mutex<HashMap<String, Child>>>,
+}
+
+#[derive(PartialEq, Eq, Hash)]
+struct CacheKey {
+    path: PathBuf,
+    modified: SystemTime,
+    /// `target_offset` depends on the target, so it is part of the key
+    target_millilufs: i64,
+    audio_track: usize,
+}
+
+lazy_static! {
+    static ref MEASUREMENT_CACHE: Mutex<HashMap<CacheKey, LoudnessStats>> =
+        Mutex::new(HashMap::new());
+}
+
+/// Build the `loudnorm` filter. With measurements this is the accurate linear
+/// second pass; without, FFmpeg's single-pass dynamic mode.
+pub fn loudnorm_filter(target_lufs: f64, stats: Option<&LoudnessStats>) -> String {
+    let base = format!(
+        "loudnorm=I={}:TP={}:LRA={}",
+        target_lufs, TARGET_TRUE_PEAK, TARGET_LRA
+    );
+
+    match stats {
+        Some(s) => format!(
+            "{}:measured_I={}:measured_TP={}:measured_LRA={}:measured_thresh={}:offset={}:linear=true",
+            base, s.input_i, s.input_tp, s.input_lra, s.input_thresh, s.target_offset
+        ),
+        None => base,
+    }
+}
+
+/// Measure loudness of audio track `audio_track` of `input`, reusing a cached
+/// measurement when allowed and the file hasn't changed since it was taken.
+/// Returns `None` if the task was cancelled during the analysis.
+pub async fn measure(
+    ffmpeg_path: &str,
+    input: &str,
+    audio_track: usize,
+    target_lufs: f64,
+    reuse_measurement: bool,
+    run: &AnalysisRun<'_>,
+) -> Result<Option<LoudnessStats>> {
+    let key = cache_key(input, audio_track, target_lufs).await;
+
+    if reuse_measurement {
+        if let Some(stats) = key.as_ref().and_then(cached) {
+            tracing::info!(input = %input, "Reusing cached loudness measurement");
+            return Ok(Some(stats));
+        }
+    }
+
+    let Some(stats) = run_analysis(ffmpeg_path, input, audio_track, target_lufs, run).await? else {
+        return Ok(None);
+    };
+
+    if let Some(key) = key {
+        MEASUREMENT_CACHE
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(key, stats.clone());
+    }
+
+    Ok(Some(stats))
+}
+
+fn cached(key: &CacheKey) -> Option<LoudnessStats> {
+    MEASUREMENT_CACHE
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .get(key)
+        .cloned()
+}
+
+async fn cache_key(input: &str, audio_track: usize, target_lufs: f64) -> Option<CacheKey> {
+    let path = tokio::fs::canonicalize(input).await.ok()?;
+    let modified = tokio::fs::metadata(&path).await.ok()?.modified().ok()?;
+    Some(CacheKey {
+        path,
+        modified,
+        target_millilufs: (target_lufs * 1000.0).round() as i64,
+        audio_track,
+    })
+}
+
+async fn run_analysis(
+    ffmpeg_path: &str,
+    input: &str,
+    audio_track: usize,
+    target_lufs: f64,
+    run: &AnalysisRun<'_>,
+) -> Result<Option<LoudnessStats>> {
+    let filter = format!("{}:print_format=json", loudnorm_filter(target_lufs, None));
+    let track = format!("0:a:{}", audio_track);
+
+    let mut child = create_async_hidden_command(ffmpeg_path)
+        .args([
+            "-hide_banner",
+            "-nostats",
+            "-i",
+            input,
+            "-map",
+            &track,
+            "-af",
+            &filter,
+            "-f",
+            "null",
+            null_device(),
+        ])
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .context("Failed to run loudness analysis")?;
+    let mut stderr = child.stderr.take().expect("Failed to capture stderr");
+
+    run.processes
+        .lock()
+        .await
+        .insert(run.task_id.to_string(), child);
+
+    let mut output = Vec::new();
+    let read = stderr.read_to_end(&mut output);
+    let read = match run.timeout {
+        Some(limit) => timeout(limit, read).await.ok(),
+        None => Some(read.await),
+    };
+
+    let Some(mut child) = run.processes.lock().await.remove(run.task_id) else {
+        return Ok(None);
+    };
+    let Some(read) = read else {
+        let _ = child.kill().await;
+        anyhow::bail!("Loudness analysis timed out");
+    };
+    read.context("Failed to read loudness analysis output")?;
+
+    let status = child.wait().await?;
+    if !status.success() {
+        anyhow::bail!("Loudness analysis failed with code: {}", status);
+    }
+
+    parse_loudnorm_output(&String::from_utf8_lossy(&output))
+        .map(Some)
+        .context("Could not read loudnorm measurements")
+}
+
+/// Extract the JSON block `loudnorm` prints at the end of an analysis pass.
+fn parse_loudnorm_output(stderr: &str) -> Option<LoudnessStats> {
+    let start = stderr.rfind('{')?;
+    let end = start + stderr[start..].find('}')?;
+    let json: HashMap<String, String> = serde_json::from_str(&stderr[start..=end]).ok()?;
+
+    let field = |name: &str| -> Option<f64> { json.get(name)?.trim().parse().ok() };
+
+    let stats = LoudnessStats {
+        input_i: field("input_i")?,
+        input_tp: field("input_tp")?,
+        input_lra: field("input_lra")?,
+        input_thresh: field("input_thresh")?,
+        target_offset: field("target_offset")?,
+    };
+
+    // Pure silence measures as -inf, which loudnorm can't take back as input
+    let finite = [stats.input_i, stats.input_tp, stats.input_thresh]
+        .iter()
+        .all(|v| v.is_finite());
+    finite.then_some(stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = r#"size=N/A time=00:03:12.00 bitrate=N/A speed= 412x
+[Parsed_loudnorm_0 @ 0x600000f0c000]
+{
+	"input_i" : "-27.61",
+	"input_tp" : "-4.47",
+	"input_lra" : "18.06",
+	"input_thresh" : "-39.20",
+	"output_i" : "-16.58",
+	"output_tp" : "-1.50",
+	"output_lra" : "14.78",
+	"output_thresh" : "-27.71",
+	"normalization_type" : "dynamic",
+	"target_offset" : "0.58"
+}
+"#;
+
+    #[test]
+    fn test_parse_loudnorm_output() {
+        let stats = parse_loudnorm_output(SAMPLE).unwrap();
+        assert_eq!(stats.input_i, -27.61);
+        assert_eq!(stats.input_tp, -4.47);
+        assert_eq!(stats.input_lra, 18.06);
+        assert_eq!(stats.input_thresh, -39.20);
+        assert_eq!(stats.target_offset, 0.58);
+    }
+
+    #[test]
+    fn test_parse_silent_input() {
+        let silent = SAMPLE
+            .replace("\"-27.61\"", "\"-inf\"")
+            .replace("\"-4.47\"", "\"-inf\"");
+        assert!(parse_loudnorm_output(&silent).is_none());
+        assert!(parse_loudnorm_output("no json here").is_none());
+    }
+
+    #[test]
+    fn test_loudnorm_filter() {
+        assert_eq!(
+            loudnorm_filter(-16.0, None),
+            "loudnorm=I=-16:TP=-1.5:LRA=11"
+        );
+
+        let stats = parse_loudnorm_output(SAMPLE).unwrap();
+        assert_eq!(
+            loudnorm_filter(-16.0, Some(&stats)),
+            "loudnorm=I=-16:TP=-1.5:LRA=11:measured_I=-27.61:measured_TP=-4.47:measured_LRA=18.06:measured_thresh=-39.2:offset=0.58:linear=true"
+        );
+    }
+}
@@ -1,6 +1,22 @@
-use crate::utils::create_async_hidden_command;
+use crate::types::FileMetadata;
+use crate::utils::{create_async_hidden_command, validate_input_path};
 use anyhow::{Context, Result};
+use lazy_static::lazy_static;
 use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+use tokio::sync::Semaphore;
+
+/// Upper bound on ffprobe processes running at once during batch analysis.
+const MAX_CONCURRENT_PROBES: usize = 8;
+/// Probe results kept for unchanged files; the least recently used go first.
+const PROBE_CACHE_CAPACITY: usize = 128;
+
+lazy_static! {
+    static ref PROBE_CACHE: Mutex<ProbeCache> = Mutex::new(ProbeCache::default());
+}
 
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "lowercase")]
@@ -17,6 +33,43 @@ pub struct VideoStream {
     pub height: u32,
     pub fps: f64,
     pub bitrate: Option<u64>,
+    /// ffprobe's `field_order`: `progressive`, or `tt`/`bb`/`tb`/`bt` for interlaced
+    #[serde(default)]
+    pub field_order: Option<String>,
+    /// ffprobe's colour tags, e.g. `smpte2084` / `bt2020` / `bt2020nc` for HDR10
+    #[serde(default)]
+    pub color_transfer: Option<String>,
+    #[serde(default)]
+    pub color_primaries: Option<String>,
+    #[serde(default)]
+    pub color_space: Option<String>,
+    /// Clockwise turn (90, 180 or 270) players apply to show the picture
+    /// upright, from the display matrix or the older `rotate` tag. Phones
+    /// record sideways and tag the stream instead of turning the pixels.
+    #[serde(default)]
+    pub rotation: Option<u32>,
+}
+
+impl VideoStream {
+    /// Whether the stream is stored as interlaced fields (DVD, broadcast TV),
+    /// which shows combing on progressive displays unless deinterlaced.
+    pub fn is_interlaced(&self) -> bool {
+        matches!(self.field_order.as_deref(), Some("tt" | "bb" | "tb" | "bt"))
+    }
+
+    /// Whether the stream is HDR / wide gamut, which looks grey and washed out
+    /// when encoded to 8-bit SDR without tone mapping.
+    pub fn is_hdr(&self) -> bool {
+        is_hdr(
+            self.color_transfer.as_deref(),
+            self.color_primaries.as_deref(),
+        )
+    }
+}
+
+/// PQ (HDR10, Dolby Vision) or HLG transfer, or BT.2020 primaries.
+pub fn is_hdr(transfer: Option<&str>, primaries: Option<&str>) -> bool {
+    matches!(transfer, Some("smpte2084" | "arib-std-b67")) || primaries == Some("bt2020")
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -25,6 +78,36 @@ pub struct AudioStream {
     pub sample_rate: u32,
     pub channels: u32,
     pub bitrate: Option<u64>,
+    #[serde(default)]
+    pub language: Option<String>,
+    #[serde(default)]
+    pub title: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SubtitleStream {
+    pub codec: String,
+    pub language: Option<String>,
+    pub title: Option<String>,
+}
+
+impl SubtitleStream {
+    /// Text-based subtitles can be converted between formats; bitmap ones
+    /// (PGS, VobSub, DVB) can only be copied as-is.
+    pub fn is_text(&self) -> bool {
+        matches!(
+            self.codec.as_str(),
+            "subrip" | "srt" | "ass" | "ssa" | "mov_text" | "webvtt" | "text"
+        )
+    }
+}
+
+/// File embedded in the container, usually a font for styled subtitles.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Attachment {
+    pub codec: Option<String>,
+    pub filename: Option<String>,
+    pub mime_type: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -33,8 +116,25 @@ pub struct MediaInfo {
     pub duration: f64,
     pub file_size: u64,
     pub format_name: String,
+    /// Overall bit rate of the file in bits/s, from the container
+    #[serde(default)]
+    pub bitrate: Option<u64>,
     pub video_streams: Vec<VideoStream>,
     pub audio_streams: Vec<AudioStream>,
+    #[serde(default)]
+    pub subtitle_streams: Vec<SubtitleStream>,
+    /// Only copied when every stream is kept
+    #[serde(default)]
+    pub attachments: Vec<Attachment>,
+    /// Existing title/artist/album/genre/year tags, for pre-filling the editor
+    #[serde(default)]
+    pub tags: Option<FileMetadata>,
+    /// Chapter markers in the source (kept when metadata is preserved)
+    #[serde(default)]
+    pub chapter_count: usize,
+    /// Set when the file extension disagrees with what ffprobe found inside
+    #[serde(default)]
+    pub mismatch_warning: Option<String>,
 }
 
 impl MediaInfo {
@@ -51,7 +151,85 @@ impl MediaInfo {
     }
 }
 
+/// Identifies one version of a file: a probe is reused only while the file
+/// still has the same modification time and size.
+#[derive(Debug, Clone, PartialEq)]
+struct ProbeKey {
+    path: PathBuf,
+    modified: SystemTime,
+    size: u64,
+}
+
+/// Small LRU of probe results, most recently used at the back.
+#[derive(Default)]
+struct ProbeCache {
+    entries: VecDeque<(ProbeKey, MediaInfo)>,
+}
+
+impl ProbeCache {
+    fn get(&mut self, key: &ProbeKey) -> Option<MediaInfo> {
+        let pos = self.entries.iter().position(|(k, _)| k.path == key.path)?;
+        let entry = self.entries.remove(pos)?;
+        // A stale entry for a changed file is dropped
+        if entry.0 != *key {
+            return None;
+        }
+        let info = entry.1.clone();
+        self.entries.push_back(entry);
+        Some(info)
+    }
+
+    fn insert(&mut self, key: ProbeKey, info: MediaInfo) {
+        self.entries.retain(|(k, _)| k.path != key.path);
+        self.entries.push_back((key, info));
+        while self.entries.len() > PROBE_CACHE_CAPACITY {
+            self.entries.pop_front();
+        }
+    }
+}
+
+async fn probe_key(path: &str) -> Option<ProbeKey> {
+    let path = tokio::fs::canonicalize(path).await.ok()?;
+    let meta = tokio::fs::metadata(&path).await.ok()?;
+    Some(ProbeKey {
+        modified: meta.modified().ok()?,
+        size: meta.len(),
+        path,
+    })
+}
+
+/// Forget every cached probe result. Returns how many were dropped.
+pub fn clear_probe_cache() -> usize {
+    let mut cache = PROBE_CACHE.lock().unwrap_or_else(|e| e.into_inner());
+    let count = cache.entries.len();
+    cache.entries.clear();
+    count
+}
+
+/// Probe `path`, answering from the cache when the file hasn't changed since
+/// it was last probed.
 pub async fn detect_media_type(app_handle: &tauri::AppHandle, path: &str) -> Result<MediaInfo> {
+    validate_input_path(path)?;
+
+    let key = probe_key(path).await;
+    if let Some(info) = key
+        .as_ref()
+        .and_then(|k| PROBE_CACHE.lock().unwrap_or_else(|e| e.into_inner()).get(k))
+    {
+        return Ok(info);
+    }
+
+    let info = run_probe(app_handle, path).await?;
+    if let Some(key) = key {
+        PROBE_CACHE
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(key, info.clone());
+    }
+    Ok(info)
+}
+
+async fn run_probe(app_handle: &tauri::AppHandle, path: &str) -> Result<MediaInfo> {
     let ffprobe_path = crate::get_ffprobe_path(app_handle)
         .map_err(|e| anyhow::anyhow!("FFprobe not found: {}", e))?;
 
@@ -66,6 +244,7 @@ pub async fn detect_media_type(app_handle: &tauri::AppHandle, path: &str) -> Res
             "-print_format",
             "json",
             "-show_format",
+            "-show_chapters",
             "-show_streams",
             path,
         ])
@@ -89,7 +268,132 @@ pub async fn detect_media_type(app_handle: &tauri::AppHandle, path: &str) -> Res
         .map(|m| m.len())
         .unwrap_or(0);
 
-    parse_probe_result(&probe, file_size)
+    let mut info = parse_probe_result(&probe, file_size)?;
+    info.mismatch_warning = container_mismatch(path, &info);
+    if let Some(warning) = &info.mismatch_warning {
+        tracing::warn!(path = %path, format = %info.format_name, "{}", warning);
+    }
+    Ok(info)
+}
+
+/// Compare the file extension with the probed container and audio codec.
+/// Returns a warning for mislabeled files (an `.avi` that is really Matroska,
+/// an `.mp3` holding AAC), whose conversions otherwise behave unexpectedly.
+/// Unknown extensions are not judged.
+pub fn container_mismatch(path: &str, info: &MediaInfo) -> Option<String> {
+    let extension = std::path::Path::new(path)
+        .extension()?
+        .to_str()?
+        .to_lowercase();
+    let expected = expected_demuxers(&extension)?;
+
+    let detected: Vec<&str> = info.format_name.split(',').collect();
+    if !detected.iter().any(|d| expected.contains(d)) {
+        return Some(format!(
+            "File has a .{} extension but is actually {}",
+            extension,
+            describe_container(&info.format_name)
+        ));
+    }
+
+    if let (Some(expected), Some(codec)) = (expected_audio_codec(&extension), info.audio_codec()) {
+        if codec != expected {
+            return Some(format!(
+                "File has a .{} extension but contains {} audio",
+                extension,
+                codec.to_uppercase()
+            ));
+        }
+    }
+
+    None
+}
+
+/// ffprobe demuxer names (entries of `format_name`) that fit an extension.
+fn expected_demuxers(extension: &str) -> Option<&'static [&'static str]> {
+    Some(match extension {
+        "mkv" | "mka" | "mk3d" => &["matroska"],
+        "webm" => &["webm", "matroska"],
+        "mp4" | "m4v" | "m4a" | "m4b" | "mov" | "3gp" | "3g2" | "avif" => &["mov", "mp4"],
+        "avi" => &["avi"],
+        "mp3" => &["mp3"],
+        "flac" => &["flac"],
+        "ogg" | "oga" | "ogv" | "opus" => &["ogg"],
+        "wav" => &["wav"],
+        "aac" => &["aac"],
+        "ac3" => &["ac3"],
+        "aif" | "aiff" => &["aiff"],
+        "wma" | "wmv" | "asf" => &["asf"],
+        "flv" => &["flv"],
+        "ts" | "mts" | "m2ts" => &["mpegts"],
+        "mpg" | "mpeg" | "vob" => &["mpeg", "mpegvideo"],
+        "caf" => &["caf"],
+        "wv" => &["wv"],
+        "ape" => &["ape"],
+        "gif" => &["gif"],
+        "webp" => &["webp_pipe"],
+        "jxl" => &["jpegxl_pipe", "jpegxl_anim"],
+        _ => return None,
+    })
+}
+
+/// Audio codec implied by extensions that name a codec rather than a container.
+fn expected_audio_codec(extension: &str) -> Option<&'static str> {
+    match extension {
+        "mp3" => Some("mp3"),
+        "flac" => Some("flac"),
+        "opus" => Some("opus"),
+        "aac" => Some("aac"),
+        _ => None,
+    }
+}
+
+fn describe_container(format_name: &str) -> String {
+    let first = format_name.split(',').next().unwrap_or(format_name);
+    match first {
+        "matroska" => "Matroska (MKV/WebM)".to_string(),
+        "mov" => "MP4/MOV".to_string(),
+        "aac" => "raw AAC (ADTS)".to_string(),
+        "ogg" => "Ogg".to_string(),
+        "asf" => "ASF (WMV/WMA)".to_string(),
+        "mpegts" => "MPEG-TS".to_string(),
+        "mpeg" => "MPEG-PS".to_string(),
+        other => other.to_uppercase(),
+    }
+}
+
+/// Probe many files concurrently (bounded). Results keep the order of `paths`.
+pub async fn detect_media_type_batch(
+    app_handle: &tauri::AppHandle,
+    paths: Vec<String>,
+) -> Vec<Result<MediaInfo>> {
+    let limit = std::thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(4)
+        .min(MAX_CONCURRENT_PROBES);
+    let permits = Arc::new(Semaphore::new(limit));
+
+    let handles: Vec<_> = paths
+        .into_iter()
+        .map(|path| {
+            let app_handle = app_handle.clone();
+            let permits = permits.clone();
+            tokio::spawn(async move {
+                let _permit = permits.acquire_owned().await?;
+                detect_media_type(&app_handle, &path).await
+            })
+        })
+        .collect();
+
+    let mut results = Vec::with_capacity(handles.len());
+    for handle in handles {
+        results.push(
+            handle
+                .await
+                .unwrap_or_else(|e| Err(anyhow::anyhow!("Probe task failed: {}", e))),
+        );
+    }
+    results
 }
 
 fn parse_probe_result(probe: &serde_json::Value, file_size: u64) -> Result<MediaInfo> {
@@ -107,6 +411,14 @@ fn parse_probe_result(probe: &serde_json::Value, file_size: u64) -> Result<Media
         .unwrap_or("unknown")
         .to_string();
 
+    let bitrate = format
+        .get("bit_rate")
+        .and_then(|b| b.as_str())
+        .and_then(|s| s.parse().ok())
+        .or_else(|| {
+            (duration > 0.0 && file_size > 0).then(|| (file_size as f64 * 8.0 / duration) as u64)
+        });
+
     let streams = probe
         .get("streams")
         .and_then(|s| s.as_array())
@@ -114,6 +426,8 @@ fn parse_probe_result(probe: &serde_json::Value, file_size: u64) -> Result<Media
 
     let mut video_streams = Vec::new();
     let mut audio_streams = Vec::new();
+    let mut subtitle_streams = Vec::new();
+    let mut attachments = Vec::new();
 
     for stream in streams {
         match stream.get("codec_type").and_then(|c| c.as_str()) {
@@ -127,9 +441,29 @@ fn parse_probe_result(probe: &serde_json::Value, file_size: u64) -> Result<Media
                     audio_streams.push(audio);
                 }
             }
+            Some("subtitle") => {
+                if let Some(subtitle) = parse_subtitle_stream(stream) {
+                    subtitle_streams.push(subtitle);
+                }
+            }
+            Some("attachment") => attachments.push(parse_attachment(stream)),
             _ => {}
         }
     }
+    attribute_container_bitrate(bitrate, &mut video_streams, &mut audio_streams);
+
+    let tags = parse_tags(format.get("tags")).or_else(|| {
+        // Ogg/Opus keep their comments on the audio stream instead
+        streams
+            .iter()
+            .find(|s| s.get("codec_type").and_then(|c| c.as_str()) == Some("audio"))
+            .and_then(|s| parse_tags(s.get("tags")))
+    });
+
+    let chapter_count = probe
+        .get("chapters")
+        .and_then(|c| c.as_array())
+        .map_or(0, |c| c.len());
 
     let media_type = if !video_streams.is_empty() {
         MediaType::Video
@@ -144,36 +478,181 @@ fn parse_probe_result(probe: &serde_json::Value, file_size: u64) -> Result<Media
         duration,
         file_size,
         format_name,
+        bitrate,
         video_streams,
         audio_streams,
+        subtitle_streams,
+        attachments,
+        tags,
+        chapter_count,
+        mismatch_warning: None,
     })
 }
 
+/// The stream's `bit_rate`, or the `BPS` statistics tag mkvmerge writes:
+/// ffprobe reports no per-stream bit rate for Matroska.
+fn stream_bitrate(stream: &serde_json::Value) -> Option<u64> {
+    stream
+        .get("bit_rate")
+        .or_else(|| {
+            let tags = stream.get("tags")?;
+            tags.get("BPS").or_else(|| tags.get("BPS-eng"))
+        })
+        .and_then(|b| b.as_str())
+        .and_then(|s| s.parse().ok())
+}
+
+/// Give the one audio or video stream without a bit rate whatever part of
+/// the container's the others don't account for (all of it in a single-stream
+/// file). With two or more unknown, there's no telling how it splits, so they
+/// stay `None`.
+fn attribute_container_bitrate(
+    total: Option<u64>,
+    video_streams: &mut [VideoStream],
+    audio_streams: &mut [AudioStream],
+) {
+    let Some(total) = total else {
+        return;
+    };
+    let known: u64 = video_streams
+        .iter()
+        .filter_map(|v| v.bitrate)
+        .chain(audio_streams.iter().filter_map(|a| a.bitrate))
+        .sum();
+
+    let mut unknown = video_streams
+        .iter_mut()
+        .map(|v| &mut v.bitrate)
+        .chain(audio_streams.iter_mut().map(|a| &mut a.bitrate))
+        .filter(|b| b.is_none());
+    let Some(slot) = unknown.next() else {
+        return;
+    };
+    if unknown.next().is_none() {
+        *slot = Some(total.saturating_sub(known)).filter(|rest| *rest > 0);
+    }
+}
+
 fn parse_video_stream(stream: &serde_json::Value) -> Option<VideoStream> {
     Some(VideoStream {
         codec: stream.get("codec_name")?.as_str()?.to_string(),
         width: stream.get("width")?.as_u64()? as u32,
         height: stream.get("height")?.as_u64()? as u32,
         fps: parse_framerate(stream.get("r_frame_rate")?.as_str()?),
-        bitrate: stream
-            .get("bit_rate")
-            .and_then(|b| b.as_str())
-            .and_then(|s| s.parse().ok()),
+        bitrate: stream_bitrate(stream),
+        field_order: stream
+            .get("field_order")
+            .and_then(|f| f.as_str())
+            .map(|s| s.to_string()),
+        color_transfer: color_tag(stream, "color_transfer"),
+        color_primaries: color_tag(stream, "color_primaries"),
+        color_space: color_tag(stream, "color_space"),
+        rotation: display_rotation(stream),
     })
 }
 
+/// Clockwise display rotation of a stream. The display matrix's `rotation` is
+/// counter-clockwise (a portrait phone clip reads -90); the `rotate` tag that
+/// older FFmpeg versions report instead is clockwise.
+fn display_rotation(stream: &serde_json::Value) -> Option<u32> {
+    let from_matrix = stream
+        .get("side_data_list")
+        .and_then(|list| list.as_array())
+        .and_then(|list| {
+            list.iter()
+                .filter(|sd| {
+                    sd.get("side_data_type").and_then(|t| t.as_str()) == Some("Display Matrix")
+                })
+                .find_map(|sd| sd.get("rotation").and_then(|r| r.as_f64()))
+        })
+        .map(|ccw| -ccw);
+    let degrees = from_matrix.or_else(|| stream_tag(stream, "rotate")?.parse().ok())?;
+
+    // Only quarter turns; anything else is left to the player
+    let clockwise = (degrees.round() as i64).rem_euclid(360) as u32;
+    Some(clockwise).filter(|r| matches!(r, 90 | 180 | 270))
+}
+
+/// Colour tag from a stream, treating ffprobe's `unknown` as absent.
+fn color_tag(stream: &serde_json::Value, key: &str) -> Option<String> {
+    stream
+        .get(key)
+        .and_then(|v| v.as_str())
+        .filter(|v| *v != "unknown")
+        .map(|s| s.to_string())
+}
+
 fn parse_audio_stream(stream: &serde_json::Value) -> Option<AudioStream> {
     Some(AudioStream {
         codec: stream.get("codec_name")?.as_str()?.to_string(),
         sample_rate: stream.get("sample_rate")?.as_str()?.parse().ok()?,
         channels: stream.get("channels")?.as_u64()? as u32,
-        bitrate: stream
-            .get("bit_rate")
-            .and_then(|b| b.as_str())
-            .and_then(|s| s.parse().ok()),
+        bitrate: stream_bitrate(stream),
+        language: stream_tag(stream, "language"),
+        title: stream_tag(stream, "title"),
+    })
+}
+
+fn parse_subtitle_stream(stream: &serde_json::Value) -> Option<SubtitleStream> {
+    Some(SubtitleStream {
+        codec: stream.get("codec_name")?.as_str()?.to_string(),
+        language: stream_tag(stream, "language"),
+        title: stream_tag(stream, "title"),
     })
 }
 
+fn parse_attachment(stream: &serde_json::Value) -> Attachment {
+    Attachment {
+        codec: stream
+            .get("codec_name")
+            .and_then(|c| c.as_str())
+            .map(|c| c.to_string()),
+        filename: stream_tag(stream, "filename"),
+        mime_type: stream_tag(stream, "mimetype"),
+    }
+}
+
+/// Read the editable tags out of an ffprobe `tags` object. Keys are matched
+/// case-insensitively (Vorbis comments are upper-case); ffprobe's `date` maps
+/// back to `year`, the field written out as `date`. `None` if none are set.
+fn parse_tags(tags: Option<&serde_json::Value>) -> Option<FileMetadata> {
+    let tags = tags?.as_object()?;
+    let tag = |name: &str| {
+        tags.iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .and_then(|(_, v)| v.as_str())
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty())
+    };
+
+    let metadata = FileMetadata {
+        title: tag("title"),
+        artist: tag("artist"),
+        album: tag("album"),
+        genre: tag("genre"),
+        year: tag("date").or_else(|| tag("year")),
+    };
+
+    let any = [
+        &metadata.title,
+        &metadata.artist,
+        &metadata.album,
+        &metadata.genre,
+        &metadata.year,
+    ]
+    .iter()
+    .any(|v| v.is_some());
+    any.then_some(metadata)
+}
+
+fn stream_tag(stream: &serde_json::Value, name: &str) -> Option<String> {
+    stream
+        .get("tags")
+        .and_then(|t| t.get(name))
+        .and_then(|v| v.as_str())
+        .map(|s| s.to_string())
+}
+
 fn parse_framerate(fps_str: &str) -> f64 {
     let parts: Vec<&str> = fps_str.split('/').collect();
     if parts.len() == 2 {
@@ -185,3 +664,203 @@ fn parse_framerate(fps_str: &str) -> f64 {
     }
     0.0
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn probed(format_name: &str, audio_codec: &str) -> MediaInfo {
+        MediaInfo {
+            media_type: MediaType::Audio,
+            duration: 10.0,
+            file_size: 0,
+            format_name: format_name.to_string(),
+            bitrate: None,
+            video_streams: Vec::new(),
+            audio_streams: vec![AudioStream {
+                codec: audio_codec.to_string(),
+                sample_rate: 44100,
+                channels: 2,
+                bitrate: None,
+                language: None,
+                title: None,
+            }],
+            subtitle_streams: Vec::new(),
+            attachments: Vec::new(),
+            tags: None,
+            chapter_count: 0,
+            mismatch_warning: None,
+        }
+    }
+
+    #[test]
+    fn test_probe_cache() {
+        let key = |name: &str, size: u64| ProbeKey {
+            path: PathBuf::from(name),
+            modified: SystemTime::UNIX_EPOCH,
+            size,
+        };
+        let mut cache = ProbeCache::default();
+        cache.insert(key("a.mp3", 1), probed("mp3", "mp3"));
+        assert_eq!(cache.get(&key("a.mp3", 1)).unwrap().format_name, "mp3");
+
+        // Changed file: miss, and the stale entry is gone
+        assert!(cache.get(&key("a.mp3", 2)).is_none());
+        assert!(cache.entries.is_empty());
+
+        for i in 0..=PROBE_CACHE_CAPACITY {
+            cache.insert(key(&format!("{}.ogg", i), 1), probed("ogg", "vorbis"));
+            // Keep the first one recently used
+            cache.get(&key("0.ogg", 1));
+        }
+        assert_eq!(cache.entries.len(), PROBE_CACHE_CAPACITY);
+        assert!(cache.get(&key("0.ogg", 1)).is_some());
+        assert!(cache.get(&key("1.ogg", 1)).is_none());
+    }
+
+    #[test]
+    fn test_container_mismatch() {
+        let mp4 = probed("mov,mp4,m4a,3gp,3g2,mj2", "aac");
+        assert_eq!(container_mismatch("song.m4a", &mp4), None);
+        assert_eq!(container_mismatch("clip.MOV", &mp4), None);
+        assert!(container_mismatch("clip.avi", &mp4)
+            .unwrap()
+            .contains("MP4/MOV"));
+
+        // Raw ADTS renamed to .mp3
+        let adts = probed("aac", "aac");
+        assert!(container_mismatch("song.mp3", &adts).is_some());
+
+        // Right container, wrong codec
+        let vorbis = probed("ogg", "vorbis");
+        assert_eq!(container_mismatch("song.ogg", &vorbis), None);
+        assert!(container_mismatch("song.opus", &vorbis)
+            .unwrap()
+            .contains("VORBIS"));
+
+        assert_eq!(container_mismatch("song.xyz", &vorbis), None);
+        assert_eq!(container_mismatch("no_extension", &vorbis), None);
+    }
+
+    #[test]
+    fn test_parse_tags() {
+        let tags = serde_json::json!({
+            "TITLE": "Song",
+            "artist": "Band",
+            "date": "2019",
+            "encoder": "Lavf60.3.100",
+            "album": "  ",
+        });
+        let metadata = parse_tags(Some(&tags)).unwrap();
+        assert_eq!(metadata.title.as_deref(), Some("Song"));
+        assert_eq!(metadata.artist.as_deref(), Some("Band"));
+        assert_eq!(metadata.year.as_deref(), Some("2019"));
+        assert_eq!(metadata.album, None);
+
+        let technical_only = serde_json::json!({ "encoder": "Lavf60.3.100" });
+        assert!(parse_tags(Some(&technical_only)).is_none());
+        assert!(parse_tags(None).is_none());
+    }
+
+    #[test]
+    fn test_parse_color_tags() {
+        let stream = serde_json::json!({
+            "codec_name": "hevc",
+            "width": 3840,
+            "height": 2160,
+            "r_frame_rate": "24000/1001",
+            "color_transfer": "smpte2084",
+            "color_primaries": "bt2020",
+            "color_space": "unknown",
+        });
+        let video = parse_video_stream(&stream).unwrap();
+        assert_eq!(video.color_transfer.as_deref(), Some("smpte2084"));
+        assert_eq!(video.color_space, None);
+        assert!(video.is_hdr());
+
+        assert!(!is_hdr(Some("bt709"), Some("bt709")));
+        assert!(is_hdr(Some("arib-std-b67"), None));
+    }
+
+    #[test]
+    fn test_display_rotation() {
+        let stream = |extra: serde_json::Value| {
+            let mut stream = serde_json::json!({
+                "codec_name": "h264",
+                "width": 1920,
+                "height": 1080,
+                "r_frame_rate": "30/1",
+            });
+            stream
+                .as_object_mut()
+                .unwrap()
+                .extend(extra.as_object().unwrap().clone());
+            parse_video_stream(&stream).unwrap().rotation
+        };
+
+        // Portrait phone clip: stored landscape, displayed turned a quarter clockwise
+        let matrix = serde_json::json!({ "side_data_list": [
+            { "side_data_type": "Display Matrix", "rotation": -90 }
+        ]});
+        assert_eq!(stream(matrix), Some(90));
+        let upside_down = serde_json::json!({ "side_data_list": [
+            { "side_data_type": "Display Matrix", "rotation": 180 }
+        ]});
+        assert_eq!(stream(upside_down), Some(180));
+        assert_eq!(
+            stream(serde_json::json!({ "tags": { "rotate": "270" } })),
+            Some(270)
+        );
+        assert_eq!(
+            stream(serde_json::json!({ "tags": { "rotate": "0" } })),
+            None
+        );
+        assert_eq!(stream(serde_json::json!({})), None);
+    }
+
+    #[test]
+    fn test_container_bitrate_fallback() {
+        let probe = |streams: serde_json::Value| {
+            serde_json::json!({
+                "format": { "duration": "100.0", "bit_rate": "8000000" },
+                "streams": streams,
+            })
+        };
+        let video = serde_json::json!({
+            "codec_type": "video", "codec_name": "h264",
+            "width": 1920, "height": 1080, "r_frame_rate": "24/1",
+        });
+        let audio = |bit_rate: Option<&str>| {
+            let mut stream = serde_json::json!({
+                "codec_type": "audio", "codec_name": "aac",
+                "sample_rate": "48000", "channels": 2,
+            });
+            if let Some(b) = bit_rate {
+                stream["bit_rate"] = b.into();
+            }
+            stream
+        };
+
+        // Single stream: all of it
+        let info = parse_probe_result(&probe(serde_json::json!([video])), 0).unwrap();
+        assert_eq!(info.bitrate, Some(8_000_000));
+        assert_eq!(info.video_streams[0].bitrate, Some(8_000_000));
+
+        // The one unknown stream gets what the others leave
+        let info = parse_probe_result(&probe(serde_json::json!([video, audio(Some("192000"))])), 0)
+            .unwrap();
+        assert_eq!(info.video_streams[0].bitrate, Some(7_808_000));
+
+        // Two unknown: no telling how it splits
+        let info = parse_probe_result(&probe(serde_json::json!([video, audio(None)])), 0).unwrap();
+        assert_eq!(info.video_streams[0].bitrate, None);
+        assert_eq!(info.audio_streams[0].bitrate, None);
+
+        // mkvmerge's statistics tag counts as the stream's own
+        let mut tagged = video.clone();
+        tagged["tags"] = serde_json::json!({ "BPS": "7500000" });
+        let info = parse_probe_result(&probe(serde_json::json!([tagged, audio(None)])), 0).unwrap();
+        assert_eq!(info.video_streams[0].bitrate, Some(7_500_000));
+        assert_eq!(info.audio_streams[0].bitrate, Some(500_000));
+    }
+}
@@ -0,0 +1,217 @@
+//! Named, reusable conversion settings.
+//!
+//! User profiles are kept in `profiles.json` in the app config directory.
+//! Built-in profiles are defined here, always listed first, and can't be
+//! overwritten or deleted.
+
+use crate::types::{ChannelLayout, ConversionSettings, Quality, ScaleMode};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+const PROFILES_FILE: &str = "profiles.json";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Profile {
+    pub name: String,
+    /// Output format extension (`mp4`, `mp3`, ...)
+    pub format: String,
+    pub settings: ConversionSettings,
+    #[serde(default)]
+    pub builtin: bool,
+}
+
+/// Profiles shipped with the app.
+pub fn builtin_profiles() -> Vec<Profile> {
+    let builtin = |name: &str, format: &str, settings| Profile {
+        name: name.to_string(),
+        format: format.to_string(),
+        settings,
+        builtin: true,
+    };
+
+    vec![
+        builtin(
+            "YouTube 1080p",
+            "mp4",
+            ConversionSettings {
+                quality: Quality::High,
+                width: Some(1920),
+                height: Some(1080),
+                scale_mode: Some(ScaleMode::Fit),
+                video_codec: Some("libx264".to_string()),
+                audio_codec: Some("aac".to_string()),
+                ..Default::default()
+            },
+        ),
+        builtin(
+            "Web MP4",
+            "mp4",
+            ConversionSettings {
+                width: Some(1280),
+                video_codec: Some("libx264".to_string()),
+                audio_codec: Some("aac".to_string()),
+                ..Default::default()
+            },
+        ),
+        builtin(
+            "Podcast MP3",
+            "mp3",
+            ConversionSettings {
+                audio_bitrate: Some(96),
+                sample_rate: Some(44100),
+                channel_layout: Some(ChannelLayout::Mono),
+                normalize_loudness: true,
+                loudness_target: Some(-16.0),
+                ..Default::default()
+            },
+        ),
+    ]
+}
+
+/// Built-in profiles followed by the user's, in the order they were saved.
+pub fn list(config_dir: &Path) -> Result<Vec<Profile>> {
+    let mut profiles = builtin_profiles();
+    profiles.extend(read_user_profiles(config_dir)?);
+    Ok(profiles)
+}
+
+/// The profile called `name` (case-insensitive).
+pub fn load(config_dir: &Path, name: &str) -> Result<Profile> {
+    list(config_dir)?
+        .into_iter()
+        .find(|p| p.name.eq_ignore_ascii_case(name.trim()))
+        .with_context(|| format!("Profile '{}' not found", name))
+}
+
+/// Store `settings` under `name`, replacing a user profile of the same name.
+pub fn save(
+    config_dir: &Path,
+    name: &str,
+    format: &str,
+    mut settings: ConversionSettings,
+) -> Result<Profile> {
+    let name = name.trim();
+    if name.is_empty() {
+        anyhow::bail!("Profile name can't be empty");
+    }
+    if is_builtin(name) {
+        anyhow::bail!("'{}' is a built-in profile and can't be replaced", name);
+    }
+
+    // Per-run state, not part of a reusable setup
+    settings.task_id = None;
+    let profile = Profile {
+        name: name.to_string(),
+        format: format.to_lowercase(),
+        settings,
+        builtin: false,
+    };
+
+    let mut profiles = read_user_profiles(config_dir)?;
+    match profiles
+        .iter_mut()
+        .find(|p| p.name.eq_ignore_ascii_case(name))
+    {
+        Some(existing) => *existing = profile.clone(),
+        None => profiles.push(profile.clone()),
+    }
+    write_user_profiles(config_dir, &profiles)?;
+
+    tracing::info!(profile = %name, "Saved conversion profile");
+    Ok(profile)
+}
+
+/// Remove the user profile called `name`. Returns whether it existed.
+pub fn delete(config_dir: &Path, name: &str) -> Result<bool> {
+    if is_builtin(name.trim()) {
+        anyhow::bail!("'{}' is a built-in profile and can't be deleted", name);
+    }
+
+    let mut profiles = read_user_profiles(config_dir)?;
+    let before = profiles.len();
+    profiles.retain(|p| !p.name.eq_ignore_ascii_case(name.trim()));
+    if profiles.len() == before {
+        return Ok(false);
+    }
+    write_user_profiles(config_dir, &profiles)?;
+    Ok(true)
+}
+
+fn is_builtin(name: &str) -> bool {
+    builtin_profiles()
+        .iter()
+        .any(|p| p.name.eq_ignore_ascii_case(name))
+}
+
+fn profiles_path(config_dir: &Path) -> PathBuf {
+    config_dir.join(PROFILES_FILE)
+}
+
+fn read_user_profiles(config_dir: &Path) -> Result<Vec<Profile>> {
+    let path = profiles_path(config_dir);
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let json = std::fs::read_to_string(&path).context("Failed to read profiles")?;
+    let mut profiles: Vec<Profile> =
+        serde_json::from_str(&json).context("Profiles file is corrupted")?;
+    for profile in &mut profiles {
+        profile.builtin = false;
+    }
+    Ok(profiles)
+}
+
+/// Write through a temporary file so a crash never leaves a half-written list.
+fn write_user_profiles(config_dir: &Path, profiles: &[Profile]) -> Result<()> {
+    std::fs::create_dir_all(config_dir).context("Failed to create config directory")?;
+    let path = profiles_path(config_dir);
+    let tmp = path.with_extension("json.tmp");
+
+    let json = serde_json::to_string_pretty(profiles)?;
+    std::fs::write(&tmp, json).context("Failed to write profiles")?;
+    std::fs::rename(&tmp, &path).context("Failed to write profiles")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_save_load_delete() {
+        let dir = std::env::temp_dir().join(format!("muxolotl-profiles-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+
+        assert_eq!(list(&dir).unwrap().len(), builtin_profiles().len());
+        assert!(save(&dir, "web mp4", "mp4", ConversionSettings::default()).is_err());
+        assert!(save(&dir, "  ", "mp4", ConversionSettings::default()).is_err());
+
+        let settings = ConversionSettings {
+            task_id: Some("task".to_string()),
+            crf: Some(20),
+            ..Default::default()
+        };
+        save(&dir, "Archive", "MKV", settings).unwrap();
+        let loaded = load(&dir, "archive").unwrap();
+        assert_eq!(loaded.format, "mkv");
+        assert_eq!(loaded.settings.crf, Some(20));
+        assert_eq!(loaded.settings.task_id, None);
+        assert!(!loaded.builtin);
+
+        // Same name replaces instead of duplicating
+        save(&dir, "ARCHIVE", "mkv", ConversionSettings::default()).unwrap();
+        let profiles = list(&dir).unwrap();
+        assert_eq!(profiles.len(), builtin_profiles().len() + 1);
+        assert_eq!(profiles.last().unwrap().settings.crf, None);
+
+        assert!(load(&dir, "YouTube 1080p").unwrap().builtin);
+        assert!(delete(&dir, "Podcast MP3").is_err());
+        assert!(delete(&dir, "archive").unwrap());
+        assert!(!delete(&dir, "archive").unwrap());
+        assert!(load(&dir, "archive").is_err());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,158 @@
+//! Silence detection and trimming.
+//!
+//! Trimming uses `silenceremove` on the start of the audio only: its
+//! `stop_periods` option cuts at the first pause anywhere in the file, not just
+//! at the end. Trailing silence is removed by reversing the audio, trimming its
+//! start and reversing back, which buffers the whole track in memory — fine for
+//! voiceovers, heavy for hour-long recordings.
+
+use crate::utils::{create_async_hidden_command, null_device};
+use anyhow::{Context, Result};
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde::Serialize;
+
+/// Level below which audio counts as silence.
+const SILENCE_THRESHOLD_DB: f64 = -50.0;
+/// Shortest pause `detect` reports; shorter gaps are natural speech rhythm.
+const MIN_DETECTED_SECONDS: f64 = 0.5;
+
+lazy_static! {
+    static ref SILENCE_START_REGEX: Regex = Regex::new(r"silence_start: (-?[\d.]+)").unwrap();
+    static ref SILENCE_END_REGEX: Regex = Regex::new(r"silence_end: (-?[\d.]+)").unwrap();
+}
+
+/// A stretch of silence in the source, in seconds.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct SilenceRange {
+    pub start: f64,
+    pub end: f64,
+}
+
+/// Filter chain removing leading and trailing silence.
+pub fn trim_filter() -> String {
+    let trim_start = format!(
+        "silenceremove=start_periods=1:start_threshold={}dB",
+        SILENCE_THRESHOLD_DB
+    );
+    format!("{0},areverse,{0},areverse", trim_start)
+}
+
+/// Find the silent stretches in audio track `audio_track` of `input`.
+/// `duration` closes a silence still open when the audio ends.
+pub async fn detect(
+    ffmpeg_path: &str,
+    input: &str,
+    audio_track: usize,
+    duration: f64,
+) -> Result<Vec<SilenceRange>> {
+    let filter = format!(
+        "silencedetect=noise={}dB:d={}",
+        SILENCE_THRESHOLD_DB, MIN_DETECTED_SECONDS
+    );
+    let track = format!("0:a:{}", audio_track);
+
+    let output = create_async_hidden_command(ffmpeg_path)
+        .args([
+            "-hide_banner",
+            "-nostats",
+            "-i",
+            input,
+            "-map",
+            &track,
+            "-af",
+            &filter,
+            "-f",
+            "null",
+            null_device(),
+        ])
+        .output()
+        .await
+        .context("Failed to run silence detection")?;
+
+    if !output.status.success() {
+        anyhow::bail!("Silence detection failed with code: {}", output.status);
+    }
+
+    Ok(parse_silencedetect_output(
+        &String::from_utf8_lossy(&output.stderr),
+        duration,
+    ))
+}
+
+/// Pair up the `silence_start` / `silence_end` lines `silencedetect` logs.
+fn parse_silencedetect_output(stderr: &str, duration: f64) -> Vec<SilenceRange> {
+    let mut ranges = Vec::new();
+    let mut open: Option<f64> = None;
+
+    for line in stderr.lines() {
+        if let Some(start) = SILENCE_START_REGEX
+            .captures(line)
+            .and_then(|c| c[1].parse::<f64>().ok())
+        {
+            // Leading silence can be reported slightly before zero
+            open = Some(start.max(0.0));
+        } else if let Some(end) = SILENCE_END_REGEX
+            .captures(line)
+            .and_then(|c| c[1].parse::<f64>().ok())
+        {
+            if let Some(start) = open.take() {
+                ranges.push(SilenceRange { start, end });
+            }
+        }
+    }
+
+    // Older builds don't log an end for silence running to the end of the file
+    if let Some(start) = open.filter(|&s| duration > s) {
+        ranges.push(SilenceRange {
+            start,
+            end: duration,
+        });
+    }
+
+    ranges
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_silencedetect_output() {
+        let stderr = "\
+[silencedetect @ 0x7f8] silence_start: -0.0213
+[silencedetect @ 0x7f8] silence_end: 1.2504 | silence_duration: 1.2717
+size=N/A time=00:00:05.00 bitrate=N/A speed= 400x
+[silencedetect @ 0x7f8] silence_start: 3.5
+[silencedetect @ 0x7f8] silence_end: 4.25 | silence_duration: 0.75
+[silencedetect @ 0x7f8] silence_start: 9.1
+";
+        assert_eq!(
+            parse_silencedetect_output(stderr, 10.0),
+            vec![
+                SilenceRange {
+                    start: 0.0,
+                    end: 1.2504
+                },
+                SilenceRange {
+                    start: 3.5,
+                    end: 4.25
+                },
+                SilenceRange {
+                    start: 9.1,
+                    end: 10.0
+                },
+            ]
+        );
+        assert!(parse_silencedetect_output("no silence here", 10.0).is_empty());
+    }
+
+    #[test]
+    fn test_trim_filter() {
+        assert_eq!(
+            trim_filter(),
+            "silenceremove=start_periods=1:start_threshold=-50dB,areverse,\
+             silenceremove=start_periods=1:start_threshold=-50dB,areverse"
+        );
+    }
+}
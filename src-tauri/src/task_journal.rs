@@ -0,0 +1,197 @@
+//! On-disk list of running conversions.
+//!
+//! `AppState::active_processes` only lives as long as the app, so each task
+//! is also written to `active_tasks.json` in the config directory when FFmpeg
+//! is spawned and dropped again when it ends. Whatever is still listed at
+//! startup was interrupted by a crash or a forced quit; those tasks are kept
+//! as orphans until the user cleans up their partial output or keeps it.
+
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const JOURNAL_FILE: &str = "active_tasks.json";
+
+lazy_static! {
+    static ref JOURNAL: Mutex<Journal> = Mutex::new(Journal::default());
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JournalEntry {
+    pub task_id: String,
+    pub output: String,
+    /// Unix time in seconds
+    pub started_at: u64,
+}
+
+/// A task from an earlier run that never reported an outcome.
+#[derive(Debug, Clone, Serialize)]
+pub struct OrphanedTask {
+    #[serde(flatten)]
+    pub entry: JournalEntry,
+    /// Size of the partial output, if it is still on disk
+    pub output_size: Option<u64>,
+}
+
+#[derive(Default)]
+struct Journal {
+    /// Unset until `init`, or when the config directory is unavailable
+    path: Option<PathBuf>,
+    running: Vec<JournalEntry>,
+    orphaned: Vec<JournalEntry>,
+}
+
+impl Journal {
+    /// Everything listed in `path` is an orphan: nothing is running yet.
+    fn load(path: PathBuf) -> Self {
+        let orphaned = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default();
+        Self {
+            path: Some(path),
+            running: Vec::new(),
+            orphaned,
+        }
+    }
+
+    fn record(&mut self, entry: JournalEntry) {
+        self.running.retain(|e| e.task_id != entry.task_id);
+        self.running.push(entry);
+        self.save();
+    }
+
+    fn finish(&mut self, task_id: &str) {
+        let before = self.running.len();
+        self.running.retain(|e| e.task_id != task_id);
+        if self.running.len() != before {
+            self.save();
+        }
+    }
+
+    fn take_orphan(&mut self, task_id: &str) -> Option<JournalEntry> {
+        let index = self.orphaned.iter().position(|e| e.task_id == task_id)?;
+        let entry = self.orphaned.remove(index);
+        self.save();
+        Some(entry)
+    }
+
+    /// Orphans stay listed until resolved, so a second crash doesn't lose them.
+    fn save(&self) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        let entries: Vec<&JournalEntry> = self.orphaned.iter().chain(&self.running).collect();
+        let result = path
+            .parent()
+            .map_or(Ok(()), std::fs::create_dir_all)
+            .and_then(|_| std::fs::write(path, serde_json::to_string(&entries)?));
+        if let Err(e) = result {
+            tracing::warn!(error = %e, "Failed to save active task list");
+        }
+    }
+}
+
+/// Load the list left by the previous run. Call once at startup, before any
+/// conversion starts.
+pub fn init(config_dir: &Path) {
+    let journal = Journal::load(config_dir.join(JOURNAL_FILE));
+    if !journal.orphaned.is_empty() {
+        tracing::warn!(
+            count = journal.orphaned.len(),
+            "Found conversions interrupted in an earlier run"
+        );
+    }
+    *JOURNAL.lock().unwrap_or_else(|e| e.into_inner()) = journal;
+}
+
+/// Note a task whose FFmpeg process is about to start writing `output`.
+pub fn record(task_id: &str, output: &str) {
+    let started_at = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    with_journal(|journal| {
+        journal.record(JournalEntry {
+            task_id: task_id.to_string(),
+            output: output.to_string(),
+            started_at,
+        })
+    });
+}
+
+/// Drop a task that ended, whatever the outcome.
+pub fn finish(task_id: &str) {
+    with_journal(|journal| journal.finish(task_id));
+}
+
+/// Tasks interrupted in an earlier run, with what is left of their output.
+pub fn orphans() -> Vec<OrphanedTask> {
+    with_journal(|journal| journal.orphaned.clone())
+        .into_iter()
+        .map(|entry| OrphanedTask {
+            output_size: std::fs::metadata(&entry.output).ok().map(|m| m.len()),
+            entry,
+        })
+        .collect()
+}
+
+/// Forget an orphaned task, deleting its partial output when `remove_output`
+/// is set (otherwise the file is kept, e.g. to convert it again).
+pub fn resolve_orphan(task_id: &str, remove_output: bool) -> anyhow::Result<()> {
+    let entry = with_journal(|journal| journal.take_orphan(task_id))
+        .ok_or_else(|| anyhow::anyhow!("No interrupted conversion with id '{}'", task_id))?;
+
+    let output = Path::new(&entry.output);
+    if remove_output && output.exists() {
+        std::fs::remove_file(output)?;
+        tracing::info!(task_id = %task_id, output = %entry.output, "Removed partial output");
+    }
+    Ok(())
+}
+
+fn with_journal<T>(f: impl FnOnce(&mut Journal) -> T) -> T {
+    f(&mut JOURNAL.lock().unwrap_or_else(|e| e.into_inner()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(task_id: &str) -> JournalEntry {
+        JournalEntry {
+            task_id: task_id.to_string(),
+            output: format!("/out/{}.mp4", task_id),
+            started_at: 0,
+        }
+    }
+
+    #[test]
+    fn test_unfinished_tasks_become_orphans() {
+        let dir = std::env::temp_dir().join(format!("muxolotl-journal-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let path = dir.join(JOURNAL_FILE);
+
+        let mut journal = Journal::load(path.clone());
+        journal.record(entry("done"));
+        journal.record(entry("crashed"));
+        journal.finish("done");
+
+        // Next launch: only the unfinished task is left, and nothing is running
+        let mut journal = Journal::load(path.clone());
+        assert_eq!(journal.orphaned, vec![entry("crashed")]);
+        assert!(journal.running.is_empty());
+
+        // Orphans survive until resolved, even across another crash
+        journal.record(entry("new"));
+        let mut journal = Journal::load(path.clone());
+        assert_eq!(journal.orphaned.len(), 2);
+        assert_eq!(journal.take_orphan("crashed"), Some(entry("crashed")));
+        assert_eq!(journal.take_orphan("crashed"), None);
+        assert_eq!(Journal::load(path).orphaned, vec![entry("new")]);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,121 @@
+//! Registry of temporary artifacts created during conversions (pass logs,
+//! palettes, concat lists, partial outputs).
+//!
+//! Entries are persisted next to the artifacts so files left behind by a crash
+//! are still known on the next launch, when everything not owned by a running
+//! task is removed.
+
+use anyhow::{Context, Result};
+use lazy_static::lazy_static;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+const REGISTRY_FILE: &str = "registry.json";
+
+type Registry = HashMap<String, Vec<PathBuf>>;
+
+lazy_static! {
+    static ref REGISTRY: Mutex<Option<Registry>> = Mutex::new(None);
+}
+
+/// Root directory for all of the app's scratch files.
+pub fn temp_root() -> PathBuf {
+    std::env::temp_dir().join("muxolotl-converter")
+}
+
+/// Create (and register) the per-task scratch directory.
+pub fn task_dir(task_id: &str) -> Result<PathBuf> {
+    let dir = temp_root().join(task_id);
+    std::fs::create_dir_all(&dir).context("Failed to create temp directory")?;
+    register(task_id, &dir);
+    Ok(dir)
+}
+
+/// Record an artifact so it is removed when the task ends, or on the next
+/// startup if the app dies first.
+pub fn register(task_id: &str, path: &Path) {
+    with_registry(|registry| {
+        let paths = registry.entry(task_id.to_string()).or_default();
+        if !paths.iter().any(|p| p == path) {
+            paths.push(path.to_path_buf());
+        }
+    });
+}
+
+/// Remove whatever the task registered and forget it. Called when a task ends,
+/// whatever the outcome.
+pub fn finish(task_id: &str) {
+    let paths = with_registry(|registry| registry.remove(task_id)).unwrap_or_default();
+    for path in paths {
+        remove_path(&path);
+    }
+}
+
+/// Remove artifacts of tasks that aren't running, plus unknown leftovers in
+/// the temp root. Returns how many paths were removed.
+pub fn cleanup_stale(active_tasks: &HashSet<String>) -> usize {
+    let stale = with_registry(|registry| {
+        let stale: Vec<PathBuf> = registry
+            .iter()
+            .filter(|(task, _)| !active_tasks.contains(*task))
+            .flat_map(|(_, paths)| paths.clone())
+            .collect();
+        registry.retain(|task, _| active_tasks.contains(task));
+        stale
+    });
+
+    let mut removed = stale.into_iter().filter(|p| remove_path(p)).count();
+
+    // Task directories from before the registry was written
+    if let Ok(entries) = std::fs::read_dir(temp_root()) {
+        for entry in entries.flatten() {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if name != REGISTRY_FILE && !active_tasks.contains(&name) && remove_path(&entry.path())
+            {
+                removed += 1;
+            }
+        }
+    }
+
+    if removed > 0 {
+        tracing::info!(removed, "Removed stale temp files");
+    }
+    removed
+}
+
+fn with_registry<T>(f: impl FnOnce(&mut Registry) -> T) -> T {
+    let mut guard = REGISTRY.lock().unwrap_or_else(|e| e.into_inner());
+    let registry = guard.get_or_insert_with(load);
+    let result = f(registry);
+    save(registry);
+    result
+}
+
+fn load() -> Registry {
+    std::fs::read_to_string(temp_root().join(REGISTRY_FILE))
+        .ok()
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default()
+}
+
+fn save(registry: &Registry) {
+    let result = std::fs::create_dir_all(temp_root()).and_then(|_| {
+        let json = serde_json::to_string(registry).unwrap_or_default();
+        std::fs::write(temp_root().join(REGISTRY_FILE), json)
+    });
+    if let Err(e) = result {
+        tracing::warn!(error = %e, "Failed to save temp file registry");
+    }
+}
+
+fn remove_path(path: &Path) -> bool {
+    let result = if path.is_dir() {
+        std::fs::remove_dir_all(path)
+    } else if path.exists() {
+        std::fs::remove_file(path)
+    } else {
+        return false;
+    };
+    result.is_ok()
+}
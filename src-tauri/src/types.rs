@@ -1,3 +1,4 @@
+use crate::loudness::{self, LoudnessStats};
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Default)]
@@ -32,6 +33,39 @@ impl Quality {
         }
     }
 
+    /// libwebp `-quality` (0–100, higher is better).
+    pub fn webp_quality(&self) -> &'static str {
+        match self {
+            Quality::Low => "60",
+            Quality::Medium => "75",
+            Quality::High => "85",
+            Quality::Ultra => "95",
+            Quality::Custom => "75",
+        }
+    }
+
+    /// libaom/SVT-AV1 `-crf` for AVIF stills (0-63, lower is better)
+    pub fn avif_crf(&self) -> &'static str {
+        match self {
+            Quality::Low => "40",
+            Quality::Medium => "32",
+            Quality::High => "24",
+            Quality::Ultra => "16",
+            Quality::Custom => "32",
+        }
+    }
+
+    /// libjxl `-distance` (Butteraugli distance, 0 = lossless, 1 = visually lossless)
+    pub fn jxl_distance(&self) -> &'static str {
+        match self {
+            Quality::Low => "3.0",
+            Quality::Medium => "2.0",
+            Quality::High => "1.0",
+            Quality::Ultra => "0.0",
+            Quality::Custom => "2.0",
+        }
+    }
+
     pub fn video_preset(&self) -> &'static str {
         match self {
             Quality::Low => "veryfast",
@@ -43,6 +77,306 @@ impl Quality {
     }
 }
 
+/// Target playback device. Bundles the container-level tweaks that stubborn
+/// hardware players need beyond codec choice.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum DeviceProfile {
+    /// Feature phones and early smartphones
+    OldPhone,
+    /// Older standalone players / set-top boxes that only accept MPEG-4 v1 files
+    LegacyPlayer,
+    /// Smart TVs and current devices
+    Modern,
+}
+
+impl DeviceProfile {
+    /// MP4 `major_brand` the device expects.
+    pub fn mp4_brand(&self) -> &'static str {
+        match self {
+            DeviceProfile::OldPhone => "mp42",
+            DeviceProfile::LegacyPlayer => "mp41",
+            DeviceProfile::Modern => "isom",
+        }
+    }
+}
+
+/// Which Opus encoder to use when a build has both.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+pub enum OpusEncoder {
+    /// libopus — mature, full feature set
+    #[default]
+    #[serde(rename = "libopus")]
+    Libopus,
+    /// FFmpeg's built-in encoder — experimental, 48 kHz only, no external library
+    #[serde(rename = "opus")]
+    Native,
+}
+
+impl OpusEncoder {
+    pub fn encoder_name(&self) -> &'static str {
+        match self {
+            OpusEncoder::Libopus => "libopus",
+            OpusEncoder::Native => "opus",
+        }
+    }
+}
+
+/// What libopus tunes its encoding for (`-application`).
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum OpusApplication {
+    /// Speech: much clearer voice at low bitrates
+    Voip,
+    /// Music and general content
+    #[default]
+    Audio,
+    /// Lowest algorithmic delay, for live use
+    #[serde(rename = "lowdelay")]
+    LowDelay,
+}
+
+impl OpusApplication {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OpusApplication::Voip => "voip",
+            OpusApplication::Audio => "audio",
+            OpusApplication::LowDelay => "lowdelay",
+        }
+    }
+}
+
+/// Suffix of in-progress output files unless the settings pick another one.
+pub const DEFAULT_PARTIAL_SUFFIX: &str = "part";
+
+/// Frame durations libopus accepts, in milliseconds.
+pub const OPUS_FRAME_DURATIONS: [f64; 6] = [2.5, 5.0, 10.0, 20.0, 40.0, 60.0];
+
+/// Explicit output channel layout. Unlike a bare channel count, going down
+/// to a layout from a wider source always rematrixes with proper mix levels.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ChannelLayout {
+    Mono,
+    Stereo,
+    #[serde(rename = "5.1")]
+    Surround51,
+}
+
+impl ChannelLayout {
+    pub fn channels(&self) -> u32 {
+        match self {
+            ChannelLayout::Mono => 1,
+            ChannelLayout::Stereo => 2,
+            ChannelLayout::Surround51 => 6,
+        }
+    }
+}
+
+/// `eq` colour controls as (name, neutral value, min, max), in the ranges
+/// FFmpeg accepts. The names double as the setting names.
+pub const COLOR_ADJUSTMENTS: [(&str, f64, f64, f64); 4] = [
+    ("brightness", 0.0, -1.0, 1.0),
+    ("contrast", 1.0, -1000.0, 1000.0),
+    ("saturation", 1.0, 0.0, 3.0),
+    ("gamma", 1.0, 0.1, 10.0),
+];
+
+/// Analog TV system that fixes the frame size and rate of DVD/VOB output.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum TvStandard {
+    /// 720×480 at 29.97 fps (North America, Japan)
+    Ntsc,
+    /// 720×576 at 25 fps (Europe, most of Asia and Africa)
+    Pal,
+}
+
+impl TvStandard {
+    /// Best guess from the source: up to 480 lines is NTSC material.
+    pub fn from_source_height(height: u32) -> Self {
+        if height <= 480 {
+            TvStandard::Ntsc
+        } else {
+            TvStandard::Pal
+        }
+    }
+
+    pub fn height(&self) -> u32 {
+        match self {
+            TvStandard::Ntsc => 480,
+            TvStandard::Pal => 576,
+        }
+    }
+
+    pub fn frame_rate(&self) -> &'static str {
+        match self {
+            TvStandard::Ntsc => "30000/1001",
+            TvStandard::Pal => "25",
+        }
+    }
+}
+
+/// Filter used to turn interlaced fields into progressive frames.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Deinterlacer {
+    /// Fast, good enough for most sources
+    Yadif,
+    /// Slower, fewer artifacts on fine detail and motion
+    Bwdif,
+}
+
+impl Deinterlacer {
+    /// One output frame per input frame, so the frame rate is unchanged.
+    pub fn filter(&self) -> &'static str {
+        match self {
+            Deinterlacer::Yadif => "yadif=mode=send_frame",
+            Deinterlacer::Bwdif => "bwdif=mode=send_frame",
+        }
+    }
+}
+
+/// How the video encoder spends bits.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum RateControl {
+    /// Constant quality from the preset or `crf`; `video_bitrate` only caps it
+    #[default]
+    Crf,
+    /// Average of `video_bitrate`, varying with scene complexity
+    Vbr,
+    /// `video_bitrate` held steady, as streaming ingest (RTMP) expects
+    Cbr,
+}
+
+/// What `keyframe_interval` counts.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum KeyframeUnit {
+    #[default]
+    Frames,
+    /// Converted to frames at the output frame rate
+    Seconds,
+}
+
+/// What to do when the output file already exists.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum CollisionPolicy {
+    /// Replace the existing file
+    #[default]
+    Overwrite,
+    /// Write to `name (1).ext`, `name (2).ext`, ... instead
+    Rename,
+    /// Stop with an error
+    Fail,
+}
+
+/// Scheduling priority of the FFmpeg process. How much this does depends on
+/// the platform: Windows starts FFmpeg in the below / above normal priority
+/// class, while Unix can only lower it (niceness 10) without root, so High
+/// runs at the default priority there.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ProcessPriority {
+    /// Yield the CPU to the desktop and other apps
+    Low,
+    #[default]
+    Normal,
+    /// Take the CPU ahead of other apps (Windows only)
+    High,
+}
+
+/// Mirror the picture along one axis.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Flip {
+    /// Left and right swapped
+    Horizontal,
+    /// Upside down
+    Vertical,
+}
+
+impl Flip {
+    pub fn filter(&self) -> &'static str {
+        match self {
+            Flip::Horizontal => "hflip",
+            Flip::Vertical => "vflip",
+        }
+    }
+}
+
+/// How to fit the picture into a target size with a different aspect ratio.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ScaleMode {
+    /// Exact target size; the picture is distorted
+    Stretch,
+    /// Whole picture inside the target, padded with black bars
+    Fit,
+    /// Target covered completely, overflow cropped
+    Fill,
+}
+
+/// Named output size, by the number of lines on the picture's short side.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub enum ResolutionPreset {
+    #[serde(rename = "480p")]
+    Sd480,
+    #[serde(rename = "720p")]
+    Hd720,
+    #[serde(rename = "1080p")]
+    Hd1080,
+    #[serde(rename = "4k")]
+    Uhd4k,
+}
+
+impl ResolutionPreset {
+    pub fn lines(&self) -> u32 {
+        match self {
+            ResolutionPreset::Sd480 => 480,
+            ResolutionPreset::Hd720 => 720,
+            ResolutionPreset::Hd1080 => 1080,
+            ResolutionPreset::Uhd4k => 2160,
+        }
+    }
+}
+
+/// Preset strength for the denoise and sharpen filters.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum FilterStrength {
+    #[default]
+    Off,
+    Light,
+    Medium,
+    Strong,
+}
+
+impl FilterStrength {
+    /// `hqdn3d` smoothing, spatial and temporal. Medium is the filter's own
+    /// default; strong starts to smear fine detail in motion.
+    pub fn denoise_filter(&self) -> Option<&'static str> {
+        match self {
+            FilterStrength::Off => None,
+            FilterStrength::Light => Some("hqdn3d=2:1.5:3:2.25"),
+            FilterStrength::Medium => Some("hqdn3d=4:3:6:4.5"),
+            FilterStrength::Strong => Some("hqdn3d=8:6:12:9"),
+        }
+    }
+
+    /// `unsharp` on luma only; sharpening chroma brings out colour noise.
+    pub fn sharpen_filter(&self) -> Option<&'static str> {
+        match self {
+            FilterStrength::Off => None,
+            FilterStrength::Light => Some("unsharp=5:5:0.5:5:5:0"),
+            FilterStrength::Medium => Some("unsharp=5:5:1.0:5:5:0"),
+            FilterStrength::Strong => Some("unsharp=7:7:1.5:7:7:0"),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct FileMetadata {
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -58,8 +392,16 @@ pub struct FileMetadata {
 }
 
 impl FileMetadata {
+    /// Replace the source metadata with these tags.
     pub fn to_ffmpeg_args(&self) -> Vec<String> {
         let mut args = vec!["-map_metadata".to_string(), "-1".to_string()];
+        args.extend(self.tag_args());
+        args
+    }
+
+    /// `-metadata key=value` pairs for the non-empty fields.
+    pub fn tag_args(&self) -> Vec<String> {
+        let mut args = Vec::new();
 
         let fields = [
             ("title", &self.title),
@@ -90,17 +432,131 @@ pub struct ConversionSettings {
     #[serde(default)]
     pub quality: Quality,
 
-    pub bitrate: Option<u32>,
+    /// Video bitrate in kbps
+    pub video_bitrate: Option<u32>,
+    /// Audio bitrate in kbps; the encoder's or format's default when unset
+    pub audio_bitrate: Option<u32>,
+    /// Deprecated single `bitrate` of older clients: the audio rate for
+    /// audio-only outputs, the video rate otherwise (see `resolve_legacy_bitrate`)
+    #[serde(rename = "bitrate", skip_serializing)]
+    pub legacy_bitrate: Option<u32>,
     pub sample_rate: Option<u32>,
     pub channels: Option<u32>,
+    /// Output layout; takes precedence over `channels` when set
+    pub channel_layout: Option<ChannelLayout>,
     pub width: Option<u32>,
     pub height: Option<u32>,
+    /// Aspect handling when both `width` and `height` are set, or the format
+    /// has a fixed frame size. When unset, fixed sizes are stretched and
+    /// otherwise only `width` is used, keeping the aspect ratio.
+    pub scale_mode: Option<ScaleMode>,
+    /// Output size as a percentage of the source (50 = half), when neither
+    /// `width` nor `height` is set
+    pub scale_percent: Option<f64>,
+    /// Output size by name (1080p = 1080 lines on the short side), when
+    /// neither `width`, `height` nor `scale_percent` is set
+    pub resolution_preset: Option<ResolutionPreset>,
+    /// Deinterlace the source first (see `VideoStream::is_interlaced`)
+    pub deinterlace: Option<Deinterlacer>,
+    /// Tone-map HDR sources down to 8-bit BT.709. Ignored for SDR sources.
+    #[serde(default)]
+    pub tonemap_sdr: bool,
+    /// Region of the source to keep, as (width, height, x, y) in source pixels.
+    /// Applied before scaling, so `width`/`height` resize the cropped picture.
+    pub crop: Option<(u32, u32, u32, u32)>,
+    /// Clockwise rotation in degrees (90, 180 or 270), applied after cropping.
+    /// `width`/`height` describe the rotated picture.
+    pub rotate: Option<u32>,
+    pub flip: Option<Flip>,
+    /// Encode the picture as stored instead of turning a rotation-tagged
+    /// source (phone footage) upright; the tag is carried over for players
+    #[serde(default)]
+    pub keep_orientation: bool,
+    /// The source's display rotation, set once it is probed unless
+    /// `keep_orientation`; FFmpeg turns such a source upright on decode.
+    /// Never read from the frontend's settings
+    #[serde(skip)]
+    pub source_rotation: Option<u32>,
+    /// Noise reduction, applied before scaling
+    #[serde(default)]
+    pub denoise: FilterStrength,
+    /// Sharpening, applied after scaling
+    #[serde(default)]
+    pub sharpen: FilterStrength,
+    /// Colour controls (see `COLOR_ADJUSTMENTS` for ranges); neutral when unset
+    pub brightness: Option<f64>,
+    pub contrast: Option<f64>,
+    pub saturation: Option<f64>,
+    pub gamma: Option<f64>,
     pub fps: Option<u32>,
+    /// Times an animated GIF/WebP plays; 0 or unset loops forever
+    pub loop_count: Option<u32>,
+    /// Force NTSC or PAL for fixed-resolution formats (DVD/VOB) instead of following the source
+    pub tv_standard: Option<TvStandard>,
     pub video_codec: Option<String>,
     pub audio_codec: Option<String>,
+    /// Which source audio track to keep (0-based, as listed in `MediaInfo::audio_streams`);
+    /// FFmpeg's default pick when unset
+    pub audio_track_index: Option<usize>,
+    /// Opus encoder to prefer (libopus by default); falls back to the other if missing
+    pub opus_encoder: Option<OpusEncoder>,
+    /// libopus tuning (`audio` when unset); ignored by other encoders
+    pub opus_application: Option<OpusApplication>,
+    /// libopus frame size in ms (one of `OPUS_FRAME_DURATIONS`); ignored by other encoders
+    pub opus_frame_duration: Option<f64>,
+    /// FLAC/WavPack compression level (0-12, higher is smaller and slower);
+    /// derived from `quality` when unset
+    pub compression_level: Option<u32>,
+
+    /// Constant-quality value (CRF / CQ / QP, lower is better) overriding the
+    /// quality preset. Clamped to what the chosen encoder accepts.
+    pub crf: Option<u32>,
+    /// Encoder speed preset overriding the one derived from `quality`, so
+    /// quality and encoding time can be set separately. x264/x265 take
+    /// `ultrafast`..`veryslow`, NVENC `p1`..`p7`; ignored by other encoders.
+    pub encoder_preset: Option<String>,
+    /// Video rate control; `Vbr` and `Cbr` target `video_bitrate` and ignore `crf`
+    #[serde(default)]
+    pub rate_control: RateControl,
+
+    /// Output pixel format (`yuv420p`, `yuv420p10le`, ...), overriding the
+    /// container default; ignored if the encoder can't take it
+    pub pixel_format: Option<String>,
+    /// Maximum consecutive B-frames (`-bf`). Software encoders only.
+    pub b_frames: Option<u32>,
+    /// Number of reference frames (`-refs`). Software encoders only.
+    pub ref_frames: Option<u32>,
+    /// Maximum distance between keyframes (`-g`); also the minimum for x264/x265
+    pub keyframe_interval: Option<u32>,
+    #[serde(default)]
+    pub keyframe_unit: KeyframeUnit,
+    /// Force a keyframe every this many seconds, for HLS/DASH segment boundaries
+    pub force_keyframes_seconds: Option<f64>,
+    /// Encoder thread count for CPU encoders; 0 or unset lets FFmpeg decide
+    pub threads: Option<u32>,
+
+    /// FFV1 slice count (4, 6, 9, 12, 16, 24 or 30); more slices = more parallelism
+    pub ffv1_slices: Option<u32>,
+
+    /// Two-pass encoding when a target bitrate is set (libx264/libx265/VP9 only)
+    #[serde(default)]
+    pub two_pass: bool,
 
     #[serde(default)]
     pub use_gpu: bool,
+    /// Hardware decoding, separately from encoding; follows `use_gpu` when
+    /// unset. Only takes effect together with a GPU encoder.
+    pub use_gpu_decode: Option<bool>,
+    /// Pick a hardware encoder; follows `use_gpu` when unset
+    pub use_gpu_encode: Option<bool>,
+    /// Which entry of `detect_all_gpus` to encode on; the best available GPU if unset
+    pub gpu_index: Option<usize>,
+    /// AMD AMF: encode at a constant QP from the quality preset instead of an
+    /// estimated bitrate when neither `video_bitrate` nor `crf` is set. Better
+    /// quality per byte, but needs Adrenalin 22.7 or newer (RDNA2/RDNA3);
+    /// older drivers can crash in constant-QP mode.
+    #[serde(default)]
+    pub amf_use_cqp: bool,
 
     #[serde(default)]
     pub copy_audio: bool,
@@ -108,7 +564,103 @@ pub struct ConversionSettings {
     #[serde(default)]
     pub extract_audio_only: bool,
 
+    /// Carry subtitle streams into the output (converted to mov_text for MP4)
+    #[serde(default)]
+    pub copy_subtitles: bool,
+    /// External subtitle file (SRT/ASS/...) to render permanently into the video
+    pub burn_subtitle_path: Option<String>,
+
+    /// After converting, decode the whole output once to catch corruption
+    /// (roughly doubles processing time)
+    #[serde(default)]
+    pub test_playback: bool,
+    /// Write a SHA-256 checksum of the output to a `<output>.sha256` sidecar
+    #[serde(default)]
+    pub compute_checksums: bool,
+    /// Save FFmpeg's full stderr to `<output>.log`, kept even when the
+    /// conversion fails
+    #[serde(default)]
+    pub write_log: bool,
+    /// Skip probing the finished output for the expected streams and length
+    #[serde(default)]
+    pub skip_output_verification: bool,
+    /// Kill the conversion after this many seconds (default 1 hour, 0 = no limit)
+    pub timeout_seconds: Option<u64>,
+
+    /// Existing output files are overwritten unless told otherwise
+    #[serde(default)]
+    pub on_collision: CollisionPolicy,
+    /// Suffix of the file FFmpeg writes to (`movie.part.mp4`), renamed to the
+    /// real name once the output is verified (default "part", "" = write the
+    /// final file directly)
+    pub partial_suffix: Option<String>,
+    /// Lower for background batches so the desktop stays responsive
+    #[serde(default)]
+    pub process_priority: ProcessPriority,
+    /// Where a streamed conversion's bytes go (`-` = stdout). Only set by the
+    /// `convert_to_stream` command, never read from the frontend's settings
+    #[serde(skip)]
+    pub stream_sink: Option<String>,
+
+    /// Only change the container: copy every stream, fail if a codec doesn't fit
+    #[serde(default)]
+    pub remux_only: bool,
+    /// Copy every stream (all audio and subtitle tracks, attachments such as
+    /// fonts), chapters and metadata as-is; fails if the container can't hold one
+    #[serde(default)]
+    pub keep_all_streams: bool,
+    /// Copy the video stream untouched and re-encode only the audio
+    #[serde(default)]
+    pub replace_audio_only: bool,
+
+    /// Lay MP4/MOV out for web playback: index (`moov`) at the front
+    #[serde(default)]
+    pub web_optimized: bool,
+    /// With `web_optimized`, write fragmented MP4 for streaming instead
+    #[serde(default)]
+    pub fragmented: bool,
+    /// MP4 `major_brand` to write (`isom`, `mp41`, `mp42`, ...). Overrides the device profile.
+    pub mp4_brand: Option<String>,
+    pub device_profile: Option<DeviceProfile>,
+
+    /// Audio gain in dB (negative lowers the volume)
+    pub volume_db: Option<f64>,
+    /// Playback speed factor (1.5 = 50% faster); audio keeps its pitch
+    pub speed: Option<f64>,
+    /// Fade in from black/silence over this many seconds at the start
+    pub fade_in_seconds: Option<f64>,
+    /// Fade out to black/silence over this many seconds before the end
+    pub fade_out_seconds: Option<f64>,
+    /// Cut leading and trailing silence (audio output only)
+    #[serde(default)]
+    pub trim_silence: bool,
+
+    /// EBU R128 loudness normalization of the audio track
+    #[serde(default)]
+    pub normalize_loudness: bool,
+    /// Integrated loudness target in LUFS (default -16)
+    pub loudness_target: Option<f64>,
+    /// Reuse a cached loudness measurement of the same, unchanged source file
+    /// instead of running the analysis pass again
+    #[serde(default)]
+    pub reuse_measurement: bool,
+    /// Filled in by the converter after the analysis pass; never sent by the frontend
+    #[serde(skip)]
+    pub measured_loudness: Option<LoudnessStats>,
+    /// Hardware decoding input options for the chosen GPU encoder; set by the converter
+    #[serde(skip)]
+    pub hwaccel_args: Vec<String>,
+
     pub metadata: Option<FileMetadata>,
+    /// Copy all source tags and chapters (`metadata` edits still apply on top).
+    /// Follows the app-wide default policy when unset.
+    pub preserve_metadata: Option<bool>,
+
+    /// Raw FFmpeg options placed right before the output path, after all the
+    /// generated ones. Nothing is removed to make room for them: FFmpeg uses
+    /// the last value of a repeated option, so they can silently conflict.
+    #[serde(default)]
+    pub extra_args: Vec<String>,
 }
 
 impl Default for ConversionSettings {
@@ -116,18 +668,90 @@ impl Default for ConversionSettings {
         Self {
             task_id: None,
             quality: Quality::Medium,
-            bitrate: None,
+            video_bitrate: None,
+            legacy_bitrate: None,
+            audio_bitrate: None,
             sample_rate: Some(44100),
             channels: Some(2),
+            channel_layout: None,
             width: None,
             height: None,
+            scale_mode: None,
+            scale_percent: None,
+            resolution_preset: None,
+            deinterlace: None,
+            tonemap_sdr: false,
+            crop: None,
+            rotate: None,
+            keep_orientation: false,
+            source_rotation: None,
+            flip: None,
+            denoise: FilterStrength::Off,
+            sharpen: FilterStrength::Off,
+            brightness: None,
+            contrast: None,
+            saturation: None,
+            gamma: None,
             fps: None,
+            loop_count: None,
+            tv_standard: None,
             video_codec: None,
             audio_codec: None,
+            audio_track_index: None,
+            opus_encoder: None,
+            opus_application: None,
+            opus_frame_duration: None,
+            compression_level: None,
+            crf: None,
+            encoder_preset: None,
+            rate_control: RateControl::Crf,
+            pixel_format: None,
+            b_frames: None,
+            ref_frames: None,
+            keyframe_interval: None,
+            keyframe_unit: KeyframeUnit::Frames,
+            force_keyframes_seconds: None,
+            threads: None,
+            ffv1_slices: None,
+            two_pass: false,
             use_gpu: false,
+            use_gpu_decode: None,
+            use_gpu_encode: None,
+            gpu_index: None,
+            amf_use_cqp: false,
             copy_audio: false,
             extract_audio_only: false,
+            copy_subtitles: false,
+            burn_subtitle_path: None,
+            test_playback: false,
+            compute_checksums: false,
+            write_log: false,
+            skip_output_verification: false,
+            timeout_seconds: None,
+            on_collision: CollisionPolicy::Overwrite,
+            partial_suffix: None,
+            process_priority: ProcessPriority::Normal,
+            stream_sink: None,
+            remux_only: false,
+            keep_all_streams: false,
+            replace_audio_only: false,
+            web_optimized: false,
+            fragmented: false,
+            mp4_brand: None,
+            device_profile: None,
+            volume_db: None,
+            speed: None,
+            fade_in_seconds: None,
+            fade_out_seconds: None,
+            trim_silence: false,
+            normalize_loudness: false,
+            loudness_target: None,
+            reuse_measurement: false,
+            measured_loudness: None,
+            hwaccel_args: Vec::new(),
             metadata: None,
+            preserve_metadata: None,
+            extra_args: Vec::new(),
         }
     }
 }
@@ -149,7 +773,218 @@ impl ConversionSettings {
         self.sample_rate.unwrap_or(44100)
     }
 
+    /// Bitrate of an audio-only output: `audio_bitrate`, or the legacy
+    /// `bitrate` of older clients. Never the video bitrate.
+    pub fn audio_only_bitrate(&self) -> Option<u32> {
+        self.audio_bitrate.or(self.legacy_bitrate)
+    }
+
+    /// Move the legacy `bitrate` into the field it meant for this output: the
+    /// audio bitrate when `audio_only`, the video bitrate otherwise. A value
+    /// set in the new field wins.
+    pub fn resolve_legacy_bitrate(&mut self, audio_only: bool) {
+        let Some(kbps) = self.legacy_bitrate.take() else {
+            return;
+        };
+        let target = if audio_only {
+            &mut self.audio_bitrate
+        } else {
+            &mut self.video_bitrate
+        };
+        target.get_or_insert(kbps);
+    }
+
     pub fn channels(&self) -> u32 {
-        self.channels.unwrap_or(2)
+        self.channel_layout
+            .map(|layout| layout.channels())
+            .or(self.channels)
+            .unwrap_or(2)
+    }
+
+    /// Channel count asked for on purpose, which must be honoured exactly.
+    /// Stereo is the default request, so formats may still narrow it (AMR is
+    /// mono-only), as they may a `channel_layout`.
+    pub fn explicit_channels(&self) -> Option<u32> {
+        match self.channel_layout {
+            Some(_) => None,
+            None => self.channels.filter(|&count| count != 2),
+        }
+    }
+
+    pub fn loudness_target(&self) -> f64 {
+        self.loudness_target
+            .unwrap_or(loudness::DEFAULT_TARGET_LUFS)
+    }
+
+    /// MP4 brand to write: explicit setting first, then the device profile's.
+    pub fn mp4_brand(&self) -> Option<&str> {
+        self.mp4_brand
+            .as_deref()
+            .or_else(|| self.device_profile.map(|p| p.mp4_brand()))
+    }
+
+    /// Encoder to try for `encoder`, honouring the user's Opus encoder choice.
+    pub fn preferred_audio_encoder<'a>(&self, encoder: &'a str) -> &'a str {
+        match encoder {
+            "libopus" | "opus" => self.opus_encoder.unwrap_or_default().encoder_name(),
+            other => other,
+        }
+    }
+
+    /// Gain in dB, if it actually changes the level.
+    pub fn volume_db(&self) -> Option<f64> {
+        self.volume_db.filter(|db| *db != 0.0)
+    }
+
+    /// Speed factor, if it actually changes playback speed.
+    pub fn speed(&self) -> Option<f64> {
+        self.speed.filter(|s| *s > 0.0 && *s != 1.0)
+    }
+
+    /// Keyframe interval in frames at `fps`, if one is set.
+    pub fn gop_frames(&self, fps: Option<f64>) -> Option<u32> {
+        let interval = self.keyframe_interval.filter(|i| *i > 0)?;
+        match self.keyframe_unit {
+            KeyframeUnit::Frames => Some(interval),
+            KeyframeUnit::Seconds => fps.map(|fps| ((interval as f64 * fps).round() as u32).max(1)),
+        }
+    }
+
+    /// Forced keyframe spacing in seconds, if there is one.
+    pub fn force_keyframes_every(&self) -> Option<f64> {
+        self.force_keyframes_seconds.filter(|s| *s > 0.0)
+    }
+
+    /// Requested clockwise rotation, if it is one of the supported quarter
+    /// turns. Applied on top of the upright picture FFmpeg decodes from a
+    /// rotation-tagged source.
+    pub fn rotation(&self) -> Option<u32> {
+        self.rotate.filter(|r| matches!(r, 90 | 180 | 270))
+    }
+
+    /// Output size from `scale_percent` or `resolution_preset` for a picture
+    /// of `source` size, keeping its aspect ratio with both sides rounded to
+    /// even numbers. `None` when `width` or `height` is set, since those win.
+    pub fn relative_size(&self, source: (u32, u32)) -> Option<(u32, u32)> {
+        if self.width.is_some() || self.height.is_some() || source.0 == 0 || source.1 == 0 {
+            return None;
+        }
+        let (width, height) = (source.0 as f64, source.1 as f64);
+        let factor = match (self.scale_percent, self.resolution_preset) {
+            (Some(percent), _) if percent > 0.0 => percent / 100.0,
+            (_, Some(preset)) => preset.lines() as f64 / width.min(height),
+            _ => return None,
+        };
+        let even = |side: f64| ((side * factor / 2.0).round() as u32 * 2).max(2);
+        Some((even(width), even(height)))
+    }
+
+    /// Whether the picture is rotated or mirrored on request. Turning a
+    /// tagged source upright alone doesn't count: a copied stream keeps its tag.
+    pub fn has_orientation_change(&self) -> bool {
+        self.rotation().is_some() || self.flip.is_some()
+    }
+
+    /// Fade-in length, if there is one.
+    pub fn fade_in(&self) -> Option<f64> {
+        self.fade_in_seconds.filter(|s| *s > 0.0)
+    }
+
+    /// Fade-out length, if there is one.
+    pub fn fade_out(&self) -> Option<f64> {
+        self.fade_out_seconds.filter(|s| *s > 0.0)
+    }
+
+    pub fn has_cleanup_filters(&self) -> bool {
+        self.denoise != FilterStrength::Off || self.sharpen != FilterStrength::Off
+    }
+
+    /// Colour controls that change the picture, clamped to FFmpeg's ranges.
+    pub fn color_adjustments(&self) -> Vec<(&'static str, f64)> {
+        let values = [self.brightness, self.contrast, self.saturation, self.gamma];
+        COLOR_ADJUSTMENTS
+            .iter()
+            .zip(values)
+            .filter_map(|(&(name, neutral, min, max), value)| {
+                let value = value.filter(|v| v.is_finite())?.clamp(min, max);
+                (value != neutral).then_some((name, value))
+            })
+            .collect()
+    }
+
+    pub fn has_fades(&self) -> bool {
+        self.fade_in().is_some() || self.fade_out().is_some()
+    }
+
+    pub fn gpu_decode(&self) -> bool {
+        self.use_gpu_decode.unwrap_or(self.use_gpu)
+    }
+
+    pub fn gpu_encode(&self) -> bool {
+        self.use_gpu_encode.unwrap_or(self.use_gpu)
+    }
+
+    /// Explicit encoder thread count; `None` when FFmpeg should pick.
+    pub fn threads(&self) -> Option<u32> {
+        self.threads.filter(|n| *n > 0)
+    }
+
+    /// Suffix for the in-progress output, `None` when writing in place
+    pub fn partial_suffix(&self) -> Option<&str> {
+        match self.partial_suffix.as_deref() {
+            None => Some(DEFAULT_PARTIAL_SUFFIX),
+            Some(suffix) => Some(suffix.trim_matches('.')).filter(|s| !s.is_empty()),
+        }
+    }
+
+    /// Duration of the output for a source of `source_duration` seconds.
+    pub fn output_duration(&self, source_duration: f64) -> f64 {
+        source_duration / self.speed().unwrap_or(1.0)
+    }
+
+    /// Whether the audio has to be filtered — and therefore re-encoded, never stream-copied.
+    pub fn has_audio_filters(&self) -> bool {
+        self.normalize_loudness
+            || self.volume_db().is_some()
+            || self.speed().is_some()
+            || self.has_fades()
+            || self.trim_silence
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_relative_size() {
+        let half = ConversionSettings {
+            scale_percent: Some(50.0),
+            ..Default::default()
+        };
+        assert_eq!(half.relative_size((1920, 1080)), Some((960, 540)));
+        // Odd results round to even sides
+        assert_eq!(half.relative_size((1278, 718)), Some((640, 360)));
+
+        let hd = ConversionSettings {
+            resolution_preset: Some(ResolutionPreset::Hd1080),
+            ..Default::default()
+        };
+        assert_eq!(hd.relative_size((3840, 2160)), Some((1920, 1080)));
+        // Portrait: the preset counts lines on the short side
+        assert_eq!(hd.relative_size((2160, 3840)), Some((1080, 1920)));
+        assert_eq!(hd.relative_size((0, 0)), None);
+
+        let both = ConversionSettings {
+            scale_percent: Some(25.0),
+            ..hd.clone()
+        };
+        assert_eq!(both.relative_size((3840, 2160)), Some((960, 540)));
+
+        let explicit = ConversionSettings {
+            width: Some(1280),
+            ..hd
+        };
+        assert_eq!(explicit.relative_size((3840, 2160)), None);
     }
 }
@@ -1,3 +1,5 @@
+use crate::error::{AppError, ErrorCode};
+use crate::types::ProcessPriority;
 use std::path::Path;
 use std::process::Command;
 
@@ -6,6 +8,14 @@ use std::os::windows::process::CommandExt;
 
 #[cfg(target_os = "windows")]
 const CREATE_NO_WINDOW: u32 = 0x08000000;
+#[cfg(target_os = "windows")]
+const BELOW_NORMAL_PRIORITY_CLASS: u32 = 0x00004000;
+#[cfg(target_os = "windows")]
+const ABOVE_NORMAL_PRIORITY_CLASS: u32 = 0x00008000;
+
+/// Niceness of low-priority processes, the default of `nice` itself
+#[cfg(not(target_os = "windows"))]
+const LOW_PRIORITY_NICENESS: &str = "10";
 
 pub fn create_hidden_command(program: &str) -> Command {
     let mut cmd = Command::new(program);
@@ -28,18 +38,105 @@ pub fn create_async_hidden_command(program: &str) -> tokio::process::Command {
     tokio::process::Command::new(program)
 }
 
-/// Validate that an input file exists and is accessible before conversion.
+/// `create_async_hidden_command` for a process started in the priority class
+/// matching `priority`.
+#[cfg(target_os = "windows")]
+pub fn create_async_priority_command(
+    program: &str,
+    priority: ProcessPriority,
+) -> tokio::process::Command {
+    let class = match priority {
+        ProcessPriority::Low => BELOW_NORMAL_PRIORITY_CLASS,
+        ProcessPriority::Normal => 0,
+        ProcessPriority::High => ABOVE_NORMAL_PRIORITY_CLASS,
+    };
+    let mut cmd = tokio::process::Command::new(program);
+    cmd.creation_flags(CREATE_NO_WINDOW | class);
+    cmd
+}
+
+/// `create_async_hidden_command` for a process run through `nice` when
+/// `priority` is low. `nice` execs the program, so the child's pid (and
+/// killing it) is unaffected. Raising the priority needs root, so High
+/// starts the program as-is.
+#[cfg(not(target_os = "windows"))]
+pub fn create_async_priority_command(
+    program: &str,
+    priority: ProcessPriority,
+) -> tokio::process::Command {
+    match priority {
+        ProcessPriority::Low => {
+            let mut cmd = tokio::process::Command::new("nice");
+            cmd.args(["-n", LOW_PRIORITY_NICENESS, program]);
+            cmd
+        }
+        ProcessPriority::Normal | ProcessPriority::High => tokio::process::Command::new(program),
+    }
+}
+
+/// FFmpeg's stdout, the output of streamed conversions.
+pub const PIPE_OUTPUT: &str = "pipe:1";
+
+/// Platform null sink for FFmpeg runs whose output is discarded (`-f null`).
+pub fn null_device() -> &'static str {
+    if cfg!(windows) {
+        "NUL"
+    } else {
+        "/dev/null"
+    }
+}
+
+/// Validate that an input file exists and is readable before conversion, so a
+/// moved or deleted file gets a clear error instead of an FFmpeg failure.
 pub fn validate_input_path(path: &str) -> anyhow::Result<()> {
     let p = Path::new(path);
     if !p.exists() {
-        anyhow::bail!("Input file does not exist: {}", path);
+        return Err(invalid_path("Input file does not exist", path).into());
     }
     if !p.is_file() {
-        anyhow::bail!("Input path is not a file: {}", path);
+        return Err(invalid_path("Input path is not a file", path).into());
+    }
+    if let Err(e) = std::fs::File::open(p) {
+        return Err(invalid_path("Input file is not readable", path)
+            .with_details(format!("{}: {}", path, e))
+            .into());
     }
     Ok(())
 }
 
+/// Validate that the folder an output file goes into exists and accepts new
+/// files. Writability is checked by creating and removing a probe file, since
+/// permission bits don't tell the whole story on every platform.
+pub fn validate_output_path(path: &str) -> anyhow::Result<()> {
+    if path == PIPE_OUTPUT {
+        return Ok(());
+    }
+    let dir = match Path::new(path).parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let dir_str = dir.to_string_lossy();
+
+    if !dir.is_dir() {
+        return Err(invalid_path("Output folder does not exist", &dir_str).into());
+    }
+
+    let probe = dir.join(format!(".muxolotl-write-check-{}", std::process::id()));
+    match std::fs::File::create(&probe) {
+        Ok(_) => {
+            let _ = std::fs::remove_file(&probe);
+            Ok(())
+        }
+        Err(e) => Err(invalid_path("Output folder is not writable", &dir_str)
+            .with_details(format!("{}: {}", dir_str, e))
+            .into()),
+    }
+}
+
+fn invalid_path(message: &str, path: &str) -> AppError {
+    AppError::new(ErrorCode::InvalidPath, message).with_details(path.to_string())
+}
+
 pub fn open_path(path: &str) -> Result<(), String> {
     #[cfg(target_os = "windows")]
     {
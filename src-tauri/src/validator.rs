@@ -1,5 +1,8 @@
 use crate::codec_map;
+use crate::converter::defaults;
 use crate::formats::{audio, video, Stability};
+use crate::media;
+use crate::types::{COLOR_ADJUSTMENTS, OPUS_FRAME_DURATIONS};
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
@@ -9,9 +12,31 @@ pub struct ValidationResult {
     pub errors: Vec<String>,
     pub info: Vec<String>,
     pub suggested_params: Vec<String>,
+    /// Encoder to offer as a one-click fix: a replacement for an unavailable
+    /// or incompatible chosen codec, otherwise what a source codec the output
+    /// can't hold will be re-encoded to (video before audio)
     pub alternative_codec: Option<String>,
     pub can_copy_video: bool,
     pub can_copy_audio: bool,
+    /// Machine-readable form of the format stability messages in
+    /// `errors`/`warnings`, for localized UIs and tests
+    pub stability_issues: Vec<StabilityIssue>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StabilityIssueKind {
+    /// Problematic/legacy format; reported as an error
+    Problematic,
+    Experimental,
+    /// Needs external libraries that may be missing from the FFmpeg build
+    RequiresSetup,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StabilityIssue {
+    pub kind: StabilityIssueKind,
+    pub extension: String,
 }
 
 impl ValidationResult {
@@ -36,18 +61,25 @@ impl ValidationResult {
     }
 
     fn check_stability(&mut self, stability: Stability, extension: &str) {
-        match stability {
+        let kind = match stability {
             Stability::Problematic => {
                 self.error(format!("Format '{}' is problematic/legacy", extension));
+                StabilityIssueKind::Problematic
             }
             Stability::Experimental => {
                 self.warn(format!("Format '{}' is experimental", extension));
+                StabilityIssueKind::Experimental
             }
             Stability::RequiresSetup => {
                 self.warn(format!("Format '{}' may require external libs", extension));
+                StabilityIssueKind::RequiresSetup
             }
-            Stability::Stable => {}
-        }
+            Stability::Stable => return,
+        };
+        self.stability_issues.push(StabilityIssue {
+            kind,
+            extension: extension.to_string(),
+        });
     }
 }
 
@@ -62,10 +94,27 @@ pub struct ValidationContext {
     pub input_video_codec: Option<String>,
     #[serde(default)]
     pub input_audio_codec: Option<String>,
+    /// Channel count of the source's first audio stream
+    #[serde(default)]
+    pub input_audio_channels: Option<u32>,
     #[serde(default)]
     pub input_width: Option<u32>,
     #[serde(default)]
     pub input_height: Option<u32>,
+    /// Source duration in seconds
+    #[serde(default)]
+    pub input_duration: Option<f64>,
+    /// ffprobe `field_order` of the source video
+    #[serde(default)]
+    pub input_field_order: Option<String>,
+    /// ffprobe `color_transfer` / `color_primaries` of the source video
+    #[serde(default)]
+    pub input_color_transfer: Option<String>,
+    #[serde(default)]
+    pub input_color_primaries: Option<String>,
+    /// Clockwise display rotation of the source video (phone footage)
+    #[serde(default)]
+    pub input_rotation: Option<u32>,
     #[serde(default)]
     pub gpu_vendor: Option<String>,
     #[serde(default)]
@@ -83,6 +132,15 @@ pub fn validate(ctx: &ValidationContext) -> ValidationResult {
         _ => result.warn("Unknown media type"),
     }
 
+    validate_loudness(&mut result, ctx);
+    validate_volume(&mut result, ctx);
+    validate_speed(&mut result, ctx);
+    validate_fades(&mut result, ctx);
+    validate_trim_silence(&mut result, ctx);
+    validate_extra_args(&mut result, ctx);
+    validate_partial_suffix(&mut result, ctx);
+    validate_process_priority(&mut result, ctx);
+
     result
 }
 
@@ -98,23 +156,35 @@ fn validate_audio(result: &mut ValidationResult, ctx: &ValidationContext) {
     result.suggested_params.extend(fmt.special_params.clone());
 
     // Check encoder availability
-    if fmt.codec != "copy"
+    let encoder = match ctx.settings.get("opusEncoder").and_then(|v| v.as_str()) {
+        Some(choice @ ("libopus" | "opus")) if fmt.codec == "libopus" => choice,
+        _ => fmt.codec.as_str(),
+    };
+    if encoder != "copy"
         && crate::codec_registry::is_initialized()
-        && !crate::codec_registry::is_encoder_available(&fmt.codec)
+        && !crate::codec_registry::is_encoder_available(encoder)
     {
-        if let Some(fallback) = crate::codec_registry::get_audio_fallback(&fmt.codec) {
+        if let Some(fallback) = crate::codec_registry::get_audio_fallback(encoder) {
             result.warn(format!(
                 "Encoder '{}' not found, will use '{}'",
-                fmt.codec, fallback
+                encoder, fallback
             ));
             result.alternative_codec = Some(fallback.to_string());
         } else {
             result.error(format!(
                 "Encoder '{}' not available in this FFmpeg build",
-                fmt.codec
+                encoder
             ));
         }
     }
+    if encoder == "opus" && result.alternative_codec.is_none() {
+        result.warn("The native Opus encoder is experimental and only encodes at 48 kHz");
+    }
+    let used_encoder = result
+        .alternative_codec
+        .clone()
+        .unwrap_or_else(|| encoder.to_string());
+    validate_opus_tuning(result, ctx, &used_encoder);
 
     // Lossy/lossless conversion warnings
     if let Some(input_fmt) = audio::get_format(&ctx.input_format) {
@@ -130,6 +200,26 @@ fn validate_audio(result: &mut ValidationResult, ctx: &ValidationContext) {
         if fmt.can_copy_codec(input_codec) {
             result.can_copy_audio = true;
             result.info("Audio stream copy possible — no re-encoding needed".to_string());
+        } else if result.alternative_codec.is_none() {
+            result.alternative_codec = Some(used_encoder.clone());
+        }
+    }
+
+    // Bitrate validation
+    let bitrate = ["audioBitrate", "bitrate"]
+        .iter()
+        .find_map(|key| ctx.settings.get(*key).and_then(|v| v.as_u64()));
+    if let Some(br) = bitrate {
+        match fmt.bitrate_range {
+            _ if !fmt.lossy => result.info(format!(
+                "{} is lossless — the bitrate setting is ignored",
+                fmt.extension
+            )),
+            Some((min, max)) if br < min as u64 || br > max as u64 => result.warn(format!(
+                "{} kbps is outside {}'s usual range ({}–{} kbps)",
+                br, fmt.extension, min, max
+            )),
+            _ => {}
         }
     }
 
@@ -144,16 +234,328 @@ fn validate_audio(result: &mut ValidationResult, ctx: &ValidationContext) {
         }
     }
 
+    if let Some(level) = ctx
+        .settings
+        .get("compressionLevel")
+        .and_then(|v| v.as_u64())
+    {
+        let (_, max) = defaults::COMPRESSION_LEVELS;
+        if !matches!(fmt.codec.as_str(), "flac" | "wavpack") {
+            result.info(format!(
+                "{} has no compression level, the setting is ignored",
+                fmt.extension
+            ));
+        } else if level > max as u64 {
+            result.warn(format!(
+                "Compression level goes up to {}, {} will be used instead of {}",
+                max, max, level
+            ));
+        }
+    }
+
     // Channel validation
-    if let Some(ch) = ctx.settings.get("channels").and_then(|v| v.as_u64()) {
-        let ch = ch as u32;
+    let target = if let Some(layout) = ctx.settings.get("channelLayout").and_then(|v| v.as_str()) {
+        let ch = match layout {
+            "mono" => 1,
+            "stereo" => 2,
+            "5.1" => 6,
+            other => {
+                result.error(format!(
+                    "Unknown channel layout '{}'. Use mono, stereo or 5.1",
+                    other
+                ));
+                return;
+            }
+        };
         if !fmt.supports_channels(ch) {
+            result.warn(format!(
+                "{} can't hold {} audio. Closest supported layout will be used",
+                fmt.extension, layout
+            ));
+        }
+        fmt.best_channels(ch)
+    } else {
+        let ch = ctx
+            .settings
+            .get("channels")
+            .and_then(|v| v.as_u64())
+            .map_or(2, |ch| ch as u32);
+        if fmt.supports_channels(ch) {
+            ch
+        } else if ch == 2 {
+            let closest = fmt.best_channels(ch);
+            result.info(format!(
+                "{} holds {} channel(s), so the audio will be mixed down to fit",
+                fmt.extension, closest
+            ));
+            closest
+        } else {
+            result.error(format!(
+                "{} does not support {} channels. Supported: {}",
+                fmt.extension,
+                ch,
+                fmt.channels_list()
+            ));
+            return;
+        }
+    };
+
+    if let Some(source) = ctx.input_audio_channels.filter(|&source| source > target) {
+        result.warn(format!(
+            "Downmixing {} channels to {} sums them into fewer speakers and may clip loud passages. Lower the volume if the result distorts",
+            source, target
+        ));
+    }
+}
+
+fn validate_loudness(result: &mut ValidationResult, ctx: &ValidationContext) {
+    let normalize = ctx
+        .settings
+        .get("normalizeLoudness")
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false);
+    if !normalize {
+        return;
+    }
+
+    // Normalization filters the audio, so it can't be stream-copied
+    result.can_copy_audio = false;
+
+    if let Some(target) = ctx.settings.get("loudnessTarget").and_then(|v| v.as_f64()) {
+        if !(-70.0..=-5.0).contains(&target) {
             result.error(format!(
-                "{} does not support {} channels",
-                fmt.extension, ch
+                "Loudness target {} LUFS out of range (-70 to -5)",
+                target
             ));
         }
     }
+
+    let reuse = ctx
+        .settings
+        .get("reuseMeasurement")
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false);
+    if !reuse {
+        result.info("Loudness normalization adds an analysis pass before encoding");
+    }
+}
+
+fn validate_opus_tuning(result: &mut ValidationResult, ctx: &ValidationContext, encoder: &str) {
+    let application = ctx.settings.get("opusApplication").and_then(|v| v.as_str());
+    let frame_duration = ctx
+        .settings
+        .get("opusFrameDuration")
+        .and_then(|v| v.as_f64());
+    if application.is_none() && frame_duration.is_none() {
+        return;
+    }
+
+    if encoder != "libopus" {
+        result.info("Opus application and frame duration only apply to libopus — ignored");
+        return;
+    }
+
+    if let Some(other) = application.filter(|a| !matches!(*a, "voip" | "audio" | "lowdelay")) {
+        result.error(format!(
+            "Unknown Opus application '{}'. Use voip, audio or lowdelay",
+            other
+        ));
+    }
+    if let Some(ms) = frame_duration.filter(|ms| !OPUS_FRAME_DURATIONS.contains(ms)) {
+        result.error(format!(
+            "Opus frame duration must be 2.5, 5, 10, 20, 40 or 60 ms (got {})",
+            ms
+        ));
+    }
+}
+
+fn validate_trim_silence(result: &mut ValidationResult, ctx: &ValidationContext) {
+    let trim = ctx
+        .settings
+        .get("trimSilence")
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false);
+    if !trim {
+        return;
+    }
+
+    if ctx.media_type == "video" {
+        result.warn("Silence trimming only applies to audio output — ignored for video");
+        return;
+    }
+
+    result.can_copy_audio = false;
+    if ctx
+        .settings
+        .get("fadeOutSeconds")
+        .and_then(|v| v.as_f64())
+        .is_some()
+    {
+        result
+            .warn("Fade-out is timed on the untrimmed length and may be cut off with the silence");
+    }
+}
+
+fn validate_volume(result: &mut ValidationResult, ctx: &ValidationContext) {
+    let Some(db) = ctx
+        .settings
+        .get("volumeDb")
+        .and_then(|v| v.as_f64())
+        .filter(|db| *db != 0.0)
+    else {
+        return;
+    };
+
+    result.can_copy_audio = false;
+
+    if !(-60.0..=60.0).contains(&db) {
+        result.error(format!("Volume change {} dB out of range (-60 to +60)", db));
+    }
+
+    let copy_audio = ctx
+        .settings
+        .get("copyAudio")
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false);
+    if copy_audio {
+        result.error("Changing the volume requires re-encoding — turn off audio copy");
+    }
+
+    let normalize = ctx
+        .settings
+        .get("normalizeLoudness")
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false);
+    if normalize {
+        result.warn(
+            "Volume is applied after loudness normalization and moves the level off the target",
+        );
+    } else if db > 0.0 {
+        result.info("Boosting the volume can clip loud passages");
+    }
+}
+
+fn validate_speed(result: &mut ValidationResult, ctx: &ValidationContext) {
+    let Some(speed) = ctx.settings.get("speed").and_then(|v| v.as_f64()) else {
+        return;
+    };
+
+    if speed <= 0.0 {
+        result.error(format!("Speed must be greater than 0 (got {})", speed));
+        return;
+    }
+    if speed == 1.0 {
+        return;
+    }
+
+    // Retiming rewrites every timestamp, so nothing can be stream-copied
+    result.can_copy_video = false;
+    result.can_copy_audio = false;
+
+    let remux_only = ctx
+        .settings
+        .get("remuxOnly")
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false);
+    if remux_only {
+        result.error("Changing playback speed requires re-encoding — turn off remux only");
+    }
+}
+
+fn validate_fades(result: &mut ValidationResult, ctx: &ValidationContext) {
+    let fade = |key: &str| ctx.settings.get(key).and_then(|v| v.as_f64());
+    let (fade_in, fade_out) = (fade("fadeInSeconds"), fade("fadeOutSeconds"));
+    if fade_in.is_none() && fade_out.is_none() {
+        return;
+    }
+
+    if fade_in.is_some_and(|s| s < 0.0) || fade_out.is_some_and(|s| s < 0.0) {
+        result.error("Fade durations can't be negative");
+        return;
+    }
+
+    let total = fade_in.unwrap_or(0.0) + fade_out.unwrap_or(0.0);
+    if total == 0.0 {
+        return;
+    }
+
+    // Fades change every sample/frame they cover
+    result.can_copy_video = false;
+    result.can_copy_audio = false;
+
+    let speed = ctx
+        .settings
+        .get("speed")
+        .and_then(|v| v.as_f64())
+        .filter(|s| *s > 0.0)
+        .unwrap_or(1.0);
+    if let Some(duration) = ctx.input_duration.filter(|d| *d > 0.0) {
+        let output_duration = duration / speed;
+        if total > output_duration {
+            result.error(format!(
+                "Fades ({}s) are longer than the {:.1}s output",
+                total, output_duration
+            ));
+        }
+    }
+
+    let remux_only = ctx
+        .settings
+        .get("remuxOnly")
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false);
+    if remux_only {
+        result.error("Fading in or out requires re-encoding — turn off remux only");
+    }
+}
+
+fn validate_extra_args(result: &mut ValidationResult, ctx: &ValidationContext) {
+    let args: Vec<&str> = ctx
+        .settings
+        .get("extraArgs")
+        .and_then(|v| v.as_array())
+        .map(|a| a.iter().filter_map(|v| v.as_str()).collect())
+        .unwrap_or_default();
+    if args.is_empty() {
+        return;
+    }
+
+    if args.contains(&"-i") {
+        result.error("Extra arguments can't add another input (-i)");
+        return;
+    }
+    const SHELL_CHARS: [char; 7] = [';', '|', '&', '$', '`', '<', '>'];
+    if args.iter().any(|a| a.contains(SHELL_CHARS)) {
+        result.warn(
+            "Extra arguments go to FFmpeg as-is — shell syntax like ; | & $ > is not interpreted",
+        );
+    }
+    result.info("Extra arguments are added after the generated ones and may conflict with them");
+}
+
+fn validate_partial_suffix(result: &mut ValidationResult, ctx: &ValidationContext) {
+    let Some(suffix) = ctx.settings.get("partialSuffix").and_then(|v| v.as_str()) else {
+        return;
+    };
+    if suffix.contains(['/', '\\']) {
+        result.error("The in-progress file suffix can't contain path separators");
+    } else if suffix.trim_matches('.').is_empty() {
+        result.info(
+            "Output is written under its final name, so an interrupted conversion can leave a truncated file that looks complete",
+        );
+    }
+}
+
+fn validate_process_priority(result: &mut ValidationResult, ctx: &ValidationContext) {
+    match ctx.settings.get("processPriority").and_then(|v| v.as_str()) {
+        Some("high") if !cfg!(windows) => result.info(
+            "Raising FFmpeg's priority needs administrator rights on this system — it runs at normal priority",
+        ),
+        Some("high") => {
+            result.info("High priority keeps the CPU busy ahead of other apps while encoding")
+        }
+        _ => {}
+    }
 }
 
 // ============ Video validation ============
@@ -167,12 +569,152 @@ fn validate_video(result: &mut ValidationResult, ctx: &ValidationContext) {
     result.check_stability(fmt.stability, &fmt.extension);
     result.suggested_params.extend(fmt.special_params.clone());
 
+    // --- Still images keep a single frame ---
+    if fmt.still_image && ctx.input_duration.is_some_and(|d| d > 1.0) {
+        result.info(format!(
+            "{} holds a single image — only the first frame is kept",
+            fmt.extension.to_uppercase()
+        ));
+    }
+
+    // Encoder choice follows `useGpuEncode`, falling back to `useGpu`
     let use_gpu = ctx
         .settings
-        .get("useGpu")
+        .get("useGpuEncode")
         .and_then(|v| v.as_bool())
+        .or_else(|| ctx.settings.get("useGpu").and_then(|v| v.as_bool()))
         .unwrap_or(true);
 
+    // --- Keep all streams: a remux that maps every stream ---
+    let keep_all_streams = ctx
+        .settings
+        .get("keepAllStreams")
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false);
+    if keep_all_streams {
+        if fmt.container == "matroska" {
+            result.info("Every stream, chapter and attachment is copied as-is");
+        } else {
+            result.warn(format!(
+                "{} can't hold attachments such as fonts and may reject some tracks — MKV keeps everything",
+                fmt.extension
+            ));
+        }
+    }
+
+    // --- Remux-only needs every source stream to fit the container ---
+    let remux_only = keep_all_streams
+        || ctx
+            .settings
+            .get("remuxOnly")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false);
+    let burn_subtitles = ctx
+        .settings
+        .get("burnSubtitlePath")
+        .and_then(|v| v.as_str())
+        .is_some_and(|p| !p.is_empty());
+    if remux_only && burn_subtitles {
+        result.error("Burning in subtitles requires re-encoding — turn off remux only");
+        return;
+    }
+    if remux_only && ctx.settings.get("crop").is_some_and(|v| !v.is_null()) {
+        result.error("Cropping requires re-encoding — turn off remux only");
+        return;
+    }
+    let deinterlace = ctx.settings.get("deinterlace").and_then(|v| v.as_str());
+    if remux_only && deinterlace.is_some() {
+        result.error("Deinterlacing requires re-encoding — turn off remux only");
+        return;
+    }
+    let tonemap_sdr = ctx
+        .settings
+        .get("tonemapSdr")
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false);
+    if remux_only && tonemap_sdr {
+        result.error("Tone mapping requires re-encoding — turn off remux only");
+        return;
+    }
+    let denoise = filter_strength(ctx, "denoise");
+    let sharpen = filter_strength(ctx, "sharpen");
+    if remux_only && (denoise.is_some() || sharpen.is_some()) {
+        result.error("Denoising or sharpening requires re-encoding — turn off remux only");
+        return;
+    }
+    let color_adjusted = COLOR_ADJUSTMENTS.iter().any(|&(name, neutral, _, _)| {
+        ctx.settings
+            .get(name)
+            .and_then(|v| v.as_f64())
+            .is_some_and(|v| v != neutral)
+    });
+    if remux_only && color_adjusted {
+        result.error("Colour adjustments require re-encoding — turn off remux only");
+        return;
+    }
+    let rotate = ctx.settings.get("rotate").and_then(|v| v.as_u64());
+    let flip = ctx.settings.get("flip").and_then(|v| v.as_str());
+    if remux_only && (rotate.is_some_and(|r| r != 0) || flip.is_some()) {
+        result.error("Rotating or flipping requires re-encoding — turn off remux only");
+        return;
+    }
+
+    if remux_only && ctx.input_video_codec.is_some() {
+        let reasons = fmt.remux_incompatibilities(
+            ctx.input_video_codec.as_deref(),
+            ctx.input_audio_codec.as_deref(),
+        );
+        if reasons.is_empty() {
+            result.can_copy_video = true;
+            result.can_copy_audio = ctx.input_audio_codec.is_some();
+            result.info("Remux only — streams are copied, no re-encoding");
+            return;
+        }
+        for reason in reasons {
+            result.error(format!("Cannot remux: {}", reason));
+        }
+        return;
+    }
+
+    // --- Replace audio only: the video stream is copied as-is ---
+    let replace_audio_only = ctx
+        .settings
+        .get("replaceAudioOnly")
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false);
+    if replace_audio_only {
+        if let Some(codec) = ctx
+            .input_video_codec
+            .as_deref()
+            .filter(|c| !fmt.supports_video_codec(c))
+        {
+            result.error(format!(
+                "{} can't hold {} video, so it can't be copied — pick another format or re-encode",
+                fmt.extension, codec
+            ));
+            return;
+        }
+        if fmt.audio_codecs.is_empty() {
+            result.error(format!("{} cannot carry audio", fmt.extension));
+            return;
+        }
+        let filtered = burn_subtitles
+            || ctx.settings.get("crop").is_some_and(|v| !v.is_null())
+            || deinterlace.is_some()
+            || tonemap_sdr
+            || denoise.is_some()
+            || sharpen.is_some()
+            || color_adjusted
+            || rotate.is_some_and(|r| r != 0)
+            || flip.is_some();
+        if filtered {
+            result.error("Video filters require re-encoding — turn off replace audio only");
+            return;
+        }
+        result.can_copy_video = ctx.input_video_codec.is_some();
+        result.info("Only the audio is re-encoded, the video stream is copied");
+    }
+
     // --- Video stream copy detection ---
     check_video_copy(result, ctx, &fmt);
 
@@ -181,6 +723,160 @@ fn validate_video(result: &mut ValidationResult, ctx: &ValidationContext) {
         check_gpu_codec(result, ctx, &fmt);
     }
 
+    // --- Codec / container compatibility ---
+    check_codec_compatibility(result, ctx, &fmt);
+
+    // --- Encoder speed preset ---
+    if let Some(preset) = ctx.settings.get("encoderPreset").and_then(|v| v.as_str()) {
+        if codec_map::NVENC_PRESETS.contains(&preset) {
+            if !use_gpu || ctx.gpu_vendor.as_deref() != Some("nvidia") {
+                result.warn(format!(
+                    "Preset '{}' only applies to NVIDIA (NVENC) encoding — ignored otherwise",
+                    preset
+                ));
+            }
+        } else if !codec_map::X26X_PRESETS.contains(&preset) {
+            result.error(format!(
+                "Unknown encoder preset '{}'. Use ultrafast to veryslow, or p1 to p7 for NVENC",
+                preset
+            ));
+        }
+    }
+
+    let amf_use_cqp = ctx
+        .settings
+        .get("amfUseCqp")
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false);
+    if use_gpu && amf_use_cqp && ctx.gpu_vendor.as_deref() == Some("amd") {
+        result.info("AMF constant QP needs Adrenalin 22.7 or newer — older drivers can crash");
+    }
+
+    // --- Crop region ---
+    validate_crop(result, ctx);
+
+    // --- Deinterlacing ---
+    let interlaced = matches!(
+        ctx.input_field_order.as_deref(),
+        Some("tt" | "bb" | "tb" | "bt")
+    );
+    match deinterlace {
+        Some("yadif" | "bwdif") => {
+            result.can_copy_video = false;
+            if !interlaced && ctx.input_field_order.is_some() {
+                result.info("Source is progressive — deinterlacing only softens it");
+            }
+        }
+        Some(other) => result.error(format!(
+            "Unknown deinterlacer '{}'. Use yadif or bwdif",
+            other
+        )),
+        None if interlaced => {
+            result.info("Source is interlaced — enable deinterlacing to avoid combing")
+        }
+        None => {}
+    }
+
+    // --- Rotation / flip ---
+    if let Some(degrees) = rotate.filter(|r| *r != 0) {
+        if matches!(degrees, 90 | 180 | 270) {
+            result.can_copy_video = false;
+        } else {
+            result.error(format!(
+                "Rotation must be 90, 180 or 270 degrees (got {})",
+                degrees
+            ));
+        }
+    }
+    if let Some(flip) = flip {
+        if matches!(flip, "horizontal" | "vertical") {
+            result.can_copy_video = false;
+        } else {
+            result.error(format!(
+                "Unknown flip '{}'. Use horizontal or vertical",
+                flip
+            ));
+        }
+    }
+
+    // --- Source orientation ---
+    if let Some(degrees) = ctx.input_rotation.filter(|r| *r != 0) {
+        let keep = ctx
+            .settings
+            .get("keepOrientation")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false);
+        if keep {
+            result.info(format!(
+                "Source is tagged to play rotated {}° — the picture is kept as stored",
+                degrees
+            ));
+        } else if fmt.is_isobmff() {
+            result.info(format!(
+                "Source is tagged to play rotated {}° — a re-encode is turned upright",
+                degrees
+            ));
+        } else if remux_only {
+            result.warn(format!(
+                "{} may not keep the source's {}° rotation tag — it can play sideways",
+                fmt.extension, degrees
+            ));
+        } else {
+            result.can_copy_video = false;
+            result.info(format!(
+                "Source is tagged to play rotated {}° — it will be re-encoded upright",
+                degrees
+            ));
+        }
+    }
+
+    // --- Denoise / sharpen ---
+    for (name, strength) in [("denoise", denoise), ("sharpen", sharpen)] {
+        match strength {
+            Some("light" | "medium" | "strong") => result.can_copy_video = false,
+            Some(other) => result.error(format!(
+                "Unknown {} strength '{}'. Use off, light, medium or strong",
+                name, other
+            )),
+            None => {}
+        }
+    }
+    if use_gpu && (denoise.is_some() || sharpen.is_some()) {
+        result.warn(
+            "Denoise and sharpen run on the CPU — GPU-decoded frames are copied back to system memory, which is slower",
+        );
+    }
+
+    // --- Colour controls ---
+    if color_adjusted {
+        result.can_copy_video = false;
+    }
+    for &(name, _, min, max) in &COLOR_ADJUSTMENTS {
+        if let Some(value) = ctx.settings.get(name).and_then(|v| v.as_f64()) {
+            if value < min || value > max {
+                result.warn(format!(
+                    "{} of {} is outside {} to {} and will be clamped",
+                    name, value, min, max
+                ));
+            }
+        }
+    }
+
+    // --- HDR tone mapping ---
+    let hdr = media::is_hdr(
+        ctx.input_color_transfer.as_deref(),
+        ctx.input_color_primaries.as_deref(),
+    );
+    if tonemap_sdr {
+        if hdr {
+            result.can_copy_video = false;
+        } else if ctx.input_color_transfer.is_some() || ctx.input_color_primaries.is_some() {
+            result.info("Source isn't HDR — tone mapping will be skipped");
+        }
+    } else if hdr && !result.can_copy_video {
+        result.warn("Source is HDR — enable tone mapping to SDR or colours will look washed out");
+    }
+
     // --- Resolution validation ---
     validate_resolution(result, ctx, &fmt);
 
@@ -188,19 +884,153 @@ fn validate_video(result: &mut ValidationResult, ctx: &ValidationContext) {
     recommend_for_input(result, ctx, &fmt);
 
     // --- Bitrate sanity check ---
-    if let Some(br) = ctx.settings.get("bitrate").and_then(|v| v.as_u64()) {
-        if br > 50_000 {
+    let intermediate = codec_map::is_intermediate_encoder(target_encoder(ctx, &fmt, use_gpu));
+    if let Some(br) = video_bitrate(ctx) {
+        if intermediate {
+            result
+                .info("ProRes and DNxHR ignore the bitrate — the quality preset picks the profile");
+        } else if br > 50_000 {
             result.warn("Very high bitrate (>50 Mbps). Ensure disk space.");
         }
-        if br < 100 {
+        if br < 100 && !intermediate {
             result.warn("Very low bitrate (<100 kbps). Expect blockiness.");
         }
     }
 
+    // --- Two-pass needs a bitrate target ---
+    let two_pass = ctx
+        .settings
+        .get("twoPass")
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false);
+    if two_pass && video_bitrate(ctx).is_none() {
+        result.warn("Two-pass encoding needs a target bitrate — it will be skipped");
+    }
+
+    // --- Rate control ---
+    let has_bitrate = video_bitrate(ctx).is_some();
+    match ctx.settings.get("rateControl").and_then(|v| v.as_str()) {
+        None | Some("crf") => {}
+        Some(mode @ ("vbr" | "cbr")) => {
+            if !has_bitrate {
+                if mode == "cbr" {
+                    result.error("Constant bitrate needs a target bitrate");
+                } else {
+                    result.warn("VBR needs a target bitrate — the quality preset will be used");
+                }
+            } else if ctx.settings.get("crf").is_some_and(|v| !v.is_null()) {
+                result.info("Quality value (CRF) is ignored when encoding to a bitrate target");
+            }
+        }
+        Some(other) => result.error(format!(
+            "Unknown rate control '{}'. Use crf, vbr or cbr",
+            other
+        )),
+    }
+
+    // --- MP4 major brand ---
+    if let Some(brand) = ctx.settings.get("mp4Brand").and_then(|v| v.as_str()) {
+        if !video::MP4_BRANDS.contains(&brand) {
+            result.error(format!(
+                "Unknown MP4 brand '{}'. Use one of: {}",
+                brand,
+                video::MP4_BRANDS.join(", ")
+            ));
+        } else if !fmt.is_isobmff() {
+            result.warn(format!(
+                "MP4 brand only applies to MP4/MOV containers, ignored for {}",
+                fmt.extension
+            ));
+        }
+    }
+
+    // --- Web optimization ---
+    let web_optimized = ctx.settings.get("webOptimized").and_then(|v| v.as_bool()) == Some(true);
+    if web_optimized && !fmt.is_isobmff() {
+        result.warn(format!(
+            "Web optimization only applies to MP4/MOV containers, ignored for {}",
+            fmt.extension
+        ));
+    } else if ctx.settings.get("fragmented").and_then(|v| v.as_bool()) == Some(true) {
+        if !web_optimized {
+            result.warn("Fragmented output needs web optimization turned on");
+        } else {
+            result.info("Fragmented MP4 streams well but some older players can't seek in it");
+        }
+    }
+
+    // --- NTSC / PAL override ---
+    if let Some(standard) = ctx.settings.get("tvStandard").and_then(|v| v.as_str()) {
+        if !matches!(standard, "ntsc" | "pal") {
+            result.error(format!(
+                "Unknown TV standard '{}'. Use ntsc or pal",
+                standard
+            ));
+        } else if !fmt.requires_fixed_resolution {
+            result.warn(format!(
+                "TV standard only applies to fixed-resolution formats (DVD/VOB), ignored for {}",
+                fmt.extension
+            ));
+        }
+    }
+
+    // --- Lossless / uncompressed codecs ---
+    validate_preservation_codec(result, ctx, &fmt);
+
+    // --- B-frames / reference frames ---
+    validate_frame_structure(result, ctx, use_gpu);
+
+    // --- Constant-quality value ---
+    validate_crf(result, ctx, &fmt, use_gpu);
+    validate_pixel_format(result, ctx, &fmt, use_gpu);
+
     // --- Encoder availability ---
     check_video_encoder(result, &fmt);
 }
 
+/// A denoise/sharpen preset other than `off`.
+fn filter_strength<'a>(ctx: &'a ValidationContext, key: &str) -> Option<&'a str> {
+    ctx.settings
+        .get(key)
+        .and_then(|v| v.as_str())
+        .filter(|s| *s != "off")
+}
+
+/// Crop is `[width, height, x, y]` and must stay inside the source frame.
+fn validate_crop(result: &mut ValidationResult, ctx: &ValidationContext) {
+    let Some(crop) = ctx.settings.get("crop").filter(|v| !v.is_null()) else {
+        return;
+    };
+    let values: Option<Vec<u64>> = crop
+        .as_array()
+        .and_then(|a| a.iter().map(|v| v.as_u64()).collect());
+    let Some([width, height, x, y]) = values.and_then(|v| <[u64; 4]>::try_from(v).ok()) else {
+        result.error("Crop must be [width, height, x, y] in pixels");
+        return;
+    };
+
+    if width == 0 || height == 0 {
+        result.error("Crop width and height must be greater than 0");
+        return;
+    }
+
+    result.can_copy_video = false;
+
+    if let (Some(source_w), Some(source_h)) = (ctx.input_width, ctx.input_height) {
+        if x + width > source_w as u64 || y + height > source_h as u64 {
+            result.error(format!(
+                "Crop region {}x{} at ({}, {}) extends past the {}x{} source",
+                width, height, x, y, source_w, source_h
+            ));
+            return;
+        }
+    }
+
+    if width % 2 != 0 || height % 2 != 0 {
+        result.warn("Odd crop dimensions are rejected by most encoders — use even numbers");
+    }
+}
+
 fn check_video_copy(
     result: &mut ValidationResult,
     ctx: &ValidationContext,
@@ -215,10 +1045,26 @@ fn check_video_copy(
             .settings
             .get("height")
             .and_then(|v| v.as_u64())
-            .is_none();
+            .is_none()
+        && ctx.settings.get("scalePercent").is_none_or(|v| v.is_null())
+        && ctx
+            .settings
+            .get("resolutionPreset")
+            .is_none_or(|v| v.is_null());
 
     let no_fps = ctx.settings.get("fps").and_then(|v| v.as_u64()).is_none();
 
+    let no_frame_structure = ctx
+        .settings
+        .get("bFrames")
+        .and_then(|v| v.as_u64())
+        .is_none()
+        && ctx
+            .settings
+            .get("refFrames")
+            .and_then(|v| v.as_u64())
+            .is_none();
+
     let no_explicit_codec = ctx
         .settings
         .get("videoCodec")
@@ -229,22 +1075,270 @@ fn check_video_copy(
 
     if no_resize
         && no_fps
+        && no_frame_structure
         && no_explicit_codec
         && no_fixed_res
+        && !fmt.still_image
         && fmt.supports_video_codec(input_codec)
     {
         result.can_copy_video = true;
         result.info("⚡ Stream copy possible — no re-encoding, very fast!".to_string());
+    } else if no_explicit_codec
+        && !fmt.supports_video_codec(input_codec)
+        && result.alternative_codec.is_none()
+    {
+        if let Some(alt) = fmt.get_software_codec() {
+            result.info(format!(
+                "{} can't hold {} video — it will be re-encoded to {}",
+                fmt.extension, input_codec, alt
+            ));
+            result.alternative_codec = Some(alt);
+        }
     }
 
     // Also check audio copy
     if let Some(audio_codec) = &ctx.input_audio_codec {
         if !audio_codec.is_empty() && fmt.supports_audio_codec(audio_codec) {
             result.can_copy_audio = true;
+        } else if !audio_codec.is_empty() && result.alternative_codec.is_none() {
+            result.alternative_codec = fmt.get_recommended_audio_codec();
+            if codec_map::PASSTHROUGH_AUDIO_CODECS.contains(&audio_codec.as_str()) {
+                result.warn(format!(
+                    "{} can't hold {} surround audio — it will be re-encoded to {}",
+                    fmt.extension,
+                    audio_codec.to_uppercase(),
+                    result
+                        .alternative_codec
+                        .as_deref()
+                        .unwrap_or("another codec")
+                ));
+            }
         }
     }
 }
 
+fn validate_preservation_codec(
+    result: &mut ValidationResult,
+    ctx: &ValidationContext,
+    fmt: &video::VideoFormat,
+) {
+    let codec = ctx.settings.get("videoCodec").and_then(|v| v.as_str());
+
+    match codec {
+        Some("ffv1") => {
+            if fmt.container != "matroska" {
+                result.error(format!(
+                    "FFV1 requires the MKV container (got {})",
+                    fmt.extension
+                ));
+            }
+
+            if let Some(slices) = ctx.settings.get("ffv1Slices").and_then(|v| v.as_u64()) {
+                if !codec_map::FFV1_SLICE_COUNTS.contains(&(slices as u32)) {
+                    result.error(format!(
+                        "FFV1 slice count must be one of {:?} (got {})",
+                        codec_map::FFV1_SLICE_COUNTS,
+                        slices
+                    ));
+                }
+            }
+
+            result.info("FFV1 is lossless — expect very large files".to_string());
+        }
+        Some("v210") => {
+            if !fmt.supports_video_codec("v210") {
+                result.error(format!(
+                    "v210 requires the MOV or MKV container (got {})",
+                    fmt.extension
+                ));
+            }
+            result.warn("v210 is uncompressed 10-bit video — roughly 1 GB per minute at 1080p");
+        }
+        Some(c) if c.starts_with("prores") || c == "dnxhd" => {
+            let name = if c == "dnxhd" { "DNxHR" } else { "ProRes" };
+            if !fmt.supports_video_codec(c) {
+                result.error(format!(
+                    "{} requires the MOV or MXF container (got {})",
+                    name, fmt.extension
+                ));
+            }
+
+            let quality = ctx
+                .settings
+                .get("quality")
+                .and_then(|v| serde_json::from_value(v.clone()).ok())
+                .unwrap_or_default();
+            let encoder = codec_map::software_encoder_for_codec(c).unwrap_or(c);
+            let profile = codec_map::intermediate_profile(encoder, quality);
+            let pix_fmt = ctx.settings.get("pixelFormat").and_then(|v| v.as_str());
+            if let (Some(profile), Some(pix_fmt)) = (profile, pix_fmt) {
+                if c == "dnxhd" && pix_fmt != profile.pixel_format {
+                    result.warn(format!(
+                        "DNxHR profile {} only encodes {} (got {})",
+                        profile.profile, profile.pixel_format, pix_fmt
+                    ));
+                }
+            }
+
+            result.info(format!(
+                "{} is an editing intermediate — expect files 10–50× larger than H.264",
+                name
+            ));
+        }
+        _ => {}
+    }
+}
+
+fn validate_frame_structure(result: &mut ValidationResult, ctx: &ValidationContext, use_gpu: bool) {
+    let b_frames = ctx.settings.get("bFrames").and_then(|v| v.as_u64());
+    let ref_frames = ctx.settings.get("refFrames").and_then(|v| v.as_u64());
+    let max = codec_map::MAX_FRAME_STRUCTURE_COUNT as u64;
+
+    if let Some(bf) = b_frames {
+        if bf > max {
+            result.error(format!(
+                "B-frames must be between 0 and {} (got {})",
+                max, bf
+            ));
+        }
+    }
+
+    if let Some(refs) = ref_frames {
+        if refs == 0 || refs > max {
+            result.error(format!(
+                "Reference frames must be between 1 and {} (got {})",
+                max, refs
+            ));
+        }
+    }
+
+    if ctx
+        .settings
+        .get("keyframeInterval")
+        .and_then(|v| v.as_u64())
+        == Some(0)
+    {
+        result.error("Keyframe interval must be at least 1");
+    }
+
+    if let Some(seconds) = ctx
+        .settings
+        .get("forceKeyframesSeconds")
+        .and_then(|v| v.as_f64())
+    {
+        if seconds <= 0.0 {
+            result.error(format!(
+                "Forced keyframe spacing must be positive (got {})",
+                seconds
+            ));
+        } else if ctx.settings.get("keyframeInterval").is_some() {
+            result.info("Forced keyframes are added on top of the keyframe interval");
+        }
+    }
+
+    if use_gpu && (b_frames.is_some() || ref_frames.is_some()) {
+        result.info("B-frame/reference frame settings apply to software encoders only".to_string());
+    }
+}
+
+/// Video bitrate in kbps, from `videoBitrate` or the deprecated `bitrate`.
+fn video_bitrate(ctx: &ValidationContext) -> Option<u64> {
+    ["videoBitrate", "bitrate"]
+        .iter()
+        .find_map(|key| ctx.settings.get(*key).and_then(|v| v.as_u64()))
+}
+
+/// Encoder the conversion will most likely use: the requested codec (or the
+/// container's first), on the GPU when one is used.
+fn target_encoder<'a>(
+    ctx: &'a ValidationContext,
+    fmt: &'a video::VideoFormat,
+    use_gpu: bool,
+) -> &'a str {
+    let codec = ctx
+        .settings
+        .get("videoCodec")
+        .and_then(|v| v.as_str())
+        .or(fmt.video_codecs.first().map(|c| c.as_str()))
+        .unwrap_or("");
+    let gpu_encoder = ctx
+        .gpu_vendor
+        .as_deref()
+        .filter(|_| use_gpu && ctx.gpu_available.unwrap_or(false))
+        .and_then(|vendor| codec_map::gpu_encoder_for_codec(codec, vendor));
+    gpu_encoder
+        .or_else(|| codec_map::software_encoder_for_codec(codec))
+        .unwrap_or(codec)
+}
+
+fn validate_pixel_format(
+    result: &mut ValidationResult,
+    ctx: &ValidationContext,
+    fmt: &video::VideoFormat,
+    use_gpu: bool,
+) {
+    let Some(pix_fmt) = ctx.settings.get("pixelFormat").and_then(|v| v.as_str()) else {
+        return;
+    };
+
+    let encoder = target_encoder(ctx, fmt, use_gpu);
+    if codec_map::is_vaapi_encoder(encoder) {
+        result.warn(format!(
+            "{} takes GPU surfaces, pixel format {} is ignored",
+            encoder, pix_fmt
+        ));
+        return;
+    }
+    let supported = codec_map::supported_pixel_formats(encoder);
+    if let Some(supported) = supported.filter(|s| !s.contains(&pix_fmt)) {
+        result.error(format!(
+            "{} can't encode {} (supports: {})",
+            encoder,
+            pix_fmt,
+            supported.join(", ")
+        ));
+        return;
+    }
+
+    // Intermediate and lossless encoders aren't meant for playback devices
+    let delivery_encoder = supported.is_none_or(|s| s.contains(&"yuv420p"))
+        && !codec_map::preserves_source_pixel_format(encoder);
+    if delivery_encoder && !codec_map::is_widely_playable_pixel_format(pix_fmt) {
+        result.warn(format!(
+            "{} may play as green or corrupt video on TVs and phones — yuv420p is the most compatible",
+            pix_fmt
+        ));
+    }
+}
+
+fn validate_crf(
+    result: &mut ValidationResult,
+    ctx: &ValidationContext,
+    fmt: &video::VideoFormat,
+    use_gpu: bool,
+) {
+    let Some(crf) = ctx.settings.get("crf").and_then(|v| v.as_u64()) else {
+        return;
+    };
+
+    let encoder = target_encoder(ctx, fmt, use_gpu);
+    match codec_map::quality_scale(encoder) {
+        Some(scale) => {
+            let clamped = scale.clamp(u32::try_from(crf).unwrap_or(u32::MAX));
+            if clamped as u64 != crf {
+                result.warn(format!(
+                    "{} accepts quality values {}–{}, {} will be used instead of {}",
+                    encoder, scale.min, scale.max, clamped, crf
+                ));
+            }
+        }
+        None => result.info(format!(
+            "{} has no constant-quality setting, the quality preset is used",
+            encoder
+        )),
+    }
+}
+
 fn check_gpu_codec(
     result: &mut ValidationResult,
     ctx: &ValidationContext,
@@ -279,6 +1373,73 @@ fn check_gpu_codec(
     }
 }
 
+/// Mirror of the checks `convert` makes on explicitly chosen codecs, so an
+/// impossible combination is reported before FFmpeg is started.
+fn check_codec_compatibility(
+    result: &mut ValidationResult,
+    ctx: &ValidationContext,
+    fmt: &video::VideoFormat,
+) {
+    let requested = |key: &str| {
+        ctx.settings
+            .get(key)
+            .and_then(|v| v.as_str())
+            .filter(|c| !c.is_empty())
+    };
+
+    if let Some(codec) = requested("videoCodec") {
+        if !fmt.supports_video_codec(codec) {
+            // GPU encoders fall back to their software counterpart
+            match codec_map::software_fallback_for_encoder(codec)
+                .filter(|sw| fmt.supports_video_codec(sw))
+            {
+                Some(sw) => {
+                    result.warn(format!(
+                        "'{}' can't be used in {}, will use '{}'",
+                        codec, fmt.extension, sw
+                    ));
+                    result.alternative_codec = Some(sw.to_string());
+                }
+                None => {
+                    let suggestion = fmt.get_software_codec();
+                    match &suggestion {
+                        Some(alt) => result.error(format!(
+                            "Video codec '{}' is not compatible with {} — use '{}'",
+                            codec, fmt.extension, alt
+                        )),
+                        None => result.error(format!(
+                            "Video codec '{}' is not compatible with {}",
+                            codec, fmt.extension
+                        )),
+                    }
+                    result.alternative_codec = suggestion;
+                }
+            }
+        }
+    }
+
+    // An unsupported audio codec is replaced rather than rejected
+    if let Some(codec) = requested("audioCodec") {
+        if !fmt.supports_audio_codec(codec) {
+            match fmt.get_recommended_audio_codec() {
+                Some(alt) => {
+                    result.warn(format!(
+                        "Audio codec '{}' is not compatible with {}, will use '{}'",
+                        codec, fmt.extension, alt
+                    ));
+                    if result.alternative_codec.is_none() {
+                        result.alternative_codec = Some(alt);
+                    }
+                }
+                None => result.warn(format!(
+                    "Audio codec '{}' is not compatible with {}",
+                    codec, fmt.extension
+                )),
+            }
+        }
+    }
+}
+
 fn validate_resolution(
     result: &mut ValidationResult,
     ctx: &ValidationContext,
@@ -287,6 +1448,68 @@ fn validate_resolution(
     let w = ctx.settings.get("width").and_then(|v| v.as_u64());
     let h = ctx.settings.get("height").and_then(|v| v.as_u64());
 
+    let percent = ctx.settings.get("scalePercent").and_then(|v| v.as_f64());
+    let preset = ctx
+        .settings
+        .get("resolutionPreset")
+        .and_then(|v| v.as_str());
+    if let Some(percent) = percent.filter(|p| *p <= 0.0 || *p > 400.0) {
+        result.error(format!(
+            "Scale percentage must be between 0 and 400 (got {})",
+            percent
+        ));
+    }
+    if percent.is_some() || preset.is_some() {
+        if fmt.requires_fixed_resolution {
+            result.info(format!(
+                "{} has a fixed frame size — relative scaling is ignored",
+                fmt.extension
+            ));
+        } else if w.is_some() || h.is_some() {
+            result.info("An explicit width or height takes precedence over relative scaling");
+        } else if percent.is_some() && preset.is_some() {
+            result.info("Scale percentage takes precedence over the resolution preset");
+        }
+    }
+    let preset_lines = match preset {
+        Some("480p") => Some(480),
+        Some("720p") => Some(720),
+        Some("1080p") => Some(1080),
+        Some("4k") => Some(2160),
+        Some(other) => {
+            result.error(format!(
+                "Unknown resolution preset '{}'. Use 480p, 720p, 1080p or 4k",
+                other
+            ));
+            None
+        }
+        None => None,
+    };
+    if let (Some(lines), Some(source_w), Some(source_h)) =
+        (preset_lines, ctx.input_width, ctx.input_height)
+    {
+        if percent.is_none() && w.is_none() && h.is_none() && lines > source_w.min(source_h) {
+            result.warn(format!(
+                "{}p is larger than the {}×{} source — upscaling adds no detail",
+                lines, source_w, source_h
+            ));
+        }
+    }
+
+    match ctx.settings.get("scaleMode").and_then(|v| v.as_str()) {
+        Some("stretch" | "fit" | "fill")
+            if !fmt.requires_fixed_resolution && (w.is_none() || h.is_none()) =>
+        {
+            result.info("Scale mode needs both width and height — ignored");
+        }
+        Some("stretch" | "fit" | "fill") => {}
+        Some(other) => result.error(format!(
+            "Unknown scale mode '{}'. Use stretch, fit or fill",
+            other
+        )),
+        None => {}
+    }
+
     if let (Some(w), Some(h)) = (w, h) {
         if fmt.requires_fixed_resolution {
             if w != 720 || (h != 576 && h != 480) {
@@ -379,6 +1602,21 @@ fn check_video_encoder(result: &mut ValidationResult, fmt: &video::VideoFormat)
         return;
     }
 
+    if fmt.still_image {
+        let encoders = codec_map::still_image_encoders(&fmt.extension);
+        if !encoders
+            .iter()
+            .any(|e| crate::codec_registry::is_encoder_available(e))
+        {
+            result.error(format!(
+                "This FFmpeg build can't write {} images (needs {})",
+                fmt.extension.to_uppercase(),
+                encoders.join(" or ")
+            ));
+        }
+        return;
+    }
+
     // Check if at least one software encoder is available for this format
     let any_sw_available = fmt.video_codecs.iter().any(|codec| {
         let sw_name = codec_map::software_encoder_for_codec(codec).unwrap_or(codec);
@@ -399,3 +1637,113 @@ fn check_video_encoder(result: &mut ValidationResult, fmt: &video::VideoFormat)
         ));
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn context(
+        media_type: &str,
+        output_format: &str,
+        settings: serde_json::Value,
+    ) -> ValidationContext {
+        ValidationContext {
+            input_format: "mkv".to_string(),
+            output_format: output_format.to_string(),
+            media_type: media_type.to_string(),
+            settings,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_incompatible_codecs() {
+        let ctx = context(
+            "video",
+            "webm",
+            json!({ "videoCodec": "libx264", "useGpu": false }),
+        );
+        let result = validate(&ctx);
+        assert!(!result.is_valid);
+        assert!(result
+            .errors
+            .iter()
+            .any(|e| e.contains("'libx264' is not compatible with webm")));
+        assert_eq!(result.alternative_codec.as_deref(), Some("libvpx-vp9"));
+
+        // The audio codec is swapped at conversion time, so it only warns
+        let ctx = context(
+            "video",
+            "webm",
+            json!({ "audioCodec": "aac", "useGpu": false }),
+        );
+        let result = validate(&ctx);
+        assert!(result.is_valid);
+        assert!(result
+            .warnings
+            .iter()
+            .any(|w| w.contains("will use 'libopus'")));
+        assert_eq!(result.alternative_codec.as_deref(), Some("libopus"));
+    }
+
+    #[test]
+    fn test_stability_issues() {
+        let result = validate(&context("audio", "shn", json!({})));
+        assert!(!result.is_valid);
+        assert_eq!(
+            result.stability_issues,
+            vec![StabilityIssue {
+                kind: StabilityIssueKind::Problematic,
+                extension: "shn".to_string(),
+            }]
+        );
+
+        let result = validate(&context("audio", "ra", json!({ "channels": 1 })));
+        assert!(result.is_valid);
+        assert_eq!(
+            result.stability_issues,
+            vec![StabilityIssue {
+                kind: StabilityIssueKind::Experimental,
+                extension: "ra".to_string(),
+            }]
+        );
+
+        assert!(validate(&context("audio", "flac", json!({})))
+            .stability_issues
+            .is_empty());
+    }
+
+    #[test]
+    fn test_explicit_channels() {
+        let result = validate(&context("audio", "mp3", json!({ "channels": 6 })));
+        assert!(!result.is_valid);
+        assert!(result
+            .errors
+            .contains(&"mp3 does not support 6 channels. Supported: 1, 2".to_string()));
+
+        let result = validate(&context("audio", "flac", json!({ "channels": 8 })));
+        assert!(result.is_valid);
+        assert!(result.warnings.is_empty());
+    }
+
+    #[test]
+    fn test_downmix_warning() {
+        let ctx = ValidationContext {
+            input_audio_channels: Some(6),
+            ..context("audio", "mp3", json!({}))
+        };
+        let result = validate(&ctx);
+        assert!(result.is_valid);
+        assert!(result
+            .warnings
+            .iter()
+            .any(|w| w.starts_with("Downmixing 6 channels to 2")));
+
+        let ctx = ValidationContext {
+            input_audio_channels: Some(2),
+            ..context("audio", "mp3", json!({}))
+        };
+        assert!(validate(&ctx).warnings.is_empty());
+    }
+}
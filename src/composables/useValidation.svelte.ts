@@ -72,8 +72,14 @@ export function useValidation() {
             settings: file.settings,
             input_video_codec: mediaInfo.video_streams[0]?.codec || null,
             input_audio_codec: mediaInfo.audio_streams[0]?.codec || null,
+            input_audio_channels: mediaInfo.audio_streams[0]?.channels || null,
             input_width: mediaInfo.video_streams[0]?.width || null,
             input_height: mediaInfo.video_streams[0]?.height || null,
+            input_duration: mediaInfo.duration || null,
+            input_field_order: mediaInfo.video_streams[0]?.field_order || null,
+            input_color_transfer: mediaInfo.video_streams[0]?.color_transfer || null,
+            input_color_primaries: mediaInfo.video_streams[0]?.color_primaries || null,
+            input_rotation: mediaInfo.video_streams[0]?.rotation || null,
             gpu_vendor: gpu.vendor !== 'none' ? gpu.vendor : null,
             gpu_name: gpu.available ? gpu.name : null,
             gpu_available: gpu.available,
@@ -4,16 +4,18 @@ import { APP_CONFIG } from '@/config';
 import { generateOutputPath } from '@/utils';
 import { fileQueueStore } from './fileQueue.svelte';
 import { gpuStore } from './gpu.svelte';
-import type { FileItem, ConversionProgress } from '@/types';
+import type { FileItem, ConversionPhase, ConversionProgress, BatchProgress } from '@/types';
 
 class ConversionStore {
   activeCount = $state(0);
+  batchProgress = $state<BatchProgress | null>(null);
 
   #unlisteners: UnlistenFn[] = [];
   #lastUpdate = new Map<string, number>();
   #onError: ((file: FileItem, error: string) => void) | null = null;
   #abortPipeline = false;
   #activeTaskIds = new Set<string>();
+  #batchId: string | null = null;
 
   get isConverting(): boolean {
     return this.activeCount > 0;
@@ -26,13 +28,13 @@ class ConversionStore {
   async init() {
     const listeners = await Promise.all([
       listen<ConversionProgress>('conversion-progress', (e) => {
-        const { task_id, percent } = e.payload;
+        const { task_id, percent, indeterminate } = e.payload;
         const now = Date.now();
         const lastUpdate = this.#lastUpdate.get(task_id) || 0;
 
         if (
           now - lastUpdate < APP_CONFIG.limits.progressThrottleMs &&
-          percent > 0 &&
+          (percent > 0 || indeterminate) &&
           percent < 100
         ) {
           return;
@@ -45,6 +47,25 @@ class ConversionStore {
         });
       }),
 
+      listen<BatchProgress>('batch-progress', (e) => {
+        if (e.payload.batch_id !== this.#batchId) return;
+        this.batchProgress = e.payload;
+      }),
+
+      listen<{ task_id: string; phase: ConversionPhase }>('conversion-phase', (e) => {
+        const { task_id, phase } = e.payload;
+        fileQueueStore.updateFile(task_id, { status: 'processing', phase });
+      }),
+
+      listen<string>('conversion-encoding', (e) => {
+        fileQueueStore.updateFile(e.payload, { encodingStarted: true });
+      }),
+
+      listen<{ task_id: string; output: string }>('conversion-output-renamed', (e) => {
+        const { task_id, output } = e.payload;
+        fileQueueStore.updateFile(task_id, { outputPath: output });
+      }),
+
       listen<string>('conversion-completed', (e) => {
         const taskId = e.payload;
         this.#lastUpdate.delete(taskId);
@@ -118,15 +139,22 @@ class ConversionStore {
       fileQueueStore.updateFile(file.id, {
         outputPath,
         status: 'processing',
+        encodingStarted: false,
         error: null,
         progress: {
           task_id: file.id,
+          phase: 'probing',
           percent: 0,
+          indeterminate: false,
           fps: null,
           speed: null,
           eta_seconds: null,
           current_time: 0,
           total_time: file.mediaInfo.duration || 1,
+          frame: null,
+          total_frames: null,
+          output_path: outputPath,
+          output_size: null,
         },
       });
 
@@ -141,9 +169,11 @@ class ConversionStore {
       const settings = {
         task_id: file.id,
         quality: file.settings.quality,
-        bitrate: file.settings.bitrate,
+        video_bitrate: file.settings.videoBitrate,
+        audio_bitrate: file.settings.audioBitrate,
         sample_rate: file.settings.sampleRate,
         channels: file.settings.channels,
+        compression_level: file.settings.compressionLevel,
         width: file.settings.width,
         height: file.settings.height,
         fps: file.settings.fps,
@@ -193,7 +223,16 @@ class ConversionStore {
 
     const maxParallel = APP_CONFIG.limits.maxParallelConversions;
 
-    const targetIds = new Set(fileQueueStore.pendingFiles.map(f => f.id));
+    const pending = fileQueueStore.pendingFiles;
+    const targetIds = new Set(pending.map(f => f.id));
+
+    const batchId = `batch-${Date.now()}`;
+    this.#batchId = batchId;
+    this.batchProgress = null;
+    await invoke('start_batch', {
+      batchId,
+      jobs: pending.map(f => ({ task_id: f.id, duration: f.mediaInfo?.duration ?? 0 })),
+    }).catch(() => {});
 
     const worker = async () => {
       while (!this.#abortPipeline) {
@@ -233,9 +272,20 @@ class ConversionStore {
 
   async cancelAll() {
     this.#abortPipeline = true;
+    if (this.#batchId) {
+      invoke('end_batch', { batchId: this.#batchId }).catch(() => {});
+      this.#batchId = null;
+      this.batchProgress = null;
+    }
 
-    const processing = fileQueueStore.files.filter(f => f.status === 'processing');
-    await Promise.all(processing.map(f => this.cancelConversion(f.id)));
+    try {
+      // Each task still reports its own `conversion-cancelled`
+      await invoke('cancel_all_conversions');
+      this.#activeTaskIds.clear();
+    } catch {
+      const processing = fileQueueStore.files.filter(f => f.status === 'processing');
+      await Promise.all(processing.map(f => this.cancelConversion(f.id)));
+    }
   }
 
   destroy() {
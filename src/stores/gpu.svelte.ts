@@ -7,9 +7,11 @@ const DEFAULT_GPU: GpuInfo = {
   name: 'CPU Only',
   encoder_h264: null,
   encoder_h265: null,
+  encoder_av1: null,
   decoder: null,
   available: false,
   encoders: {},
+  vaapi_device: null,
 };
 
 class GpuStore {
@@ -5,10 +5,13 @@ export interface GpuInfo {
   name: string;
   encoder_h264: string | null;
   encoder_h265: string | null;
+  encoder_av1: string | null;
   decoder: string | null;
   available: boolean;
   /** Map of encoder name → actually available on this hardware */
   encoders: Record<string, boolean>;
+  /** DRM render node used for VAAPI encoding (Linux only) */
+  vaapi_device: string | null;
 }
 
 export type MediaType = 'audio' | 'video' | 'unknown';
@@ -19,6 +22,11 @@ export interface VideoStream {
   height: number;
   fps: number;
   bitrate: number | null;
+  field_order: string | null;
+  color_transfer: string | null;
+  color_primaries: string | null;
+  color_space: string | null;
+  rotation: number | null;
 }
 
 export interface AudioStream {
@@ -26,6 +34,20 @@ export interface AudioStream {
   sample_rate: number;
   channels: number;
   bitrate: number | null;
+  language: string | null;
+  title: string | null;
+}
+
+export interface SubtitleStream {
+  codec: string;
+  language: string | null;
+  title: string | null;
+}
+
+export interface Attachment {
+  codec: string | null;
+  filename: string | null;
+  mime_type: string | null;
 }
 
 export interface MediaInfo {
@@ -33,8 +55,15 @@ export interface MediaInfo {
   duration: number;
   file_size: number;
   format_name: string;
+  /** Overall bit rate in bits/s, from the container */
+  bitrate: number | null;
   video_streams: VideoStream[];
   audio_streams: AudioStream[];
+  subtitle_streams: SubtitleStream[];
+  attachments: Attachment[];
+  tags: FileMetadata | null;
+  chapter_count: number;
+  mismatch_warning: string | null;
 }
 
 export type Stability = 'stable' | 'requires_setup' | 'experimental' | 'problematic';
@@ -70,6 +99,42 @@ export interface VideoFormat {
   typical_use: string;
   max_resolution: [number, number] | null;
   special_params: string[];
+  still_image: boolean;
+}
+
+export interface ExtensionEntry {
+  extension: string;
+  name: string;
+  category: Category;
+}
+
+export interface ExtensionGroups {
+  audio: ExtensionEntry[];
+  video: ExtensionEntry[];
+  image: ExtensionEntry[];
+}
+
+export interface SettingRanges {
+  audio_bitrate: [number, number] | null;
+  sample_rates: number[];
+  channels: number[];
+  compression_level: [number, number] | null;
+  crf: [number, number] | null;
+  max_resolution: [number, number] | null;
+}
+
+/** Recommended settings for a format, in the backend's snake_case layout */
+export interface FormatDefaults {
+  settings: {
+    quality: Quality;
+    audio_bitrate: number | null;
+    sample_rate: number | null;
+    channels: number | null;
+    compression_level: number | null;
+    crf: number | null;
+    [key: string]: unknown;
+  };
+  ranges: SettingRanges;
 }
 
 export interface RecommendedFormats {
@@ -78,6 +143,12 @@ export interface RecommendedFormats {
   setup: string[];
   experimental: string[];
   problematic: string[];
+  unavailable: string[];
+}
+
+export interface StabilityIssue {
+  kind: 'problematic' | 'experimental' | 'requires_setup';
+  extension: string;
 }
 
 export interface ValidationResult {
@@ -89,19 +160,67 @@ export interface ValidationResult {
   alternative_codec: string | null;
   can_copy_video: boolean;
   can_copy_audio: boolean;
+  stability_issues: StabilityIssue[];
 }
 
 export type ConversionStatus = 'pending' | 'processing' | 'completed' | 'failed' | 'cancelled';
 export type Quality = 'low' | 'medium' | 'high' | 'ultra' | 'custom';
+export type FilterStrength = 'off' | 'light' | 'medium' | 'strong';
+
+export type ConversionPhase = 'probing' | 'encoding' | 'finalizing';
 
 export interface ConversionProgress {
   task_id: string;
+  phase: ConversionPhase;
   percent: number;
+  indeterminate: boolean;
   fps: number | null;
   speed: number | null;
   eta_seconds: number | null;
   current_time: number;
   total_time: number;
+  frame: number | null;
+  total_frames: number | null;
+  output_path: string | null;
+  output_size: number | null;
+}
+
+/** Predicted output size from `estimate_output_size` */
+export interface SizeEstimate {
+  expected_bytes: number;
+  min_bytes: number;
+  max_bytes: number;
+  low_confidence: boolean;
+}
+
+export interface BatchProgress {
+  batch_id: string;
+  percent: number;
+  current_index: number;
+  finished: number;
+  total: number;
+}
+
+/** Conversion a crash or forced quit interrupted in an earlier run */
+export interface OrphanedTask {
+  task_id: string;
+  output: string;
+  started_at: number;
+  output_size: number | null;
+}
+
+/** Silent stretch of a source, in seconds, from `detect_silence` */
+export interface SilenceRange {
+  start: number;
+  end: number;
+}
+
+/** Saved conversion setup; `settings` uses the backend's snake_case keys */
+export interface Profile {
+  name: string;
+  format: string;
+  settings: Record<string, unknown>;
+  builtin: boolean;
 }
 
 export interface FileMetadata {
@@ -114,16 +233,45 @@ export interface FileMetadata {
 
 export interface FileSettings {
   quality: Quality;
-  bitrate?: number;
+  videoBitrate?: number;
+  audioBitrate?: number;
+  compressionLevel?: number;
   sampleRate?: number;
   channels?: number;
+  channelLayout?: 'mono' | 'stereo' | '5.1';
+  trimSilence?: boolean;
+  denoise?: FilterStrength;
+  sharpen?: FilterStrength;
+  brightness?: number;
+  contrast?: number;
+  saturation?: number;
+  gamma?: number;
   width?: number;
   height?: number;
+  scaleMode?: 'stretch' | 'fit' | 'fill';
+  scalePercent?: number;
+  resolutionPreset?: '480p' | '720p' | '1080p' | '4k';
   fps?: number;
   videoCodec?: string;
+  encoderPreset?: string;
+  pixelFormat?: string;
   audioCodec?: string;
   useGpu: boolean;
+  useGpuDecode?: boolean;
+  useGpuEncode?: boolean;
+  amfUseCqp?: boolean;
+  keyframeInterval?: number;
+  keyframeUnit?: 'frames' | 'seconds';
+  forceKeyframesSeconds?: number;
   extractAudioOnly: boolean;
+  replaceAudioOnly?: boolean;
+  keepAllStreams?: boolean;
+  writeLog?: boolean;
+  partialSuffix?: string;
+  processPriority?: 'low' | 'normal' | 'high';
+  keepOrientation?: boolean;
+  webOptimized?: boolean;
+  fragmented?: boolean;
   copyAudio?: boolean;
   metadata?: FileMetadata;
 }
@@ -138,6 +286,9 @@ export interface FileItem {
   settings: FileSettings;
   status: ConversionStatus;
   progress: ConversionProgress | null;
+  phase?: ConversionPhase;
+  /** FFmpeg has written media; before that progress is indeterminate */
+  encodingStarted?: boolean;
   error: string | null;
   completedAt?: number;
   addedAt: number;
@@ -151,6 +302,35 @@ export interface QueueStats {
   failed: number;
 }
 
+export interface FfmpegInfo {
+  ffmpeg_version: string;
+  ffprobe_version: string | null;
+  enabled_libraries: string[];
+  configuration: string[];
+}
+
+export type CheckStatus = 'ok' | 'warning' | 'error';
+
+export interface DiagnosticCheck {
+  name: string;
+  status: CheckStatus;
+  message: string;
+  details?: string;
+}
+
+export interface MissingEncoder {
+  encoder: string;
+  formats: string[];
+}
+
+/** Toolchain health check from `run_diagnostics` */
+export interface DiagnosticsReport {
+  status: CheckStatus;
+  checks: DiagnosticCheck[];
+  gpus: GpuInfo[];
+  missing_encoders: MissingEncoder[];
+}
+
 export interface SystemInfo {
   os: string;
   platform: string;